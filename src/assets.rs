@@ -0,0 +1,687 @@
+use std::fmt::Display;
+
+use crate::{serialization::DFSerializable, values::{Particle, Potion, Sound, Vector}};
+
+// Sound, particle and potion names DiamondFire accepts in snd, part and pot items,
+// as listed in the plot's item menus. Keep entries sorted within each group.
+
+pub const PITCH: (f64, f64) = (0.0, 2.0);
+pub const VOLUME: (f64, f64) = (0.0, 10.0);
+
+pub struct SoundSpec {
+    pub name: &'static str,
+    pub variants: &'static [&'static str]
+}
+
+const fn sound(name: &'static str) -> SoundSpec {
+    SoundSpec { name, variants: &[] }
+}
+
+pub const SOUNDS: &[SoundSpec] = &[
+    // Note blocks
+    sound("Banjo"), sound("Bass Drum"), sound("Bell"), sound("Bit"), sound("Chime"), sound("Cow Bell"),
+    sound("Didgeridoo"), sound("Flute"), sound("Guitar"), sound("Harp"), sound("Hat"), sound("Iron Xylophone"),
+    sound("Pling"), sound("Snare Drum"), sound("String Bass"), sound("Xylophone"),
+    // Players and items
+    sound("Armor Equip Chain"), sound("Armor Equip Diamond"), sound("Armor Equip Elytra"),
+    sound("Armor Equip Generic"), sound("Armor Equip Gold"), sound("Armor Equip Iron"),
+    sound("Armor Equip Leather"), sound("Armor Equip Netherite"), sound("Armor Equip Turtle"),
+    sound("Arrow Hit Player"), sound("Book Page Turn"), sound("Book Put"), sound("Bottle Empty"),
+    sound("Bottle Fill"), sound("Bucket Empty"), sound("Bucket Empty Lava"), sound("Bucket Fill"),
+    sound("Bucket Fill Lava"), sound("Bundle Drop Contents"), sound("Bundle Insert"), sound("Bundle Remove One"),
+    sound("Crossbow Hit"), sound("Crossbow Loading End"), sound("Crossbow Loading Start"), sound("Crossbow Shoot"),
+    sound("Elytra Flying"), sound("Experience Orb Pickup"), sound("Firecharge Use"), sound("Flintandsteel Use"),
+    sound("Generic Drink"), sound("Generic Eat"), sound("Generic Splash"), sound("Generic Swim"),
+    SoundSpec { name: "Goat Horn", variants: &["admire", "call", "dream", "feel", "ponder", "seek", "sing", "yearn"] },
+    sound("Hoe Till"), sound("Honey Bottle Drink"), sound("Item Break"), sound("Item Pickup"), sound("Level Up"),
+    sound("Player Attack Crit"), sound("Player Attack Knockback"), sound("Player Attack Nodamage"),
+    sound("Player Attack Strong"), sound("Player Attack Sweep"), sound("Player Attack Weak"),
+    sound("Player Big Fall"), sound("Player Breath"), sound("Player Burp"), sound("Player Death"),
+    sound("Player Hurt"), sound("Player Hurt Drown"), sound("Player Hurt Freeze"), sound("Player Hurt On Fire"),
+    sound("Player Hurt Sweet Berry Bush"), sound("Player Small Fall"), sound("Player Splash"), sound("Player Swim"),
+    sound("Shield Block"), sound("Shield Break"), sound("Shovel Flatten"), sound("Spyglass Use"),
+    sound("Totem Use"), sound("Trident Hit"), sound("Trident Return"), sound("Trident Riptide"),
+    sound("Trident Throw"), sound("Trident Thunder"),
+    // Blocks
+    sound("Amethyst Block Chime"), sound("Anvil Break"), sound("Anvil Destroy"), sound("Anvil Land"),
+    sound("Anvil Place"), sound("Anvil Use"), sound("Barrel Close"), sound("Barrel Open"), sound("Beacon Activate"),
+    sound("Beacon Ambient"), sound("Beacon Deactivate"), sound("Beacon Power Select"), sound("Bell Resonate"),
+    sound("Bell Use"), sound("Brewing Stand Brew"), sound("Bubble Column Bubble Pop"), sound("Campfire Crackle"),
+    sound("Chest Close"), sound("Chest Locked"), sound("Chest Open"), sound("Composter Fill"),
+    sound("Composter Ready"), sound("Conduit Activate"), sound("Conduit Deactivate"), sound("Copper Bulb Turn Off"),
+    sound("Copper Bulb Turn On"), sound("Crafter Craft"), sound("Decorated Pot Shatter"),
+    sound("Dispenser Dispense"), sound("Dispenser Fail"), sound("Door Close"), sound("Door Open"),
+    sound("End Portal Frame Fill"), sound("End Portal Spawn"), sound("Ender Chest Close"),
+    sound("Ender Chest Open"), sound("Fence Gate Close"), sound("Fence Gate Open"), sound("Fire Ambient"),
+    sound("Fire Extinguish"), sound("Furnace Fire Crackle"), sound("Glass Break"), sound("Grass Break"),
+    sound("Gravel Break"), sound("Grindstone Use"), sound("Lava Ambient"), sound("Lava Pop"), sound("Lever Click"),
+    sound("Note Block Imitate Creeper"), sound("Note Block Imitate Ender Dragon"),
+    sound("Note Block Imitate Skeleton"), sound("Note Block Imitate Wither Skeleton"),
+    sound("Note Block Imitate Zombie"), sound("Piston Contract"), sound("Piston Extend"), sound("Portal Ambient"),
+    sound("Portal Travel"), sound("Portal Trigger"), sound("Pressure Plate Click Off"),
+    sound("Pressure Plate Click On"), sound("Respawn Anchor Charge"), sound("Respawn Anchor Deplete"),
+    sound("Respawn Anchor Set Spawn"), sound("Sand Break"), sound("Sculk Sensor Clicking"),
+    sound("Sculk Shrieker Shriek"), sound("Shulker Box Close"), sound("Shulker Box Open"),
+    sound("Smithing Table Use"), sound("Stone Break"), sound("Stone Button Click Off"),
+    sound("Stone Button Click On"), sound("Trapdoor Close"), sound("Trapdoor Open"),
+    sound("Trial Spawner Spawn Mob"), sound("Tripwire Attach"), sound("Tripwire Click Off"),
+    sound("Tripwire Click On"), sound("UI Button Click"), sound("UI Cartography Table Take Result"),
+    sound("UI Loom Take Result"), sound("UI Stonecutter Take Result"), sound("UI Toast Challenge Complete"),
+    sound("UI Toast In"), sound("UI Toast Out"), sound("Vault Open Shutter"), sound("Water Ambient"),
+    sound("Wood Break"), sound("Wooden Button Click Off"), sound("Wooden Button Click On"), sound("Wool Break"),
+    // Entities
+    sound("Allay Ambient With Item"), sound("Allay Ambient Without Item"), sound("Allay Death"),
+    sound("Allay Hurt"), sound("Allay Item Given"), sound("Allay Item Taken"), sound("Arrow Hit"),
+    sound("Arrow Shoot"), sound("Axolotl Attack"), sound("Axolotl Death"), sound("Axolotl Hurt"),
+    sound("Axolotl Idle Air"), sound("Axolotl Idle Water"), sound("Axolotl Splash"), sound("Axolotl Swim"),
+    sound("Bat Ambient"), sound("Bat Death"), sound("Bat Hurt"), sound("Bat Loop"), sound("Bat Takeoff"),
+    sound("Bee Death"), sound("Bee Hurt"), sound("Bee Loop"), sound("Bee Loop Aggressive"), sound("Bee Pollinate"),
+    sound("Bee Sting"), sound("Blaze Ambient"), sound("Blaze Burn"), sound("Blaze Death"), sound("Blaze Hurt"),
+    sound("Blaze Shoot"), sound("Breeze Death"), sound("Breeze Hurt"), sound("Breeze Idle Air"),
+    sound("Breeze Idle Ground"), sound("Breeze Jump"), sound("Breeze Land"), sound("Breeze Shoot"),
+    sound("Breeze Wind Burst"), sound("Camel Ambient"), sound("Camel Dash"), sound("Camel Death"),
+    sound("Camel Eat"), sound("Camel Hurt"), sound("Camel Sit"), sound("Camel Stand"), sound("Cat Ambient"),
+    sound("Cat Death"), sound("Cat Eat"), sound("Cat Hiss"), sound("Cat Hurt"), sound("Cat Purr"),
+    sound("Cat Purreow"), sound("Chicken Ambient"), sound("Chicken Death"), sound("Chicken Egg"),
+    sound("Chicken Hurt"), sound("Chicken Step"), sound("Cow Ambient"), sound("Cow Death"), sound("Cow Hurt"),
+    sound("Cow Milk"), sound("Cow Step"), sound("Creeper Death"), sound("Creeper Hurt"), sound("Creeper Primed"),
+    sound("Dolphin Ambient"), sound("Dolphin Attack"), sound("Dolphin Death"), sound("Dolphin Hurt"),
+    sound("Dolphin Jump"), sound("Dolphin Play"), sound("Dolphin Splash"), sound("Dolphin Swim"),
+    sound("Donkey Ambient"), sound("Donkey Angry"), sound("Donkey Chest"), sound("Donkey Death"),
+    sound("Donkey Eat"), sound("Donkey Hurt"), sound("Drowned Ambient"), sound("Drowned Death"),
+    sound("Drowned Hurt"), sound("Drowned Shoot"), sound("Drowned Step"), sound("Drowned Swim"), sound("Egg Throw"),
+    sound("Elder Guardian Ambient"), sound("Elder Guardian Curse"), sound("Elder Guardian Death"),
+    sound("Elder Guardian Flop"), sound("Elder Guardian Hurt"), sound("Ender Dragon Ambient"),
+    sound("Ender Dragon Death"), sound("Ender Dragon Flap"), sound("Ender Dragon Growl"),
+    sound("Ender Dragon Hurt"), sound("Ender Dragon Shoot"), sound("Ender Eye Death"), sound("Ender Eye Launch"),
+    sound("Ender Pearl Throw"), sound("Enderman Ambient"), sound("Enderman Death"), sound("Enderman Hurt"),
+    sound("Enderman Scream"), sound("Enderman Stare"), sound("Enderman Teleport"), sound("Endermite Ambient"),
+    sound("Endermite Death"), sound("Endermite Hurt"), sound("Endermite Step"), sound("Evoker Ambient"),
+    sound("Evoker Cast Spell"), sound("Evoker Celebrate"), sound("Evoker Death"), sound("Evoker Fangs Attack"),
+    sound("Evoker Hurt"), sound("Evoker Prepare Attack"), sound("Evoker Prepare Summon"),
+    sound("Evoker Prepare Wololo"), sound("Experience Bottle Throw"), sound("Firework Blast"),
+    sound("Firework Blast Far"), sound("Firework Large Blast"), sound("Firework Large Blast Far"),
+    sound("Firework Launch"), sound("Firework Shoot"), sound("Firework Twinkle"), sound("Firework Twinkle Far"),
+    sound("Fishing Bobber Retrieve"), sound("Fishing Bobber Splash"), sound("Fishing Bobber Throw"),
+    sound("Fox Aggro"), sound("Fox Ambient"), sound("Fox Bite"), sound("Fox Death"), sound("Fox Eat"),
+    sound("Fox Hurt"), sound("Fox Screech"), sound("Fox Sleep"), sound("Fox Sniff"), sound("Fox Spit"),
+    sound("Fox Teleport"), sound("Frog Ambient"), sound("Frog Death"), sound("Frog Eat"), sound("Frog Hurt"),
+    sound("Frog Lay Spawn"), sound("Frog Long Jump"), sound("Frog Step"), sound("Frog Tongue"),
+    sound("Generic Big Fall"), sound("Generic Burn"), sound("Generic Death"), sound("Generic Explode"),
+    sound("Generic Extinguish Fire"), sound("Generic Hurt"), sound("Generic Small Fall"), sound("Ghast Ambient"),
+    sound("Ghast Death"), sound("Ghast Hurt"), sound("Ghast Scream"), sound("Ghast Shoot"), sound("Ghast Warn"),
+    sound("Goat Ambient"), sound("Goat Death"), sound("Goat Eat"), sound("Goat Hurt"), sound("Goat Long Jump"),
+    sound("Goat Milk"), sound("Goat Prepare Ram"), sound("Goat Ram Impact"), sound("Goat Screaming Ambient"),
+    sound("Goat Step"), sound("Guardian Ambient"), sound("Guardian Attack"), sound("Guardian Death"),
+    sound("Guardian Flop"), sound("Guardian Hurt"), sound("Hoglin Ambient"), sound("Hoglin Angry"),
+    sound("Hoglin Attack"), sound("Hoglin Converted To Zombified"), sound("Hoglin Death"), sound("Hoglin Hurt"),
+    sound("Hoglin Retreat"), sound("Hoglin Step"), sound("Horse Ambient"), sound("Horse Angry"),
+    sound("Horse Armor"), sound("Horse Breathe"), sound("Horse Death"), sound("Horse Eat"), sound("Horse Gallop"),
+    sound("Horse Hurt"), sound("Horse Jump"), sound("Horse Land"), sound("Horse Saddle"), sound("Horse Step"),
+    sound("Horse Step Wood"), sound("Husk Ambient"), sound("Husk Converted To Zombie"), sound("Husk Death"),
+    sound("Husk Hurt"), sound("Husk Step"), sound("Iron Golem Attack"), sound("Iron Golem Damage"),
+    sound("Iron Golem Death"), sound("Iron Golem Hurt"), sound("Iron Golem Repair"), sound("Iron Golem Step"),
+    sound("Item Frame Add Item"), sound("Item Frame Break"), sound("Item Frame Remove Item"),
+    sound("Item Frame Rotate Item"), sound("Leash Knot Break"), sound("Leash Knot Place"),
+    sound("Lightning Bolt Impact"), sound("Lightning Bolt Thunder"), sound("Llama Ambient"), sound("Llama Angry"),
+    sound("Llama Chest"), sound("Llama Death"), sound("Llama Eat"), sound("Llama Hurt"), sound("Llama Spit"),
+    sound("Llama Step"), sound("Llama Swag"), sound("Magma Cube Death"), sound("Magma Cube Hurt"),
+    sound("Magma Cube Jump"), sound("Magma Cube Squish"), sound("Minecart Riding"), sound("Mooshroom Convert"),
+    sound("Mooshroom Eat"), sound("Mooshroom Milk"), sound("Mooshroom Shear"), sound("Mooshroom Suspicious Milk"),
+    sound("Ocelot Ambient"), sound("Ocelot Death"), sound("Ocelot Hurt"), sound("Painting Break"),
+    sound("Painting Place"), sound("Panda Aggressive Ambient"), sound("Panda Ambient"), sound("Panda Bite"),
+    sound("Panda Cant Breed"), sound("Panda Death"), sound("Panda Eat"), sound("Panda Hurt"),
+    sound("Panda Pre Sneeze"), sound("Panda Sneeze"), sound("Panda Step"), sound("Panda Worried Ambient"),
+    sound("Parrot Ambient"), sound("Parrot Death"), sound("Parrot Eat"), sound("Parrot Fly"), sound("Parrot Hurt"),
+    sound("Parrot Step"), sound("Phantom Ambient"), sound("Phantom Bite"), sound("Phantom Death"),
+    sound("Phantom Flap"), sound("Phantom Hurt"), sound("Phantom Swoop"), sound("Pig Ambient"), sound("Pig Death"),
+    sound("Pig Hurt"), sound("Pig Saddle"), sound("Pig Step"), sound("Piglin Admiring Item"),
+    sound("Piglin Ambient"), sound("Piglin Angry"), sound("Piglin Brute Ambient"), sound("Piglin Brute Angry"),
+    sound("Piglin Brute Converted To Zombified"), sound("Piglin Brute Death"), sound("Piglin Brute Hurt"),
+    sound("Piglin Brute Step"), sound("Piglin Celebrate"), sound("Piglin Converted To Zombified"),
+    sound("Piglin Death"), sound("Piglin Hurt"), sound("Piglin Jealous"), sound("Piglin Retreat"),
+    sound("Piglin Step"), sound("Pillager Ambient"), sound("Pillager Celebrate"), sound("Pillager Death"),
+    sound("Pillager Hurt"), sound("Polar Bear Ambient"), sound("Polar Bear Ambient Baby"),
+    sound("Polar Bear Death"), sound("Polar Bear Hurt"), sound("Polar Bear Step"), sound("Polar Bear Warning"),
+    sound("Rabbit Ambient"), sound("Rabbit Attack"), sound("Rabbit Death"), sound("Rabbit Hurt"),
+    sound("Rabbit Jump"), sound("Ravager Ambient"), sound("Ravager Attack"), sound("Ravager Celebrate"),
+    sound("Ravager Death"), sound("Ravager Hurt"), sound("Ravager Roar"), sound("Ravager Step"),
+    sound("Ravager Stunned"), sound("Sheep Ambient"), sound("Sheep Death"), sound("Sheep Hurt"),
+    sound("Sheep Shear"), sound("Sheep Step"), sound("Shulker Ambient"), sound("Shulker Close"),
+    sound("Shulker Death"), sound("Shulker Hurt"), sound("Shulker Open"), sound("Shulker Shoot"),
+    sound("Shulker Teleport"), sound("Silverfish Ambient"), sound("Silverfish Death"), sound("Silverfish Hurt"),
+    sound("Silverfish Step"), sound("Skeleton Ambient"), sound("Skeleton Converted To Stray"),
+    sound("Skeleton Death"), sound("Skeleton Hurt"), sound("Skeleton Shoot"), sound("Skeleton Step"),
+    sound("Slime Attack"), sound("Slime Death"), sound("Slime Hurt"), sound("Slime Jump"), sound("Slime Squish"),
+    sound("Sniffer Death"), sound("Sniffer Digging"), sound("Sniffer Drop Seed"), sound("Sniffer Eat"),
+    sound("Sniffer Happy"), sound("Sniffer Hurt"), sound("Sniffer Idle"), sound("Sniffer Scenting"),
+    sound("Sniffer Searching"), sound("Sniffer Sniffing"), sound("Sniffer Step"), sound("Snow Golem Ambient"),
+    sound("Snow Golem Death"), sound("Snow Golem Hurt"), sound("Snow Golem Shear"), sound("Snow Golem Shoot"),
+    sound("Snowball Throw"), sound("Spider Ambient"), sound("Spider Death"), sound("Spider Hurt"),
+    sound("Spider Step"), sound("Splash Potion Break"), sound("Splash Potion Throw"), sound("Squid Ambient"),
+    sound("Squid Death"), sound("Squid Hurt"), sound("Squid Squirt"), sound("Stray Ambient"), sound("Stray Death"),
+    sound("Stray Hurt"), sound("Stray Step"), sound("Strider Ambient"), sound("Strider Death"),
+    sound("Strider Eat"), sound("Strider Happy"), sound("Strider Hurt"), sound("Strider Retreat"),
+    sound("Strider Saddle"), sound("Strider Step"), sound("Strider Step Lava"), sound("TNT Primed"),
+    sound("Tadpole Death"), sound("Tadpole Flop"), sound("Tadpole Grow Up"), sound("Tadpole Hurt"),
+    sound("Turtle Ambient Land"), sound("Turtle Death"), sound("Turtle Egg Break"), sound("Turtle Egg Crack"),
+    sound("Turtle Egg Hatch"), sound("Turtle Hurt"), sound("Turtle Lay Egg"), sound("Turtle Shamble"),
+    sound("Turtle Swim"), sound("Vex Ambient"), sound("Vex Charge"), sound("Vex Death"), sound("Vex Hurt"),
+    sound("Villager Ambient"), sound("Villager Celebrate"), sound("Villager Death"), sound("Villager Hurt"),
+    sound("Villager No"), sound("Villager Trade"),
+    SoundSpec { name: "Villager Work", variants: &["armorer", "butcher", "cartographer", "cleric", "farmer", "fisherman", "fletcher", "leatherworker", "librarian", "mason", "shepherd", "toolsmith", "weaponsmith"] },
+    sound("Villager Yes"), sound("Vindicator Ambient"), sound("Vindicator Celebrate"), sound("Vindicator Death"),
+    sound("Vindicator Hurt"), sound("Wandering Trader Ambient"), sound("Wandering Trader Death"),
+    sound("Wandering Trader Disappeared"), sound("Wandering Trader Drink Milk"),
+    sound("Wandering Trader Drink Potion"), sound("Wandering Trader Hurt"), sound("Wandering Trader No"),
+    sound("Wandering Trader Reappeared"), sound("Wandering Trader Trade"), sound("Wandering Trader Yes"),
+    sound("Warden Agitated"), sound("Warden Ambient"), sound("Warden Angry"), sound("Warden Attack Impact"),
+    sound("Warden Death"), sound("Warden Dig"), sound("Warden Emerge"), sound("Warden Heartbeat"),
+    sound("Warden Hurt"), sound("Warden Listening"), sound("Warden Listening Angry"), sound("Warden Nearby Close"),
+    sound("Warden Nearby Closer"), sound("Warden Nearby Closest"), sound("Warden Roar"), sound("Warden Sniff"),
+    sound("Warden Sonic Boom"), sound("Warden Sonic Charge"), sound("Warden Step"), sound("Warden Tendril Clicks"),
+    sound("Wind Charge Throw"), sound("Wind Charge Wind Burst"), sound("Witch Ambient"), sound("Witch Celebrate"),
+    sound("Witch Death"), sound("Witch Drink"), sound("Witch Hurt"), sound("Witch Throw"), sound("Wither Ambient"),
+    sound("Wither Break Block"), sound("Wither Death"), sound("Wither Hurt"), sound("Wither Shoot"),
+    sound("Wither Skeleton Ambient"), sound("Wither Skeleton Death"), sound("Wither Skeleton Hurt"),
+    sound("Wither Skeleton Step"), sound("Wither Spawn"), sound("Wolf Ambient"), sound("Wolf Death"),
+    sound("Wolf Growl"), sound("Wolf Howl"), sound("Wolf Hurt"), sound("Wolf Pant"), sound("Wolf Shake"),
+    sound("Wolf Step"), sound("Wolf Whine"), sound("Zoglin Ambient"), sound("Zoglin Angry"), sound("Zoglin Attack"),
+    sound("Zoglin Death"), sound("Zoglin Hurt"), sound("Zoglin Step"), sound("Zombie Ambient"),
+    sound("Zombie Attack Iron Door"), sound("Zombie Attack Wooden Door"), sound("Zombie Break Wooden Door"),
+    sound("Zombie Converted To Drowned"), sound("Zombie Death"), sound("Zombie Destroy Egg"), sound("Zombie Hurt"),
+    sound("Zombie Infect"), sound("Zombie Step"), sound("Zombie Villager Ambient"),
+    sound("Zombie Villager Converted"), sound("Zombie Villager Cure"), sound("Zombie Villager Death"),
+    sound("Zombie Villager Hurt"), sound("Zombie Villager Step"), sound("Zombified Piglin Ambient"),
+    sound("Zombified Piglin Angry"), sound("Zombified Piglin Death"), sound("Zombified Piglin Hurt"),
+    // Ambience and music
+    sound("Ambient Cave"), sound("Ambient Underwater Enter"), sound("Ambient Underwater Exit"),
+    sound("Ambient Underwater Loop"), sound("Music Disc 11"), sound("Music Disc 13"), sound("Music Disc Blocks"),
+    sound("Music Disc Cat"), sound("Music Disc Chirp"), sound("Music Disc Far"), sound("Music Disc Mall"),
+    sound("Music Disc Mellohi"), sound("Music Disc Otherside"), sound("Music Disc Pigstep"),
+    sound("Music Disc Relic"), sound("Music Disc Stal"), sound("Music Disc Strad"), sound("Music Disc Wait"),
+    sound("Music Disc Ward"), sound("Weather Rain"), sound("Weather Rain Above")
+];
+
+// The data tab fields a particle can be given, beyond amount and spread
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ParticleField {
+    Motion, Color, Material, Size, Roll
+}
+impl ParticleField {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParticleField::Motion => "motion",
+            ParticleField::Color => "color",
+            ParticleField::Material => "material",
+            ParticleField::Size => "size",
+            ParticleField::Roll => "roll",
+        }
+    }
+}
+impl Display for ParticleField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+pub struct ParticleSpec {
+    pub name: &'static str,
+    pub fields: &'static [ParticleField]
+}
+
+use ParticleField::*;
+const fn particle(name: &'static str, fields: &'static [ParticleField]) -> ParticleSpec {
+    ParticleSpec { name, fields }
+}
+
+pub const PARTICLES: &[ParticleSpec] = &[
+    particle("Angry Villager", &[]), particle("Ash", &[]), particle("Block", &[Motion, Material]),
+    particle("Block Marker", &[Material]), particle("Bubble", &[Motion]), particle("Bubble Column Up", &[Motion]),
+    particle("Bubble Pop", &[Motion]), particle("Campfire Cosy Smoke", &[Motion]),
+    particle("Campfire Signal Smoke", &[Motion]), particle("Cherry Leaves", &[]), particle("Cloud", &[Motion]),
+    particle("Composter", &[]), particle("Crimson Spore", &[]), particle("Crit", &[Motion]),
+    particle("Current Down", &[]), particle("Damage Indicator", &[Motion]), particle("Dolphin", &[]),
+    particle("Dragon Breath", &[Motion]), particle("Dripping Dripstone Lava", &[]),
+    particle("Dripping Dripstone Water", &[]), particle("Dripping Honey", &[]), particle("Dripping Lava", &[]),
+    particle("Dripping Obsidian Tear", &[]), particle("Dripping Water", &[]), particle("Dust", &[Color, Size]),
+    particle("Dust Color Transition", &[Color, Size]), particle("Dust Pillar", &[Motion, Material]),
+    particle("Dust Plume", &[Motion]), particle("Effect", &[]), particle("Egg Crack", &[]),
+    particle("Elder Guardian", &[]), particle("Electric Spark", &[Motion]), particle("Enchant", &[Motion]),
+    particle("Enchanted Hit", &[Motion]), particle("End Rod", &[Motion]), particle("Entity Effect", &[Color]),
+    particle("Explosion", &[Size]), particle("Explosion Emitter", &[]), particle("Falling Dripstone Lava", &[]),
+    particle("Falling Dripstone Water", &[]), particle("Falling Dust", &[Material]), particle("Falling Honey", &[]),
+    particle("Falling Lava", &[]), particle("Falling Nectar", &[]), particle("Falling Obsidian Tear", &[]),
+    particle("Falling Spore Blossom", &[]), particle("Falling Water", &[]), particle("Firework", &[Motion]),
+    particle("Fishing", &[Motion]), particle("Flame", &[Motion]), particle("Glow", &[]),
+    particle("Glow Squid Ink", &[Motion]), particle("Gust", &[]), particle("Gust Emitter Large", &[]),
+    particle("Gust Emitter Small", &[]), particle("Happy Villager", &[]), particle("Heart", &[]),
+    particle("Infested", &[]), particle("Instant Effect", &[]), particle("Item", &[Motion, Material]),
+    particle("Item Cobweb", &[]), particle("Item Slime", &[]), particle("Item Snowball", &[]),
+    particle("Landing Honey", &[]), particle("Landing Lava", &[]), particle("Landing Obsidian Tear", &[]),
+    particle("Large Smoke", &[Motion]), particle("Lava", &[]), particle("Mycelium", &[]),
+    particle("Nautilus", &[Motion]), particle("Note", &[Color]), particle("Ominous Spawning", &[Motion]),
+    particle("Poof", &[Motion]), particle("Portal", &[Motion]), particle("Raid Omen", &[]), particle("Rain", &[]),
+    particle("Reverse Portal", &[Motion]), particle("Scrape", &[Motion]), particle("Sculk Charge", &[Motion, Roll]),
+    particle("Sculk Charge Pop", &[Motion]), particle("Sculk Soul", &[Motion]), particle("Shriek", &[]),
+    particle("Small Flame", &[Motion]), particle("Small Gust", &[]), particle("Smoke", &[Motion]),
+    particle("Sneeze", &[Motion]), particle("Snowflake", &[Motion]), particle("Sonic Boom", &[]),
+    particle("Soul", &[Motion]), particle("Soul Fire Flame", &[Motion]), particle("Spit", &[Motion]),
+    particle("Splash", &[]), particle("Spore Blossom Air", &[]), particle("Squid Ink", &[Motion]),
+    particle("Sweep Attack", &[Size]), particle("Totem of Undying", &[Motion]), particle("Trial Omen", &[]),
+    particle("Trial Spawner Detection", &[Motion]), particle("Trial Spawner Detection Ominous", &[Motion]),
+    particle("Underwater", &[]), particle("Vault Connection", &[Motion]), particle("Vibration", &[]),
+    particle("Warped Spore", &[]), particle("Wax Off", &[Motion]), particle("Wax On", &[Motion]),
+    particle("White Ash", &[]), particle("White Smoke", &[Motion]), particle("Witch", &[])
+];
+
+// Builds PotionEffect, its display names and the list of known effects from one table
+macro_rules! potion_effects {
+    ($($variant:ident => $name:literal),* $(,)?) => {
+        #[derive(Clone, Debug)]
+        pub enum PotionEffect {
+            $($variant,)*
+            // Effects added to DiamondFire after this catalog
+            Other(String)
+        }
+        pub const POTION_EFFECTS: &[PotionEffect] = &[$(PotionEffect::$variant),*];
+        impl PotionEffect {
+            // Takes the display name, as in pot items
+            pub fn parse(s: &str) -> Option<Self> {
+                match s {
+                    $($name => Some(PotionEffect::$variant),)*
+                    _ => None
+                }
+            }
+            pub fn name(&self) -> &str {
+                match self {
+                    $(PotionEffect::$variant => $name,)*
+                    PotionEffect::Other(name) => name
+                }
+            }
+        }
+    };
+}
+
+potion_effects! {
+    // Beneficial
+    Absorption => "Absorption", ConduitPower => "Conduit Power", DolphinGrace => "Dolphin's Grace",
+    FireResistance => "Fire Resistance", Haste => "Haste", HealthBoost => "Health Boost",
+    HeroOfTheVillage => "Hero of the Village", InstantHealth => "Instant Health", Invisibility => "Invisibility",
+    JumpBoost => "Jump Boost", Luck => "Luck", NightVision => "Night Vision", Regeneration => "Regeneration",
+    Resistance => "Resistance", Saturation => "Saturation", SlowFalling => "Slow Falling", Speed => "Speed",
+    Strength => "Strength", WaterBreathing => "Water Breathing",
+    // Harmful
+    BadLuck => "Bad Luck", BadOmen => "Bad Omen", Blindness => "Blindness", Darkness => "Darkness",
+    Glowing => "Glowing", Hunger => "Hunger", Infested => "Infested", InstantDamage => "Instant Damage",
+    Levitation => "Levitation", MiningFatigue => "Mining Fatigue", Nausea => "Nausea", Oozing => "Oozing",
+    Poison => "Poison", RaidOmen => "Raid Omen", Slowness => "Slowness", TrialOmen => "Trial Omen",
+    Weakness => "Weakness", Weaving => "Weaving", WindCharged => "Wind Charged", Wither => "Wither",
+}
+
+impl PotionEffect {
+    // Falls back to Other for names this catalog doesn't know
+    pub fn from_name(s: &str) -> Self {
+        PotionEffect::parse(s).unwrap_or_else(|| PotionEffect::Other(String::from(s)))
+    }
+    // Turns an Other that names a known effect into that effect
+    pub fn normalized(self) -> Self {
+        match self {
+            PotionEffect::Other(name) => PotionEffect::from_name(&name),
+            e => e
+        }
+    }
+}
+// Effects are the same when DiamondFire sees the same name, however they were built
+impl PartialEq for PotionEffect {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+impl Eq for PotionEffect {}
+impl DFSerializable for PotionEffect {
+    fn serialize(&self) -> String {
+        String::from(self.name())
+    }
+}
+
+// Durations are in ticks; DiamondFire shows its longest duration as infinite
+pub const INFINITE: u64 = 1_000_000;
+pub const AMPLIFIER: (i16, i16) = (-255, 255);
+
+pub fn sound_spec(name: &str) -> Option<&'static SoundSpec> {
+    SOUNDS.iter().find(|s| s.name == name)
+}
+pub fn particle_spec(name: &str) -> Option<&'static ParticleSpec> {
+    PARTICLES.iter().find(|p| p.name == name)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.to_lowercase().chars().collect(), b.to_lowercase().chars().collect());
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut diagonal = row[0];
+        row[0] = i;
+        for j in 1..=b.len() {
+            let above = row[j];
+            row[j] = (diagonal + (a[i - 1] != b[j - 1]) as usize).min(row[j - 1] + 1).min(above + 1);
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+// The closest name, if it's close enough to be a typo
+pub fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = (name.chars().count() / 3).max(2);
+    candidates.into_iter()
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|(d, _)| *d <= limit)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AssetError {
+    UnknownSound { name: String, suggestion: Option<&'static str> },
+    UnknownVariant { sound: String, variant: String, suggestion: Option<&'static str> },
+    UnknownParticle { name: String, suggestion: Option<&'static str> },
+    Range { field: &'static str, value: f64, min: f64, max: f64 },
+    UnknownEffect { name: String, suggestion: Option<&'static str> },
+    Field { particle: String, field: ParticleField }
+}
+impl AssetError {
+    pub fn suggestion(&self) -> Option<&'static str> {
+        match self {
+            AssetError::UnknownSound { suggestion, .. }
+            | AssetError::UnknownVariant { suggestion, .. }
+            | AssetError::UnknownParticle { suggestion, .. }
+            | AssetError::UnknownEffect { suggestion, .. } => *suggestion,
+            _ => None
+        }
+    }
+}
+impl Display for AssetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssetError::UnknownSound { name, .. } => write!(f, "unknown sound {name}")?,
+            AssetError::UnknownVariant { sound, variant, .. } => write!(f, "{sound} has no variant {variant}")?,
+            AssetError::UnknownParticle { name, .. } => write!(f, "unknown particle {name}")?,
+            AssetError::UnknownEffect { name, .. } => write!(f, "unknown potion effect {name}")?,
+            AssetError::Range { field, value, min, max } => write!(f, "{field} {value} is outside {min}..{max}")?,
+            AssetError::Field { particle, field } => write!(f, "{particle} doesn't take a {field}")?,
+        }
+        match self.suggestion() {
+            Some(s) => write!(f, " (did you mean {s}?)"),
+            None => Ok(())
+        }
+    }
+}
+
+fn in_range(field: &'static str, value: f64, (min, max): (f64, f64)) -> Result<(), AssetError> {
+    if (min..=max).contains(&value) { Ok(()) } else { Err(AssetError::Range { field, value, min, max }) }
+}
+
+impl Sound {
+    // Checks the name, variant and ranges against the catalog
+    pub fn new(sound: &str, variant: Option<&str>, pitch: f64, volume: f64) -> Result<Self, AssetError> {
+        let sound = Sound { sound: String::from(sound), variant: variant.map(String::from), pitch, volume };
+        sound.check()?;
+        Ok(sound)
+    }
+    pub fn check(&self) -> Result<&'static SoundSpec, AssetError> {
+        let spec = sound_spec(&self.sound).ok_or_else(|| AssetError::UnknownSound {
+            name: self.sound.clone(),
+            suggestion: suggest(&self.sound, SOUNDS.iter().map(|s| s.name))
+        })?;
+        if let Some(variant) = &self.variant {
+            if !spec.variants.contains(&variant.as_str()) {
+                return Err(AssetError::UnknownVariant {
+                    sound: self.sound.clone(),
+                    variant: variant.clone(),
+                    suggestion: suggest(variant, spec.variants.iter().copied())
+                });
+            }
+        }
+        in_range("pitch", self.pitch, PITCH)?;
+        in_range("volume", self.volume, VOLUME)?;
+        Ok(spec)
+    }
+}
+
+impl Particle {
+    // One particle with no spread or data; fill in the data with the setters and check it
+    pub fn new(name: &str) -> Result<Self, AssetError> {
+        let spec = particle_spec(name).ok_or_else(|| AssetError::UnknownParticle {
+            name: String::from(name),
+            suggestion: suggest(name, PARTICLES.iter().map(|p| p.name))
+        })?;
+        Ok(Particle {
+            particle: String::from(spec.name),
+            amount: 1,
+            color: None,
+            variation_color: None,
+            material: None,
+            motion: None,
+            variation_motion: None,
+            roll: None,
+            size: None,
+            variation_size: None,
+            spread: (0.0, 0.0)
+        })
+    }
+    pub fn amount(mut self, amount: u64) -> Self {
+        self.amount = amount;
+        self
+    }
+    pub fn spread(mut self, horizontal: f64, vertical: f64) -> Self {
+        self.spread = (horizontal, vertical);
+        self
+    }
+    pub fn motion(mut self, motion: Vector, variation: f64) -> Self {
+        self.motion = Some(motion);
+        self.variation_motion = Some(variation);
+        self
+    }
+    pub fn color(mut self, (r, g, b): (u8, u8, u8), variation: f64) -> Self {
+        self.color = Some((r, g, b));
+        self.variation_color = Some(variation);
+        self
+    }
+    pub fn material(mut self, material: &str) -> Self {
+        self.material = Some(String::from(material));
+        self
+    }
+    pub fn size(mut self, size: f64, variation: f64) -> Self {
+        self.size = Some(size);
+        self.variation_size = Some(variation);
+        self
+    }
+    pub fn roll(mut self, roll: f64) -> Self {
+        self.roll = Some(roll);
+        self
+    }
+    // The data fields that have been set
+    pub fn fields(&self) -> Vec<ParticleField> {
+        [
+            (Motion, self.motion.is_some() || self.variation_motion.is_some()),
+            (Color, self.color.is_some() || self.variation_color.is_some()),
+            (Material, self.material.is_some()),
+            (Size, self.size.is_some() || self.variation_size.is_some()),
+            (Roll, self.roll.is_some())
+        ].into_iter().filter(|(_, set)| *set).map(|(f, _)| f).collect()
+    }
+    pub fn check(&self) -> Result<&'static ParticleSpec, AssetError> {
+        let spec = particle_spec(&self.particle).ok_or_else(|| AssetError::UnknownParticle {
+            name: self.particle.clone(),
+            suggestion: suggest(&self.particle, PARTICLES.iter().map(|p| p.name))
+        })?;
+        if let Some(field) = self.fields().into_iter().find(|f| !spec.fields.contains(f)) {
+            return Err(AssetError::Field { particle: self.particle.clone(), field });
+        }
+        for (field, variation) in [("motion variation", self.variation_motion), ("color variation", self.variation_color), ("size variation", self.variation_size)] {
+            if let Some(v) = variation {
+                in_range(field, v, (0.0, 100.0))?;
+            }
+        }
+        if let Some(size) = self.size {
+            in_range("size", size, (0.0, f64::MAX))?;
+        }
+        Ok(spec)
+    }
+}
+
+impl Potion {
+    // Checks the duration and amplifier against DiamondFire's limits
+    pub fn new(effect: PotionEffect, ticks: u64, level: i16) -> Result<Self, AssetError> {
+        let potion = Potion { effect: effect.normalized(), ticks, level };
+        potion.check()?;
+        Ok(potion)
+    }
+    pub fn infinite(effect: PotionEffect, level: i16) -> Result<Self, AssetError> {
+        Potion::new(effect, INFINITE, level)
+    }
+    pub fn is_infinite(&self) -> bool {
+        self.ticks >= INFINITE
+    }
+    // An Other effect whose name is close to, but not, a known one is most likely a typo
+    pub fn check(&self) -> Result<(), AssetError> {
+        if let PotionEffect::Other(name) = &self.effect.clone().normalized() {
+            if let Some(suggestion) = suggest(name, POTION_EFFECTS.iter().map(|e| e.name())) {
+                return Err(AssetError::UnknownEffect { name: name.clone(), suggestion: Some(suggestion) });
+            }
+        }
+        in_range("duration", self.ticks as f64, (1.0, INFINITE as f64))?;
+        in_range("amplifier", self.level as f64, (AMPLIFIER.0 as f64, AMPLIFIER.1 as f64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code_line::CodeLine, decompile::parse_templates, params::ParamBuilder, program::Program, statements::Statement, testing::{local, set}, typeck::{TypeChecker, TypeError, TypeErrorKind}, validate::validate, values::{Selector, Value}};
+
+    // The error codes validation reports for a line that stores the value
+    fn codes(value: Value) -> Vec<&'static str> {
+        validate(&Program::new_from(vec![ CodeLine::new_from(vec![ Statement::PlayerEvent(String::from("Join")), set(local("v"), value) ]) ]))
+            .diagnostics.iter().map(|d| d.code).filter(|c| c.starts_with('E')).collect()
+    }
+
+    #[test]
+    fn test_sounds() {
+        assert!(Sound::new("Pling", None, 1.0, 2.0).is_ok());
+        assert!(Sound::new("Goat Horn", Some("ponder"), 0.5, 1.0).is_ok());
+        assert!(Sound::new("Warden Sonic Boom", None, 1.0, 1.0).is_ok());
+    }
+
+    #[test]
+    fn test_sound_suggestions() {
+        assert_eq!(Sound::new("Plign", None, 1.0, 2.0).err(), Some(AssetError::UnknownSound { name: String::from("Plign"), suggestion: Some("Pling") }));
+        assert_eq!(Sound::new("Goat Horn", Some("pondr"), 1.0, 1.0).err().and_then(|e| e.suggestion()), Some("ponder"));
+        assert_eq!(Sound::new("Xyz Qwerty", None, 1.0, 1.0).err().and_then(|e| e.suggestion()), None);
+    }
+
+    #[test]
+    fn test_sound_range() {
+        assert!(matches!(Sound::new("Pling", None, 2.5, 1.0), Err(AssetError::Range { field: "pitch", .. })));
+    }
+
+    #[test]
+    fn test_error_message() {
+        assert_eq!(AssetError::UnknownParticle { name: String::from("Flme"), suggestion: Some("Flame") }.to_string(), "unknown particle Flme (did you mean Flame?)");
+    }
+
+    #[test]
+    fn test_particles() {
+        let dust = Particle::new("Dust").unwrap().amount(5).color((255, 0, 0), 10.0).size(2.0, 0.0);
+        assert!(dust.check().is_ok());
+        assert!(matches!(Particle::new("Dsut"), Err(AssetError::UnknownParticle { suggestion: Some("Dust"), .. })));
+    }
+
+    #[test]
+    fn test_particle_fields() {
+        let flame = Particle::new("Flame").unwrap().color((0, 0, 0), 0.0);
+        assert_eq!(flame.check().err(), Some(AssetError::Field { particle: String::from("Flame"), field: ParticleField::Color }));
+    }
+
+    #[test]
+    fn test_error_codes() {
+        // Values out of range and data a particle doesn't take are told apart
+        assert_eq!(codes(Value::Particle(Particle::new("Flame").unwrap().color((0, 0, 0), 0.0))), ["E0111"]);
+        assert_eq!(codes(Value::Sound(Sound { sound: String::from("Bell"), variant: None, pitch: 5.0, volume: 1.0 })), ["E0109"]);
+        assert_eq!(codes(Value::Sound(Sound { sound: String::from("Bel"), variant: None, pitch: 1.0, volume: 1.0 })), ["E0108"]);
+    }
+
+    #[test]
+    fn test_unknown_sound_in_program() {
+        let sound = Value::Sound(Sound { sound: String::from("Bel"), variant: None, pitch: 1.0, volume: 1.0 });
+        let p = Program::new_from(vec![ CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            set(local("s"), sound),
+        ]) ]);
+        let line = TypeChecker::new(&p).check_line(&p.lines()[0]);
+        assert!(matches!(&line.errors[..], [TypeError { statement: 1, kind: TypeErrorKind::Asset(AssetError::UnknownSound { suggestion: Some("Bell"), .. }), .. }]));
+        // The name is still sent as written
+        assert!(p.lines()[0].serialize().contains(r#"{"id":"snd","data":{"sound":"Bel","pitch":1,"vol":1}}"#));
+    }
+
+    #[test]
+    fn test_sound_variant() {
+        let horn = Value::Sound(Sound::new("Goat Horn", Some("call"), 1.0, 1.0).unwrap());
+        assert_eq!(horn.serialize_item(&Statement::PlayerEvent(String::from("Join"))), r#"{"id":"snd","data":{"sound":"Goat Horn","variant":"call","pitch":1,"vol":1}}"#);
+    }
+
+    #[test]
+    fn test_precision() {
+        // Pitches and particle data keep every digit they were given through a round trip
+        let p = Program::new_from(vec![ CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            set(local("s"), Value::Sound(Sound::new("Bell", None, 1.0000001, 0.1).unwrap())),
+            set(local("p"), Value::Particle(Particle::new("Dust").unwrap().color((255, 0, 0), 0.3).size(1.0000001, 0.0))),
+        ]) ]);
+        let json = p.lines()[0].serialize();
+        assert!(json.contains(r#""pitch":1.0000001,"vol":0.1"#));
+        assert_eq!(parse_templates(&json).unwrap()[0].serialize(), json);
+    }
+
+    #[test]
+    fn test_potion_effects() {
+        assert_eq!(PotionEffect::parse("Dolphin's Grace"), Some(PotionEffect::DolphinGrace));
+        assert_eq!(PotionEffect::parse("Wind Charged"), Some(PotionEffect::WindCharged));
+        assert_eq!(PotionEffect::parse("Raid Omen").map(|e| e.serialize()), Some(String::from("Raid Omen")));
+        assert_eq!(PotionEffect::from_name("Sparkles"), PotionEffect::Other(String::from("Sparkles")));
+    }
+
+    #[test]
+    fn test_potion_ranges() {
+        assert!(Potion::new(PotionEffect::Speed, 200, 1).is_ok());
+        assert!(Potion::infinite(PotionEffect::Other(String::from("Sparkles")), 0).unwrap().is_infinite());
+        assert!(matches!(Potion::new(PotionEffect::Speed, 0, 1), Err(AssetError::Range { field: "duration", .. })));
+        assert!(matches!(Potion::new(PotionEffect::Speed, INFINITE + 1, 1), Err(AssetError::Range { field: "duration", .. })));
+        assert!(matches!(Potion::new(PotionEffect::Haste, 20, 300), Err(AssetError::Range { field: "amplifier", .. })));
+    }
+
+    #[test]
+    fn test_unknown_effect() {
+        assert!(matches!(Potion::new(PotionEffect::from_name("Oozng"), 20, 0), Err(AssetError::UnknownEffect { suggestion: Some("Oozing"), .. })));
+    }
+
+    #[test]
+    fn test_other_known_effect() {
+        // An Other spelling out a known effect is that effect
+        assert_eq!(PotionEffect::Other(String::from("Speed")), PotionEffect::Speed);
+        assert!(matches!(Potion::new(PotionEffect::Other(String::from("Speed")), 20, 0).unwrap().effect, PotionEffect::Speed));
+        assert!(Potion { effect: PotionEffect::Other(String::from("Speed")), ticks: 20, level: 0 }.check().is_ok());
+    }
+
+    #[test]
+    fn test_potion_roundtrip() {
+        let give = CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            Statement::PlayerAction { action: String::from("GivePotion"), parameters: ParamBuilder::new().param(Value::Potion(Potion::new(PotionEffect::TrialOmen, 600, 2).unwrap())).complete_unchecked(), selector: Selector::Default },
+        ]);
+        let json = give.serialize();
+        assert!(json.contains(r#"{"id":"pot","data":{"pot":"Trial Omen","dur":600,"amp":2}}"#));
+        assert_eq!(parse_templates(&json).unwrap()[0].serialize(), json);
+    }
+}
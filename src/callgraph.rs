@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use crate::{program::Program, serialization::json_escape, statements::Statement};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NodeKind {
+    Event, Function, Process,
+    // Called somewhere but not defined in the program
+    Undefined
+}
+impl NodeKind {
+    fn name(&self) -> &'static str {
+        match self {
+            NodeKind::Event => "event",
+            NodeKind::Function => "function",
+            NodeKind::Process => "process",
+            NodeKind::Undefined => "undefined",
+        }
+    }
+}
+
+pub struct Node {
+    pub name: String,
+    pub kind: NodeKind,
+    pub line: Option<usize>
+}
+
+// A call block: `line` and `statement` locate it in the program
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub process: bool,
+    pub line: usize,
+    pub statement: usize
+}
+
+// Calls through a text code are left out since their target isn't known
+pub struct CallGraph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>
+}
+impl CallGraph {
+    pub fn build(program: &Program) -> Self {
+        let mut nodes = vec![];
+        let mut by_name: HashMap<(bool, String), usize> = HashMap::new();
+        let mut line_nodes = vec![];
+        for (li, line) in program.lines().iter().enumerate() {
+            let (kind, name, process) = match line.body().first() {
+                Some(Statement::Function { name, .. }) => (NodeKind::Function, name.clone(), false),
+                Some(Statement::Process { name, .. }) => (NodeKind::Process, name.clone(), true),
+                Some(Statement::PlayerEvent(_)) | Some(Statement::EntityEvent(_)) => (NodeKind::Event, line.name(), false),
+                _ => {
+                    line_nodes.push(None);
+                    continue;
+                }
+            };
+            if kind != NodeKind::Event {
+                by_name.insert((process, name.clone()), nodes.len());
+            }
+            line_nodes.push(Some(nodes.len()));
+            nodes.push(Node { name, kind, line: Some(li) });
+        }
+        let mut edges = vec![];
+        for (li, line) in program.lines().iter().enumerate() {
+            let Some(from) = line_nodes[li] else { continue };
+            for (si, stmnt) in line.body().iter().enumerate() {
+                let (name, process) = match stmnt {
+                    Statement::CallFunction { name, .. } => (name, false),
+                    Statement::CallProcess { name, .. } => (name, true),
+                    _ => continue
+                };
+                if name.contains('%') {
+                    continue;
+                }
+                let to = *by_name.entry((process, name.clone())).or_insert_with(|| {
+                    nodes.push(Node { name: name.clone(), kind: NodeKind::Undefined, line: None });
+                    nodes.len() - 1
+                });
+                edges.push(Edge { from, to, process, line: li, statement: si });
+            }
+        }
+        Self { nodes, edges }
+    }
+    pub fn calls(&self, node: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |e| e.from == node)
+    }
+    pub fn events(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.nodes.len()).filter(|&n| self.nodes[n].kind == NodeKind::Event)
+    }
+    // Starting a process begins a new thread, so only function calls add to
+    // the call stack. Every strongly connected set of functions that call
+    // each other (or one calling itself) is returned, in program order.
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        let mut tarjan = Tarjan { graph: self, index: vec![None; self.nodes.len()], low: vec![0; self.nodes.len()], stack: vec![], on_stack: vec![false; self.nodes.len()], next: 0, out: vec![] };
+        for n in 0..self.nodes.len() {
+            if tarjan.index[n].is_none() {
+                tarjan.visit(n);
+            }
+        }
+        let mut cycles: Vec<Vec<usize>> = tarjan.out.into_iter()
+            .filter(|c| c.len() > 1 || self.calls(c[0]).any(|e| !e.process && e.to == c[0]))
+            .map(|mut c| {
+                c.sort();
+                c
+            })
+            .collect();
+        cycles.sort();
+        cycles
+    }
+    // Deepest chain of nested function calls starting at a node, or None when
+    // it can reach recursion and has no bound
+    pub fn max_depth(&self, node: usize) -> Option<usize> {
+        fn depth(graph: &CallGraph, node: usize, memo: &mut HashMap<usize, Option<usize>>, visiting: &mut Vec<bool>) -> Option<usize> {
+            if let Some(d) = memo.get(&node) {
+                return *d;
+            }
+            if visiting[node] {
+                return None;
+            }
+            visiting[node] = true;
+            let mut out = Some(0);
+            for e in graph.calls(node).filter(|e| !e.process) {
+                out = match (out, depth(graph, e.to, memo, visiting)) {
+                    (Some(a), Some(b)) => Some(a.max(b + 1)),
+                    _ => None
+                };
+            }
+            visiting[node] = false;
+            memo.insert(node, out);
+            out
+        }
+        depth(self, node, &mut HashMap::new(), &mut vec![false; self.nodes.len()])
+    }
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph calls {\n");
+        for (i, n) in self.nodes.iter().enumerate() {
+            let (shape, label) = match n.kind {
+                NodeKind::Event => ("box", match self.max_depth(i) {
+                    Some(d) => format!("{}\\nmax depth {d}", n.name),
+                    None => format!("{}\\nunbounded depth", n.name)
+                }),
+                NodeKind::Function => ("ellipse", n.name.clone()),
+                NodeKind::Process => ("hexagon", n.name.clone()),
+                NodeKind::Undefined => ("ellipse\", style=\"dashed", n.name.clone()),
+            };
+            out += &format!("  n{i} [label=\"{}\", shape=\"{shape}\"];\n", label.replace('"', "\\\""));
+        }
+        for e in &self.edges {
+            out += &format!("  n{} -> n{}{};\n", e.from, e.to, if e.process { " [style=\"dashed\"]" } else { "" });
+        }
+        out += "}\n";
+        out
+    }
+    pub fn to_json(&self) -> String {
+        let nodes = self.nodes.iter().map(|n| format!(
+            r#"{{"name":"{}","kind":"{}","line":{}}}"#,
+            json_escape(&n.name),
+            n.kind.name(),
+            n.line.map_or_else(|| String::from("null"), |l| l.to_string())
+        )).collect::<Vec<String>>().join(",");
+        let edges = self.edges.iter().map(|e| format!(
+            r#"{{"from":{},"to":{},"process":{},"line":{},"statement":{}}}"#,
+            e.from, e.to, e.process, e.line, e.statement
+        )).collect::<Vec<String>>().join(",");
+        let cycles = self.cycles().iter().map(|c| format!(
+            "[{}]",
+            c.iter().map(|n| format!("\"{}\"", json_escape(&self.nodes[*n].name))).collect::<Vec<String>>().join(",")
+        )).collect::<Vec<String>>().join(",");
+        let depths = self.events().map(|n| format!(
+            r#"{{"event":"{}","max_depth":{}}}"#,
+            json_escape(&self.nodes[n].name),
+            self.max_depth(n).map_or_else(|| String::from("null"), |d| d.to_string())
+        )).collect::<Vec<String>>().join(",");
+        format!(r#"{{"nodes":[{nodes}],"edges":[{edges}],"cycles":[{cycles}],"depths":[{depths}]}}"#)
+    }
+}
+
+struct Tarjan<'a> {
+    graph: &'a CallGraph,
+    index: Vec<Option<usize>>,
+    low: Vec<usize>,
+    stack: Vec<usize>,
+    on_stack: Vec<bool>,
+    next: usize,
+    out: Vec<Vec<usize>>
+}
+impl Tarjan<'_> {
+    fn visit(&mut self, n: usize) {
+        self.index[n] = Some(self.next);
+        self.low[n] = self.next;
+        self.next += 1;
+        self.stack.push(n);
+        self.on_stack[n] = true;
+        let targets: Vec<usize> = self.graph.calls(n).filter(|e| !e.process).map(|e| e.to).collect();
+        for m in targets {
+            match self.index[m] {
+                None => {
+                    self.visit(m);
+                    self.low[n] = self.low[n].min(self.low[m]);
+                }
+                Some(i) if self.on_stack[m] => self.low[n] = self.low[n].min(i),
+                Some(_) => {}
+            }
+        }
+        if Some(self.low[n]) == self.index[n] {
+            let mut component = vec![];
+            loop {
+                let m = self.stack.pop().unwrap();
+                self.on_stack[m] = false;
+                component.push(m);
+                if m == n {
+                    break;
+                }
+            }
+            self.out.push(component);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code_line::CodeLine, params::ParamBuilder, validate::validate};
+
+    // Join calls a chain three deep, Quit calls into a ping/pong cycle
+    fn program() -> Program {
+        let call = |name: &str| Statement::CallFunction { name: String::from(name), parameters: ParamBuilder::new().complete_unchecked() };
+        let func = |name: &str, calls: &[&str]| {
+            let mut body = vec![ Statement::Function { name: String::from(name), parameters: ParamBuilder::new().complete_unchecked() } ];
+            body.extend(calls.iter().map(|c| call(c)));
+            CodeLine::new_from(body)
+        };
+        Program::new_from(vec![
+            CodeLine::new_from(vec![ Statement::PlayerEvent(String::from("Join")), call("a"), call("b") ]),
+            CodeLine::new_from(vec![ Statement::PlayerEvent(String::from("Quit")), call("ping") ]),
+            func("a", &["b"]),
+            func("b", &["c"]),
+            func("c", &[]),
+            func("ping", &["pong"]),
+            func("pong", &["ping", "%var(next)"]),
+        ])
+    }
+
+    #[test]
+    fn test_call_graph() {
+        let graph = CallGraph::build(&program());
+        assert_eq!(graph.nodes.len(), 7);
+        assert_eq!(graph.edges.len(), 7);
+    }
+
+    #[test]
+    fn test_cycles() {
+        let graph = CallGraph::build(&program());
+        let names = |nodes: &Vec<usize>| nodes.iter().map(|n| graph.nodes[*n].name.clone()).collect::<Vec<String>>();
+        assert_eq!(graph.cycles().iter().map(names).collect::<Vec<Vec<String>>>(), vec![vec![String::from("ping"), String::from("pong")]]);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let graph = CallGraph::build(&program());
+        assert_eq!(graph.events().map(|e| graph.max_depth(e)).collect::<Vec<Option<usize>>>(), vec![Some(3), None]);
+    }
+
+    #[test]
+    fn test_output() {
+        let graph = CallGraph::build(&program());
+        assert!(graph.to_dot().contains("n0 [label=\"Player Event: Join\\nmax depth 3\", shape=\"box\"];"));
+        assert!(graph.to_json().ends_with(r#""cycles":[["ping","pong"]],"depths":[{"event":"Player Event: Join","max_depth":3},{"event":"Player Event: Quit","max_depth":null}]}"#));
+    }
+
+    #[test]
+    fn test_recursion_is_a_warning() {
+        let report = validate(&program());
+        assert!(!report.has_errors());
+        assert_eq!(report.diagnostics.len(), 1);
+    }
+}
@@ -0,0 +1,370 @@
+use std::fmt::Display;
+
+use crate::{serialization::DFSerializable, values::{GameValue, Selector, ValueType::{self, *}}};
+
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub value_type: ValueType,
+    pub plural: bool,
+    pub optional: bool
+}
+const fn req(name: &'static str, value_type: ValueType) -> ArgSpec {
+    ArgSpec { name, value_type, plural: false, optional: false }
+}
+const fn opt(name: &'static str, value_type: ValueType) -> ArgSpec {
+    ArgSpec { name, value_type, plural: false, optional: true }
+}
+const fn many(name: &'static str, value_type: ValueType) -> ArgSpec {
+    ArgSpec { name, value_type, plural: true, optional: false }
+}
+const fn many_opt(name: &'static str, value_type: ValueType) -> ArgSpec {
+    ArgSpec { name, value_type, plural: true, optional: true }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Target {
+    None,
+    // The first argument is the variable the block writes to
+    Required,
+    // Only counts as the target when there are more arguments than the action takes
+    Optional
+}
+
+pub struct ActionSpec {
+    pub block: &'static str,
+    pub action: &'static str,
+    pub target: Target,
+    pub args: &'static [ArgSpec],
+    // Type written to the target variable; None keeps whatever was assigned
+    pub result: Option<ValueType>
+}
+impl ActionSpec {
+    pub fn max_args(&self) -> Option<usize> {
+        if self.args.iter().any(|a| a.plural) {
+            None
+        } else {
+            Some(self.args.len())
+        }
+    }
+    // Whether the first of `count` arguments is the target variable. An
+    // optional target is only there when the arguments overflow the spec.
+    pub fn takes_target(&self, count: usize) -> bool {
+        match self.target {
+            Target::None => false,
+            Target::Required => true,
+            Target::Optional => self.max_args().is_some_and(|m| count > m)
+        }
+    }
+}
+
+const fn action(block: &'static str, action: &'static str, args: &'static [ArgSpec]) -> ActionSpec {
+    ActionSpec { block, action, target: Target::None, args, result: None }
+}
+const fn setter(block: &'static str, action: &'static str, args: &'static [ArgSpec], result: ValueType) -> ActionSpec {
+    ActionSpec { block, action, target: Target::Required, args, result: Some(result) }
+}
+
+// Not every action is listed; anything missing here is simply not type checked
+pub static ACTIONS: &[ActionSpec] = &[
+    ActionSpec { block: "set_var", action: "=", target: Target::Required, args: &[req("value", Any)], result: None },
+    setter("set_var", "+", &[many("numbers", Number)], Number),
+    setter("set_var", "-", &[many("numbers", Number)], Number),
+    setter("set_var", "x", &[many("numbers", Number)], Number),
+    setter("set_var", "/", &[many("numbers", Number)], Number),
+    setter("set_var", "%", &[req("dividend", Number), req("divisor", Number)], Number),
+    setter("set_var", "+=", &[opt("amount", Number)], Number),
+    setter("set_var", "-=", &[opt("amount", Number)], Number),
+    setter("set_var", "RandomNumber", &[req("min", Number), req("max", Number)], Number),
+    setter("set_var", "RandomValue", &[many("values", Any)], Any),
+    setter("set_var", "String", &[many_opt("texts", Any)], Text),
+    setter("set_var", "GetCoord", &[req("location", Location)], Number),
+    setter("set_var", "SetCoord", &[req("location", Location), req("coordinate", Number)], Location),
+    setter("set_var", "Distance", &[req("from", Location), req("to", Location)], Number),
+    setter("set_var", "CreateList", &[many_opt("values", Any)], List),
+    setter("set_var", "AppendValue", &[many("values", Any)], List),
+    setter("set_var", "GetListValue", &[req("list", List), req("index", Number)], Any),
+    setter("set_var", "SetListValue", &[req("index", Number), req("value", Any)], List),
+    setter("set_var", "RemoveListIndex", &[many("indices", Number)], List),
+    setter("set_var", "ListLength", &[req("list", List)], Number),
+    setter("set_var", "CreateDict", &[opt("keys", List), opt("values", List)], Dict),
+    setter("set_var", "GetDictValue", &[req("dict", Dict), req("key", Text)], Any),
+    setter("set_var", "SetDictValue", &[req("key", Text), req("value", Any)], Dict),
+    setter("set_var", "RemoveDictEntry", &[many("keys", Text)], Dict),
+    setter("set_var", "GetDictKeys", &[req("dict", Dict)], List),
+    setter("set_var", "GetDictValues", &[req("dict", Dict)], List),
+    setter("set_var", "DictSize", &[req("dict", Dict)], Number),
+    action("player_action", "SendMessage", &[many_opt("message", Any)]),
+    action("player_action", "SendTitle", &[req("title", Component), opt("subtitle", Component), opt("duration", Number)]),
+    action("player_action", "Teleport", &[req("location", Location)]),
+    action("player_action", "SetHealth", &[req("health", Number)]),
+    action("player_action", "GiveItems", &[many("items", Item)]),
+    action("player_action", "GivePotion", &[many("effects", Potion)]),
+    action("player_action", "PlaySound", &[many("sounds", Sound), opt("location", Location)]),
+    action("player_action", "SetVelocity", &[req("velocity", Vector)]),
+    action("entity_action", "Teleport", &[req("location", Location)]),
+    action("entity_action", "Heal", &[opt("amount", Number)]),
+    action("entity_action", "SetVelocity", &[req("velocity", Vector)]),
+    action("game_action", "CancelEvent", &[]),
+    action("control", "Wait", &[opt("duration", Number)]),
+    action("control", "Return", &[]),
+    action("control", "End", &[]),
+    action("control", "Skip", &[]),
+    action("control", "StopRepeat", &[]),
+    action("if_var", "=", &[req("value", Any), many("compare", Any)]),
+    action("if_var", "!=", &[req("value", Any), many("compare", Any)]),
+    action("if_var", "<", &[req("value", Number), req("compare", Number)]),
+    action("if_var", ">", &[req("value", Number), req("compare", Number)]),
+    action("if_var", "<=", &[req("value", Number), req("compare", Number)]),
+    action("if_var", ">=", &[req("value", Number), req("compare", Number)]),
+    action("if_var", "InRange", &[req("value", Number), req("min", Number), req("max", Number)]),
+    action("if_player", "IsLookingAt", &[many("locations", Location)]),
+    action("if_player", "IsNear", &[many("locations", Location), opt("radius", Number)]),
+    ActionSpec { block: "repeat", action: "Multiple", target: Target::Optional, args: &[req("times", Number)], result: Some(Number) },
+    setter("repeat", "Range", &[req("start", Number), req("end", Number), opt("step", Number)], Number),
+    setter("repeat", "ForEach", &[req("list", List)], Any),
+    // The value variable and dictionary follow the key variable, which is the target
+    setter("repeat", "ForEachEntry", &[req("value", Any), req("dict", Dict)], Text),
+    action("repeat", "Forever", &[]),
+];
+
+pub fn action_spec(block: &str, action: &str) -> Option<&'static ActionSpec> {
+    ACTIONS.iter().find(|a| a.block == block && a.action == action)
+}
+
+// What a game value is read from, which decides the targets it allows
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Subject {
+    // Only players have it, like food level
+    Player,
+    // Any entity, players included
+    Entity,
+    // The plot, the event or the current selection; these ignore the target
+    Plot, Event, Selection
+}
+impl Subject {
+    pub fn allows(&self, target: Selector) -> bool {
+        use Selector::*;
+        match self {
+            Subject::Player => matches!(target, Default | Selection | Killer | Damager | Victim | Shooter),
+            Subject::Entity => matches!(target, Default | Selection | Killer | Damager | Victim | Shooter | Projectile | LastEntity),
+            Subject::Plot | Subject::Event | Subject::Selection => matches!(target, Default),
+        }
+    }
+}
+impl Display for Subject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Subject::Player => "player",
+            Subject::Entity => "entity",
+            Subject::Plot => "plot",
+            Subject::Event => "event",
+            Subject::Selection => "selection",
+        })
+    }
+}
+
+pub struct GameValueSpec {
+    pub name: &'static str,
+    pub subject: Subject,
+    pub result: ValueType
+}
+const fn game_value(name: &'static str, subject: Subject, result: ValueType) -> GameValueSpec {
+    GameValueSpec { name, subject, result }
+}
+
+// DiamondFire's Game Values menu, grouped the way the menu groups them. Values
+// added to DiamondFire after this table are rejected as unknown until they get
+// an entry here.
+pub static GAME_VALUES: &[GameValueSpec] = &[
+    // Statistical values
+    game_value("Current Health", Subject::Entity, Number),
+    game_value("Maximum Health", Subject::Entity, Number),
+    game_value("Absorption Health", Subject::Entity, Number),
+    game_value("Armor Points", Subject::Entity, Number),
+    game_value("Armor Toughness", Subject::Entity, Number),
+    game_value("Attack Damage", Subject::Entity, Number),
+    game_value("Attack Speed", Subject::Entity, Number),
+    game_value("Knockback Resistance", Subject::Entity, Number),
+    game_value("Movement Speed", Subject::Entity, Number),
+    game_value("Scale", Subject::Entity, Number),
+    game_value("Invulnerability Ticks", Subject::Entity, Number),
+    game_value("Fall Distance", Subject::Entity, Number),
+    game_value("Fire Ticks", Subject::Entity, Number),
+    game_value("Remaining Air", Subject::Entity, Number),
+    game_value("Freeze Ticks", Subject::Entity, Number),
+    game_value("Food Level", Subject::Player, Number),
+    game_value("Saturation", Subject::Player, Number),
+    game_value("Food Exhaustion", Subject::Player, Number),
+    game_value("Experience Level", Subject::Player, Number),
+    game_value("Experience Progress", Subject::Player, Number),
+    game_value("Ping", Subject::Player, Number),
+    game_value("Attack Cooldown", Subject::Player, Number),
+    game_value("Attack Cooldown Ticks", Subject::Player, Number),
+    game_value("Item Usage Progress", Subject::Player, Number),
+    game_value("Selected Hotbar Slot", Subject::Player, Number),
+    game_value("Steer Sideways Movement", Subject::Player, Number),
+    game_value("Steer Forward Movement", Subject::Player, Number),
+    // Locational values
+    game_value("Location", Subject::Entity, Location),
+    game_value("X-Coordinate", Subject::Entity, Number),
+    game_value("Y-Coordinate", Subject::Entity, Number),
+    game_value("Z-Coordinate", Subject::Entity, Number),
+    game_value("Pitch", Subject::Entity, Number),
+    game_value("Yaw", Subject::Entity, Number),
+    game_value("Eye Location", Subject::Entity, Location),
+    game_value("Standing Block Location", Subject::Entity, Location),
+    game_value("Direction", Subject::Entity, Vector),
+    game_value("Velocity", Subject::Entity, Vector),
+    game_value("Target Block Location", Subject::Player, Location),
+    game_value("Target Block Side", Subject::Player, Text),
+    game_value("Spawn Location", Subject::Player, Location),
+    // Item values
+    game_value("Main Hand Item", Subject::Entity, Item),
+    game_value("Off Hand Item", Subject::Entity, Item),
+    game_value("Armor Items", Subject::Entity, List),
+    game_value("Saddle Item", Subject::Entity, Item),
+    game_value("Body Armor Item", Subject::Entity, Item),
+    game_value("Entity Item", Subject::Entity, Item),
+    game_value("Inventory Items", Subject::Player, List),
+    game_value("Hotbar Items", Subject::Player, List),
+    game_value("Ender Chest Items", Subject::Player, List),
+    game_value("Inventory Menu Items", Subject::Player, List),
+    game_value("Cursor Item", Subject::Player, Item),
+    // Informational values
+    game_value("Name", Subject::Entity, Text),
+    game_value("UUID", Subject::Entity, Text),
+    game_value("Entity Type", Subject::Entity, Text),
+    game_value("Potion Effects", Subject::Entity, List),
+    game_value("Shooter UUID", Subject::Entity, Text),
+    game_value("Vehicle UUID", Subject::Entity, Text),
+    game_value("Passenger UUIDs", Subject::Entity, List),
+    game_value("Lead Holder UUID", Subject::Entity, Text),
+    game_value("Target Entity UUID", Subject::Entity, Text),
+    game_value("Game Mode", Subject::Player, Text),
+    game_value("Open Inventory Title", Subject::Player, Text),
+    // Plot values
+    game_value("Player Count", Subject::Plot, Number),
+    game_value("Entity Count", Subject::Plot, Number),
+    game_value("CPU Usage", Subject::Plot, Number),
+    game_value("Server TPS", Subject::Plot, Number),
+    game_value("Timestamp", Subject::Plot, Number),
+    game_value("Plot Id", Subject::Plot, Number),
+    game_value("Plot Name", Subject::Plot, Text),
+    game_value("Plot Size", Subject::Plot, Number),
+    // Event values
+    game_value("Event Damage", Subject::Event, Number),
+    game_value("Damage Event Cause", Subject::Event, Text),
+    game_value("Event Healing", Subject::Event, Number),
+    game_value("Heal Event Cause", Subject::Event, Text),
+    game_value("Event Death Message", Subject::Event, Text),
+    game_value("Event Block Location", Subject::Event, Location),
+    game_value("Event Block Side", Subject::Event, Text),
+    game_value("Event Item", Subject::Event, Item),
+    game_value("Event Hotbar Slot", Subject::Event, Number),
+    game_value("Event Clicked Slot Index", Subject::Event, Number),
+    game_value("Event Clicked Slot Item", Subject::Event, Item),
+    game_value("Event Clicked Slot New Item", Subject::Event, Item),
+    game_value("Close Inventory Event Cause", Subject::Event, Text),
+    game_value("Event Command", Subject::Event, Text),
+    game_value("Event Command Arguments", Subject::Event, List),
+    game_value("Event Chat Message", Subject::Event, Text),
+    game_value("Event Power", Subject::Event, Number),
+    game_value("Event Teleport Cause", Subject::Event, Text),
+    game_value("Event Fall Distance", Subject::Event, Number),
+    // Selection values
+    game_value("Selection Size", Subject::Selection, Number),
+    game_value("Selection Target Names", Subject::Selection, List),
+    game_value("Selection Target UUIDs", Subject::Selection, List),
+    game_value("Midpoint Between Targets", Subject::Selection, Location),
+];
+
+pub fn game_value_spec(name: &str) -> Option<&'static GameValueSpec> {
+    GAME_VALUES.iter().find(|g| g.name == name)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GameValueError {
+    Unknown { name: String },
+    Target { name: String, subject: Subject, target: Selector }
+}
+impl Display for GameValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameValueError::Unknown { name } => write!(f, "unknown game value {name}"),
+            GameValueError::Target { name, subject, target } =>
+                write!(f, "{name} is a {subject} value and can't be read from {}", target.serialize()),
+        }
+    }
+}
+
+impl GameValue {
+    // Checks the name and target against the catalog
+    pub fn new(name: &str, selector: Option<Selector>) -> Result<Self, GameValueError> {
+        let value = GameValue { name: String::from(name), selector };
+        value.check()?;
+        Ok(value)
+    }
+    pub fn spec(&self) -> Option<&'static GameValueSpec> {
+        game_value_spec(&self.name)
+    }
+    pub fn check(&self) -> Result<&'static GameValueSpec, GameValueError> {
+        let spec = self.spec().ok_or_else(|| GameValueError::Unknown { name: self.name.clone() })?;
+        let target = self.selector.unwrap_or_default();
+        if !spec.subject.allows(target) {
+            return Err(GameValueError::Target { name: self.name.clone(), subject: spec.subject, target });
+        }
+        Ok(spec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code_line::CodeLine, params::ParamBuilder, program::Program, statements::Statement, validate::validate, values::Value};
+
+    #[test]
+    fn test_game_value_targets() {
+        assert!(GameValue::new("Location", Some(Selector::LastEntity)).is_ok());
+        assert_eq!(GameValue::new("Food Level", Some(Selector::Projectile)).err(), Some(GameValueError::Target {
+            name: String::from("Food Level"), subject: Subject::Player, target: Selector::Projectile
+        }));
+        assert!(GameValue::new("Player Count", Some(Selector::Victim)).is_err());
+        assert!(GameValue::new("Player Count", None).is_ok());
+    }
+
+    #[test]
+    fn test_unknown_game_value() {
+        assert_eq!(GameValue::new("Helth", None).err(), Some(GameValueError::Unknown { name: String::from("Helth") }));
+    }
+
+    #[test]
+    fn test_catalog_entries() {
+        assert!(GameValue::new("Armor Points", Some(Selector::Damager)).is_ok());
+        assert!(GameValue::new("Event Death Message", None).is_ok());
+        assert!(GameValue::new("Ender Chest Items", Some(Selector::Killer)).is_ok());
+        assert!(GameValue::new("Vehicle UUID", Some(Selector::Projectile)).is_ok());
+        assert!(GameValue::new("Close Inventory Event Cause", Some(Selector::Selection)).is_err());
+    }
+
+    #[test]
+    fn test_names_unique() {
+        // Every name is listed once, so lookups can't find a stale duplicate
+        assert!(GAME_VALUES.iter().all(|g| game_value_spec(g.name).is_some_and(|s| std::ptr::eq(s, g))));
+    }
+
+    #[test]
+    fn test_unknown_game_value_diagnostic() {
+        let helth = Value::GameValue(GameValue { name: String::from("Helth"), selector: None });
+        let report = validate(&Program::new_from(vec![ CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            Statement::PlayerAction { action: String::from("SetHealth"), parameters: ParamBuilder::new().param(helth).complete_unchecked(), selector: Selector::Default },
+        ]) ]));
+        assert_eq!(report.diagnostics.iter().map(|d| d.code).collect::<Vec<_>>(), vec!["E0106"]);
+    }
+
+    #[test]
+    fn test_game_value_json() {
+        let value = Value::GameValue(GameValue { name: String::from("Event Damage"), selector: Some(Selector::Killer) });
+        assert_eq!(value.serialize_item(&Statement::PlayerEvent(String::from("Join"))), r#"{"id":"g_val","data":{"type":"Event Damage","target":"Killer"}}"#);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{code_line::CodeLine, statements::Statement, validate::opens_bracket};
+
+// Control flow between the blocks of one line. Node i is statement i, and
+// node `exit` (one past the last statement) is where the line finishes or
+// returns. Brackets, Else and Close blocks are nodes of their own.
+pub struct Cfg {
+    pub successors: Vec<Vec<usize>>,
+    pub predecessors: Vec<Vec<usize>>,
+    pub exit: usize
+}
+impl Cfg {
+    pub fn build(line: &CodeLine) -> Self {
+        let body = line.body();
+        let exit = body.len();
+        let mut matching = vec![None; body.len()];
+        let mut stack = vec![];
+        for (i, stmnt) in body.iter().enumerate() {
+            if matches!(stmnt, Statement::Close | Statement::CloseRepeat) {
+                if let Some(open) = stack.pop() {
+                    matching[open] = Some(i);
+                    matching[i] = Some(open);
+                }
+            } else if opens_bracket(stmnt) {
+                stack.push(i);
+            }
+        }
+        // Innermost repeat around each block, for Skip and StopRepeat
+        let mut loops = vec![None; body.len()];
+        let mut open_loops = vec![];
+        for (i, stmnt) in body.iter().enumerate() {
+            if matches!(stmnt, Statement::CloseRepeat) {
+                open_loops.pop();
+            }
+            loops[i] = open_loops.last().copied();
+            if matches!(stmnt, Statement::Repeat { .. }) {
+                open_loops.push(i);
+            }
+        }
+        let mut successors = vec![vec![]; exit + 1];
+        for (i, stmnt) in body.iter().enumerate() {
+            let next = i + 1;
+            successors[i] = match stmnt {
+                Statement::Control { action, .. } if action == "Return" || action == "End" => vec![exit],
+                Statement::Control { action, .. } if action == "Skip" && loops[i].is_some() => vec![loops[i].unwrap()],
+                Statement::Control { action, .. } if action == "StopRepeat" && loops[i].is_some() =>
+                    vec![matching[loops[i].unwrap()].map_or(exit, |c| c + 1)],
+                Statement::Repeat { .. } => match matching[i] {
+                    Some(close) => vec![next, close + 1],
+                    None => vec![next]
+                },
+                Statement::CloseRepeat => match matching[i] {
+                    Some(open) => vec![open],
+                    None => vec![next]
+                },
+                // The false branch goes to the Else, or to the Close when there is none
+                s if opens_bracket(s) && !matches!(s, Statement::Else) => match matching[i] {
+                    Some(close) if matches!(body.get(close + 1), Some(Statement::Else)) => vec![next, close + 1],
+                    Some(close) => vec![next, close],
+                    None => vec![next]
+                },
+                // The end of an if body jumps over its Else
+                Statement::Close => match (matching[i], body.get(next)) {
+                    (Some(open), Some(Statement::Else)) if !matches!(body[open], Statement::Else) =>
+                        vec![matching[next].unwrap_or(next)],
+                    _ => vec![next]
+                },
+                _ => vec![next]
+            };
+        }
+        for s in successors.iter_mut() {
+            s.dedup();
+        }
+        let mut predecessors = vec![vec![]; exit + 1];
+        for (i, succ) in successors.iter().enumerate() {
+            for &s in succ {
+                predecessors[s].push(i);
+            }
+        }
+        Self { successors, predecessors, exit }
+    }
+    // Nodes that can't be reached from the first block
+    pub fn unreachable(&self) -> Vec<usize> {
+        let mut seen = vec![false; self.exit + 1];
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            if n > self.exit || seen[n] {
+                continue;
+            }
+            seen[n] = true;
+            stack.extend(&self.successors[n]);
+        }
+        (0..self.exit).filter(|n| !seen[*n]).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{params::ParamBuilder, testing::{local, set, var}, values::{Number, Selector, Text, Value}};
+
+    // An if/else around assignments, then a Return with a block after it
+    fn branches() -> CodeLine {
+        let send = |value: Value| Statement::PlayerAction { action: String::from("SendMessage"), parameters: ParamBuilder::new().param(value).complete_unchecked(), selector: Selector::Default };
+        let if_var = || Statement::IfVariable { action: String::from("="), parameters: ParamBuilder::new().param(var("flag")).param(Value::Number(Number::from(1))).complete_unchecked(), not: false };
+        CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),                                       // 0
+            set(local("flag"), Value::Number(Number::from(1))),                                 // 1
+            if_var(),                                                                           // 2
+            set(local("a"), Value::Number(Number::from(1))),                                    // 3
+            Statement::Close,                                                                   // 4
+            Statement::Else,                                                                    // 5
+            set(local("a"), Value::Number(Number::from(2))),                                    // 6
+            set(local("b"), Value::Number(Number::from(2))),                                    // 7
+            Statement::Close,                                                                   // 8
+            send(var("a")),                                                                     // 9
+            send(Value::Text(Text(String::from("%var(b)")))),                                   // 10
+            set(local("unused"), var("a")),                                                     // 11
+            Statement::Control { action: String::from("Return"), parameters: ParamBuilder::new().complete_unchecked() }, // 12
+            send(var("missing")),                                                               // 13
+        ])
+    }
+
+    #[test]
+    fn test_branches() {
+        let cfg = Cfg::build(&branches());
+        assert_eq!(cfg.successors[2], vec![3, 5]);
+        assert_eq!(cfg.successors[4], vec![8]);
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let cfg = Cfg::build(&branches());
+        assert_eq!(cfg.successors[12], vec![cfg.exit]);
+        assert_eq!(cfg.unreachable(), vec![13]);
+    }
+}
@@ -0,0 +1,423 @@
+use std::{fs, io::{ErrorKind, IsTerminal, Write}, path::PathBuf};
+
+use crate::{callgraph::CallGraph, compile::DFCompile, decompile::{decode_template, parse_templates, render, DecompileError}, export::{data_version, export, ExportFormat, DEFAULT_DATA_VERSION}, diagnostics::{Report, SourceMap}, linker::{LinkWarning, Linker, Module}, optimize::{Optimizer, Rewrite}, minify::Minifier, passes::{DeadCodePass, MinifyPass, OptimizePass, PassManager, SizePass, ValidatePass}, size::{self, compress_error, measure, render_table, Metric, SizeLimits}, program::Program, project::{Manifest, Project, MANIFEST_NAME}, serialization::json_escape, transport::{send, Transport}, validate::validate};
+
+pub const USAGE: &str = "\
+usage: kindling <command> [options] <inputs...>
+
+commands:
+  build <files...>       compile templates and print a /give command per line
+  send <files...>        give the templates to the player through recode or CodeClient
+  export <files...>      write templates as json, mcfunction or a schematic
+  check <files...>       validate without compiling
+  size <files...>        print the size of every line's template, checking the limits
+  graph <files...>       print the call graph as Graphviz DOT (or JSON with --format json)
+  decompile <template>   list the blocks of a template code string or a file holding one
+                         (or the template JSON with --format json)
+
+inputs are template JSON files (one template or an array) or files holding template code strings.
+without inputs, the project described by the nearest Kindling.toml is used.
+build, send and export leave out functions and processes that no event can reach,
+then optimize what is left
+
+options:
+  --manifest <file>                      use this Kindling.toml for settings and sources
+  --format <text|json>                   output format (default text)
+  --transport <recode|codeclient>        send: where to send templates (default recode)
+  --endpoint <url>                       send: websocket address to connect to
+  --as <json|mcfunction|schematic>       export: output format (default json)
+  -o, --output <file>                    export: file to write instead of stdout
+  --keep <name>                          keep this function or process even if nothing calls it
+                                         (a trailing * matches a prefix; may be repeated)
+  --disable <rewrite|all>                turn off an optimizer rewrite: empty-branches, unreachable,
+                                         merge-assignments, fold-constants or noop-waits (may be repeated)
+  --minify <locals|all>                  shorten local variable names (and with all, globals too)
+  --minify-map <file>                    write the original and minified names to this file
+  --export-global <name>                 never rename this global (a trailing * matches a prefix)
+  --limit <metric>=<n>                   fail when a line goes over this: blocks, length, json-bytes,
+                                         compressed-bytes, code-length, give-length or message-length
+                                         (length defaults to the plot size, or 50; may be repeated)
+  --time-passes                          print how long each pass took
+  --dump <pass|all>                      print the program before and after a pass (may be repeated)
+";
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_INVALID: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_INPUT: i32 = 3;
+pub const EXIT_TRANSPORT: i32 = 4;
+
+#[derive(Debug)]
+pub struct CliError {
+    pub code: i32,
+    pub message: String
+}
+impl CliError {
+    fn usage(message: &str) -> Self {
+        Self { code: EXIT_USAGE, message: format!("{message}\n\n{USAGE}") }
+    }
+    fn input(message: String) -> Self {
+        Self { code: EXIT_INPUT, message }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Format {
+    Text, Json
+}
+
+struct Options {
+    command: String,
+    inputs: Vec<String>,
+    format: Format,
+    manifest: Option<String>,
+    transport: Option<Transport>,
+    endpoint: Option<String>,
+    export_as: ExportFormat,
+    output: Option<String>,
+    keep: Vec<String>,
+    optimizer: Optimizer,
+    time_passes: bool,
+    dump: Vec<String>,
+    minify: Option<Minifier>,
+    minify_map: Option<String>,
+    exported: Vec<String>,
+    limits: Vec<(Metric, usize)>
+}
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, CliError> {
+        let mut args = args.iter();
+        let command = args.next().ok_or_else(|| CliError::usage("no command given"))?.clone();
+        let mut out = Options {
+            command,
+            inputs: vec![],
+            format: Format::Text,
+            manifest: None,
+            transport: None,
+            endpoint: None,
+            export_as: ExportFormat::Json,
+            output: None,
+            keep: vec![],
+            optimizer: Optimizer::new(),
+            time_passes: false,
+            dump: vec![],
+            minify: None,
+            minify_map: None,
+            exported: vec![],
+            limits: vec![]
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| CliError::usage(&format!("{arg} needs a value")));
+            match arg.as_str() {
+                "--format" => out.format = match value()?.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    f => return Err(CliError::usage(&format!("unknown format {f}")))
+                },
+                "--transport" => {
+                    let t = value()?;
+                    out.transport = Some(Transport::parse(t).ok_or_else(|| CliError::usage(&format!("unknown transport {t}")))?);
+                }
+                "--manifest" => out.manifest = Some(value()?.clone()),
+                "--endpoint" => out.endpoint = Some(value()?.clone()),
+                "--as" => {
+                    let f = value()?;
+                    out.export_as = ExportFormat::parse(f).ok_or_else(|| CliError::usage(&format!("unknown export format {f}")))?;
+                }
+                "-o" | "--output" => out.output = Some(value()?.clone()),
+                "--keep" => out.keep.push(value()?.clone()),
+                "--disable" => out.optimizer = match value()?.as_str() {
+                    "all" => Optimizer::none(),
+                    r => out.optimizer.disable(Rewrite::parse(r).ok_or_else(|| CliError::usage(&format!("unknown rewrite {r}")))?)
+                },
+                "--minify" => out.minify = match value()?.as_str() {
+                    "locals" => Some(Minifier::new()),
+                    "all" => Some(Minifier::new().globals(true)),
+                    m => return Err(CliError::usage(&format!("unknown minify mode {m}")))
+                },
+                "--minify-map" => out.minify_map = Some(value()?.clone()),
+                "--export-global" => out.exported.push(value()?.clone()),
+                "--limit" => {
+                    let l = value()?;
+                    let limit = l.split_once('=').and_then(|(m, n)| Some((Metric::parse(m)?, n.parse().ok()?)));
+                    out.limits.push(limit.ok_or_else(|| CliError::usage(&format!("invalid limit {l}")))?);
+                }
+                "--time-passes" => out.time_passes = true,
+                "--dump" => out.dump.push(value()?.clone()),
+                "-h" | "--help" => out.command = String::from("help"),
+                a if a.starts_with('-') => return Err(CliError::usage(&format!("unknown option {a}"))),
+                a => out.inputs.push(String::from(a))
+            }
+        }
+        Ok(out)
+    }
+}
+
+pub fn run(args: &[String]) -> i32 {
+    match execute(args) {
+        Ok(()) => EXIT_OK,
+        Err(e) => {
+            if !e.message.is_empty() {
+                eprintln!("{}", e.message);
+            }
+            e.code
+        }
+    }
+}
+
+fn execute(args: &[String]) -> Result<(), CliError> {
+    let options = Options::parse(args)?;
+    match options.command.as_str() {
+        "build" => build(&options),
+        "send" => {
+            let (program, manifest) = load_checked(&options)?;
+            let transport = transport(&options, manifest.as_ref());
+            let endpoint = options.endpoint.as_deref().or(manifest.as_ref().and_then(|m| m.endpoint.as_deref()));
+            let sent = send(&program, transport, endpoint)
+                .map_err(|e| CliError { code: EXIT_TRANSPORT, message: e.to_string() })?;
+            match options.format {
+                Format::Text => println!("sent {sent} template(s)"),
+                Format::Json => println!(r#"{{"sent":{sent}}}"#),
+            }
+            Ok(())
+        }
+        "export" => {
+            let (program, manifest) = load_checked(&options)?;
+            let data_version = manifest.as_ref()
+                .and_then(|m| m.minecraft_version.as_deref())
+                .and_then(data_version)
+                .unwrap_or(DEFAULT_DATA_VERSION);
+            let bytes = export(&program, options.export_as, data_version)
+                .map_err(|report| CliError { code: EXIT_INVALID, message: report.render(std::io::stderr().is_terminal()) })?;
+            match &options.output {
+                Some(path) => fs::write(path, bytes).map_err(|e| CliError::input(format!("could not write {path}: {e}"))),
+                None => std::io::stdout().write_all(&bytes).map_err(|e| CliError::input(e.to_string()))
+            }
+        }
+        "check" => {
+            let (program, _) = load(&options)?;
+            let report = validate(&program);
+            match options.format {
+                Format::Text => eprint!("{}", report.render(std::io::stderr().is_terminal())),
+                Format::Json => println!("{}", report.to_json()),
+            }
+            if report.has_errors() {
+                return Err(CliError { code: EXIT_INVALID, message: String::new() });
+            }
+            Ok(())
+        }
+        "size" => {
+            let (program, manifest) = load_checked(&options)?;
+            let sizes = measure(&program, transport(&options, manifest.as_ref()))
+                .map_err(|e| report_error(&options, EXIT_INVALID, &Report::from(compress_error(e))))?;
+            match options.format {
+                Format::Text => print!("{}", render_table(&sizes)),
+                Format::Json => println!("{}", size::to_json(&sizes)),
+            }
+            Ok(())
+        }
+        "graph" => {
+            let (program, _) = load(&options)?;
+            let graph = CallGraph::build(&program);
+            match options.format {
+                Format::Text => print!("{}", graph.to_dot()),
+                Format::Json => println!("{}", graph.to_json()),
+            }
+            Ok(())
+        }
+        "decompile" => {
+            let [input] = &options.inputs[..] else {
+                return Err(CliError::usage("decompile takes exactly one template"));
+            };
+            let to_json = |code: &str| if code.trim_start().starts_with(['{', '[']) {
+                Ok(String::from(code))
+            } else {
+                decode_template(code)
+            };
+            // A file, or else the template itself. Something that is neither was most
+            // likely a mistyped path, so that's the error reported.
+            let json = match fs::read_to_string(input) {
+                Ok(code) => to_json(&code).map_err(|e| CliError::input(format!("{input}: {e}")))?,
+                Err(e) if e.kind() == ErrorKind::NotFound => to_json(input)
+                    .map_err(|_| CliError::input(format!("could not read {input}: {e}")))?,
+                Err(e) => return Err(CliError::input(format!("could not read {input}: {e}")))
+            };
+            let lines = parse_templates(&json).map_err(|e| parse_error(&options, input, &json, &e))?;
+            match options.format {
+                Format::Text => print!("{}", render(&lines)),
+                Format::Json => {
+                    let parsed: serde_json::Value = serde_json::from_str(&json).map_err(|e| CliError::input(e.to_string()))?;
+                    println!("{parsed}")
+                }
+            }
+            Ok(())
+        }
+        "help" => {
+            print!("{USAGE}");
+            Ok(())
+        }
+        c => Err(CliError::usage(&format!("unknown command {c}")))
+    }
+}
+
+fn build(options: &Options) -> Result<(), CliError> {
+    let (program, manifest) = load_checked(options)?;
+    program.check_size(manifest.map_or(50, |m| m.plot_size))
+        .map_err(|report| report_error(options, EXIT_INVALID, &report))?;
+    match options.format {
+        Format::Text => for line in program.lines() {
+            println!("{}", program.give_command(line));
+        },
+        Format::Json => println!(
+            "[{}]",
+            program.lines().iter().map(|l| format!(
+                r#"{{"name":"{}","code":"{}","give":"{}"}}"#,
+                json_escape(&l.name()),
+                l.compile(),
+                json_escape(&program.give_command(l))
+            )).collect::<Vec<String>>().join(",")
+        ),
+    }
+    Ok(())
+}
+
+fn transport(options: &Options, manifest: Option<&Manifest>) -> Transport {
+    options.transport.or(manifest.map(|m| m.transport)).unwrap_or(Transport::Recode)
+}
+
+fn load(options: &Options) -> Result<(Program, Option<Manifest>), CliError> {
+    let manifest_path = match &options.manifest {
+        Some(path) => Some(PathBuf::from(path)),
+        None if options.inputs.is_empty() => std::env::current_dir().ok().and_then(|d| Project::find(&d)),
+        None => None
+    };
+    let project = match manifest_path {
+        Some(path) => Some(Project::load(&path).map_err(|e| report_error(options, EXIT_INPUT, &e.to_report()))?),
+        None => None
+    };
+    if options.inputs.is_empty() {
+        let Some(project) = project else {
+            return Err(CliError::usage(&format!("{} needs input files or a {MANIFEST_NAME}", options.command)));
+        };
+        let (program, warnings) = project.program().map_err(|e| report_error(options, EXIT_INPUT, &e.to_report()))?;
+        print_link_warnings(options, &warnings);
+        return Ok((program, Some(project.manifest)));
+    }
+    // Loose inputs share one namespace, but still get their event handlers merged
+    let mut linker = Linker::new();
+    for path in &options.inputs {
+        let source = fs::read_to_string(path).map_err(|e| CliError::input(format!("could not read {path}: {e}")))?;
+        let lines = parse_templates(&source).map_err(|e| parse_error(options, path, &source, &e))?;
+        linker = linker.module(Module { name: String::new(), lines });
+    }
+    let (mut program, warnings) = linker.link_with_warnings().map_err(|errors| {
+        let mut report = Report::new(SourceMap::new());
+        for e in &errors {
+            report.push(e.to_diagnostic());
+        }
+        report_error(options, EXIT_INVALID, &report)
+    })?;
+    print_link_warnings(options, &warnings);
+    if let Some(project) = &project {
+        program = project.apply(program);
+    }
+    Ok((program, project.map(|p| p.manifest)))
+}
+
+// Errors come out as diagnostics, in the same format as everything else
+fn report_error(options: &Options, code: i32, report: &Report) -> CliError {
+    let message = match options.format {
+        Format::Text => report.render(std::io::stderr().is_terminal()).trim_end().to_string(),
+        Format::Json => report.to_json(),
+    };
+    CliError { code, message }
+}
+
+fn parse_error(options: &Options, path: &str, source: &str, error: &DecompileError) -> CliError {
+    let mut sources = SourceMap::new();
+    let id = sources.add(path, String::from(source));
+    let diagnostic = error.to_diagnostic(&sources, id);
+    let mut report = Report::new(sources);
+    report.push(diagnostic);
+    report_error(options, EXIT_INPUT, &report)
+}
+
+fn print_link_warnings(options: &Options, warnings: &[LinkWarning]) {
+    if warnings.is_empty() {
+        return;
+    }
+    let mut report = Report::new(SourceMap::new());
+    for warning in warnings {
+        report.push(warning.to_diagnostic());
+    }
+    match options.format {
+        Format::Text => eprint!("{}", report.render(std::io::stderr().is_terminal())),
+        Format::Json => eprintln!("{}", report.to_json()),
+    }
+}
+
+// Refuses to hand out templates that fail validation, then drops unreachable
+// code and optimizes the rest
+fn load_checked(options: &Options) -> Result<(Program, Option<Manifest>), CliError> {
+    let (mut program, manifest) = load(options)?;
+    let mut keep = options.keep.clone();
+    keep.extend(manifest.iter().flat_map(|m| m.keep.iter().cloned()));
+    let mut passes = PassManager::new()
+        .analysis(ValidatePass { json: options.format == Format::Json, color: std::io::stderr().is_terminal() })
+        .transform(DeadCodePass { keep })
+        .transform(OptimizePass(options.optimizer.clone()));
+    if let Some(minifier) = &options.minify {
+        let minifier = options.exported.iter().fold(minifier.clone(), |m, e| m.export(e));
+        passes = passes.transform(MinifyPass { minifier, map: options.minify_map.as_ref().map(PathBuf::from) });
+    }
+    let limits = options.limits.iter().fold(
+        manifest.as_ref().map_or_else(|| SizeLimits::for_plot(50), |m| m.limits.clone()),
+        |limits, (metric, value)| limits.limit(*metric, *value)
+    );
+    passes = passes.analysis(SizePass {
+        limits,
+        transport: transport(options, manifest.as_ref()),
+        json: options.format == Format::Json,
+        color: std::io::stderr().is_terminal()
+    });
+    for pass in &options.dump {
+        passes = passes.dump(pass);
+    }
+    let log = passes.run(&mut program).map_err(|e| CliError { code: EXIT_INVALID, message: e.message })?;
+    // Validation warnings are shown whatever the format; other summaries only with text
+    let summaries = log.records.iter().filter(|r| options.format == Format::Text || r.name == "validate");
+    for summary in summaries.filter_map(|r| r.summary.as_ref()) {
+        eprintln!("{summary}");
+    }
+    eprint!("{}", log.dumps());
+    if options.time_passes {
+        eprint!("{}", log.timings());
+    }
+    Ok((program, manifest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code_line::CodeLine, params::ParamBuilder, statements::Statement, values::Selector};
+
+    #[test]
+    fn test_decompile_missing_path() {
+        // A mistyped path is an input error, not a template that fails to decode
+        let args = |input: &str| vec![String::from("decompile"), String::from(input), String::from("--format"), String::from("json")];
+        let line = CodeLine::new_from(vec![ Statement::PlayerEvent(String::from("Join")) ]);
+        assert_eq!(run(&args("templates/mising.json")), EXIT_INPUT);
+        assert_eq!(run(&args(&line.compile())), EXIT_OK);
+    }
+
+    #[test]
+    fn test_build_checks_size_limits() {
+        // Building JSON checks the line fits on the plot, as building give commands does
+        let mut body = vec![ Statement::PlayerEvent(String::from("Join")) ];
+        body.extend((0..30).map(|_| Statement::PlayerAction { action: String::from("Heal"), parameters: ParamBuilder::new().complete_unchecked(), selector: Selector::Default }));
+        let path = std::env::temp_dir().join("kindling_build_limits.json");
+        fs::write(&path, CodeLine::new_from(body).compile()).unwrap();
+        let build = |format: &str| run(&[String::from("build"), path.display().to_string(), String::from("--format"), String::from(format)]);
+        assert_eq!((build("json"), build("text")), (EXIT_INVALID, EXIT_INVALID));
+    }
+}
@@ -2,17 +2,14 @@ use std::io::Write;
 
 use flate2::{write::GzEncoder, Compression};
 
-use crate::{statements::Statement, serialization::DFSerializable, compile::DFCompile};
+use crate::{statements::Statement, serialization::DFSerializable, compile::DFCompile, template::Template};
 
 pub struct CodeLine {
     body: Vec<Statement>
 }
 impl DFSerializable for CodeLine {
     fn serialize(&self) -> String {
-        format!(
-            r#"{{"blocks":[{}]}}"#,
-            self.body.iter().map(|x| x.serialize()).collect::<Vec<String>>().join(",")
-        )
+        Template::from_line(self).to_json()
     }
 }
 impl DFCompile for CodeLine {
@@ -22,6 +19,12 @@ impl DFCompile for CodeLine {
         base64::encode(encoder.finish().unwrap())
     }
 }
+impl Default for CodeLine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CodeLine {
     pub fn new() -> Self {
         Self { body: vec![] }
@@ -29,7 +32,13 @@ impl CodeLine {
     pub fn new_from(body: Vec<Statement> ) -> Self {
         Self { body }
     }
+    pub fn body(&self) -> &[Statement] {
+        &self.body
+    }
+    pub fn body_mut(&mut self) -> &mut Vec<Statement> {
+        &mut self.body
+    }
     pub fn name(&self) -> String {
-        self.body.get(0).map_or_else(|| String::from("Empty"), |b| b.name())
+        self.body.first().map_or_else(|| String::from("Empty"), |b| b.name())
     }
 }
\ No newline at end of file
@@ -0,0 +1,345 @@
+use std::fmt::Display;
+
+use crate::{decimal::Decimal, params::ParamBuilder, statements::Statement, values::{Number, Text, Value, Variable, VariableScope}};
+
+// A block has 27 slots and list actions spend one on the target variable
+const PER_BLOCK: usize = 26;
+
+fn set_var(action: &str, target: &Variable, args: Vec<Value>) -> Statement {
+    let builder = args.into_iter().fold(ParamBuilder::new().param(Value::Variable(target.clone())), |b, v| b.param(v));
+    Statement::SetVariable { action: String::from(action), parameters: builder.complete_unchecked() }
+}
+
+fn chunks(values: Vec<Value>) -> Vec<Vec<Value>> {
+    let mut out: Vec<Vec<Value>> = vec![];
+    for v in values {
+        match out.last_mut() {
+            Some(chunk) if chunk.len() < PER_BLOCK => chunk.push(v),
+            _ => out.push(vec![v])
+        }
+    }
+    out
+}
+
+// A list variable. Each method lowers one operation to set variable blocks.
+pub struct List(pub Variable);
+impl List {
+    pub fn new(variable: Variable) -> Self {
+        Self(variable)
+    }
+    fn var(&self) -> Value {
+        Value::Variable(self.0.clone())
+    }
+    // CreateList with as many values as fit, then AppendValue for the rest
+    pub fn create(&self, values: Vec<Value>) -> Vec<Statement> {
+        let mut chunks = chunks(values).into_iter();
+        let mut out = vec![set_var("CreateList", &self.0, chunks.next().unwrap_or_default())];
+        out.extend(chunks.map(|c| set_var("AppendValue", &self.0, c)));
+        out
+    }
+    pub fn append(&self, values: Vec<Value>) -> Vec<Statement> {
+        chunks(values).into_iter().map(|c| set_var("AppendValue", &self.0, c)).collect()
+    }
+    // DiamondFire lists start at index 1
+    pub fn get(&self, index: Value, dest: &Variable) -> Statement {
+        set_var("GetListValue", dest, vec![self.var(), index])
+    }
+    pub fn set(&self, index: Value, value: Value) -> Statement {
+        set_var("SetListValue", &self.0, vec![index, value])
+    }
+    pub fn remove(&self, index: Value) -> Statement {
+        set_var("RemoveListIndex", &self.0, vec![index])
+    }
+    pub fn length(&self, dest: &Variable) -> Statement {
+        set_var("ListLength", dest, vec![self.var()])
+    }
+    // Runs the body once per value, with the value in `item`
+    pub fn for_each(&self, item: &Variable, body: Vec<Statement>) -> Vec<Statement> {
+        let mut out = vec![Statement::Repeat {
+            action: String::from("ForEach"),
+            subaction: None,
+            parameters: ParamBuilder::new().param(Value::Variable(item.clone())).param(self.var()).complete_unchecked(),
+            not: false
+        }];
+        out.extend(body);
+        out.push(Statement::CloseRepeat);
+        out
+    }
+}
+
+// A dictionary variable
+pub struct Dict(pub Variable, VariableScope);
+impl Dict {
+    pub fn new(variable: Variable) -> Self {
+        Self(variable, VariableScope::Line)
+    }
+    // Scope for the temporaries. They are line variables by default, so functions
+    // this line calls can't see or clobber them.
+    pub fn temporaries(mut self, scope: VariableScope) -> Self {
+        self.1 = scope;
+        self
+    }
+    fn var(&self) -> Value {
+        Value::Variable(self.0.clone())
+    }
+    // Temporaries used to build larger dictionaries, named like function return variables
+    pub fn key_list(&self) -> Variable {
+        Variable { name: format!("{}.keys", self.0.name), scope: self.1 }
+    }
+    pub fn value_list(&self) -> Variable {
+        Variable { name: format!("{}.values", self.0.name), scope: self.1 }
+    }
+    // Either an empty CreateDict and one SetDictValue per entry, or key and value
+    // lists passed to CreateDict, whichever takes fewer blocks
+    pub fn create(&self, entries: Vec<(Value, Value)>) -> Vec<Statement> {
+        let n = entries.len();
+        let list_blocks = 2 * n.div_ceil(PER_BLOCK) + 1;
+        if n < list_blocks {
+            let mut out = vec![set_var("CreateDict", &self.0, vec![])];
+            out.extend(entries.into_iter().map(|(k, v)| self.set(k, v)));
+            return out;
+        }
+        let (keys, values): (Vec<Value>, Vec<Value>) = entries.into_iter().unzip();
+        let (key_list, value_list) = (self.key_list(), self.value_list());
+        let mut out = List::new(key_list.clone()).create(keys);
+        out.extend(List::new(value_list.clone()).create(values));
+        out.push(set_var("CreateDict", &self.0, vec![Value::Variable(key_list), Value::Variable(value_list)]));
+        out
+    }
+    pub fn get(&self, key: Value, dest: &Variable) -> Statement {
+        set_var("GetDictValue", dest, vec![self.var(), key])
+    }
+    pub fn set(&self, key: Value, value: Value) -> Statement {
+        set_var("SetDictValue", &self.0, vec![key, value])
+    }
+    pub fn remove(&self, key: Value) -> Statement {
+        set_var("RemoveDictEntry", &self.0, vec![key])
+    }
+    pub fn keys(&self, dest: &Variable) -> Statement {
+        set_var("GetDictKeys", dest, vec![self.var()])
+    }
+    pub fn values(&self, dest: &Variable) -> Statement {
+        set_var("GetDictValues", dest, vec![self.var()])
+    }
+    pub fn size(&self, dest: &Variable) -> Statement {
+        set_var("DictSize", dest, vec![self.var()])
+    }
+    // Runs the body once per entry, with the entry in `key` and `value`
+    pub fn for_each(&self, key: &Variable, value: &Variable, body: Vec<Statement>) -> Vec<Statement> {
+        let mut out = vec![Statement::Repeat {
+            action: String::from("ForEachEntry"),
+            subaction: None,
+            parameters: ParamBuilder::new().param(Value::Variable(key.clone())).param(Value::Variable(value.clone())).param(self.var()).complete_unchecked(),
+            not: false
+        }];
+        out.extend(body);
+        out.push(Statement::CloseRepeat);
+        out
+    }
+}
+
+// List and dictionary literals: [1, "two", three] and {"a": 1, "b": x}.
+// Items are numbers, quoted text or local variable names; literals don't nest.
+pub enum Literal {
+    List(Vec<Value>),
+    Dict(Vec<(Value, Value)>)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LiteralError {
+    Expected { expected: &'static str, at: usize },
+    Nested { at: usize },
+    Trailing { at: usize }
+}
+impl Display for LiteralError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LiteralError::Expected { expected, at } => write!(f, "expected {expected} at {at}"),
+            LiteralError::Nested { at } => write!(f, "nested list or dictionary at {at}; assign it to a variable first"),
+            LiteralError::Trailing { at } => write!(f, "unexpected text after the literal at {at}"),
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars: Vec<(usize, char)>,
+    source: &'a str,
+    pos: usize
+}
+impl Parser<'_> {
+    fn at(&self) -> usize {
+        self.chars.get(self.pos).map_or(self.source.len(), |(i, _)| *i)
+    }
+    fn skip_space(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|(_, c)| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+    fn peek(&mut self) -> Option<char> {
+        self.skip_space();
+        self.chars.get(self.pos).map(|(_, c)| *c)
+    }
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<(), LiteralError> {
+        if self.eat(c) { Ok(()) } else { Err(LiteralError::Expected { expected, at: self.at() }) }
+    }
+    fn item(&mut self) -> Result<Value, LiteralError> {
+        let next = self.peek();
+        let at = self.at();
+        match next {
+            Some('[' | '{') => Err(LiteralError::Nested { at }),
+            Some('"') => {
+                self.pos += 1;
+                let mut text = String::new();
+                loop {
+                    match self.chars.get(self.pos).map(|(_, c)| *c) {
+                        None => return Err(LiteralError::Expected { expected: "a closing quote", at: self.at() }),
+                        Some('"') => break,
+                        Some('\\') if self.chars.get(self.pos + 1).is_some() => {
+                            text.push(self.chars[self.pos + 1].1);
+                            self.pos += 2;
+                        }
+                        Some(c) => {
+                            text.push(c);
+                            self.pos += 1;
+                        }
+                    }
+                }
+                self.pos += 1;
+                Ok(Value::Text(Text(text)))
+            }
+            Some(_) => {
+                let start = self.pos;
+                while self.chars.get(self.pos).is_some_and(|(_, c)| !matches!(c, ',' | ':' | ']' | '}') && !c.is_whitespace()) {
+                    self.pos += 1;
+                }
+                let word: String = self.chars[start..self.pos].iter().map(|(_, c)| c).collect();
+                if let Some(n) = Decimal::parse(&word) {
+                    Ok(Value::Number(Number::Literal(n)))
+                } else if !word.is_empty() && word.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.') {
+                    Ok(Value::Variable(Variable { name: word, scope: VariableScope::Local }))
+                } else {
+                    Err(LiteralError::Expected { expected: "a number, text or variable", at })
+                }
+            }
+            None => Err(LiteralError::Expected { expected: "a value", at })
+        }
+    }
+    // Comma separated until `close`, allowing a trailing comma
+    fn sequence<T>(&mut self, close: char, mut one: impl FnMut(&mut Self) -> Result<T, LiteralError>) -> Result<Vec<T>, LiteralError> {
+        let mut out = vec![];
+        while !self.eat(close) {
+            out.push(one(self)?);
+            if !self.eat(',') {
+                self.expect(close, "a comma or the end of the literal")?;
+                break;
+            }
+        }
+        Ok(out)
+    }
+}
+
+pub fn parse_literal(source: &str) -> Result<Literal, LiteralError> {
+    let mut p = Parser { chars: source.char_indices().collect(), source, pos: 0 };
+    let literal = if p.eat('[') {
+        Literal::List(p.sequence(']', |p| p.item())?)
+    } else if p.eat('{') {
+        Literal::Dict(p.sequence('}', |p| {
+            let key = p.item()?;
+            p.expect(':', "a colon")?;
+            Ok((key, p.item()?))
+        })?)
+    } else {
+        return Err(LiteralError::Expected { expected: "[ or {", at: p.at() });
+    };
+    if p.peek().is_some() {
+        return Err(LiteralError::Trailing { at: p.at() });
+    }
+    Ok(literal)
+}
+
+impl Literal {
+    // The blocks that store this literal in `dest`
+    pub fn lower(self, dest: &Variable) -> Vec<Statement> {
+        match self {
+            Literal::List(values) => List::new(dest.clone()).create(values),
+            Literal::Dict(entries) => Dict::new(dest.clone()).create(entries)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code_line::CodeLine, program::Program, serialization::DFSerializable, testing::{local, var}, typeck::TypeChecker, values::ValueType};
+
+    fn actions(stmnts: &[Statement]) -> Vec<String> {
+        stmnts.iter().map(|s| s.action().map(String::from).unwrap_or_default()).collect()
+    }
+
+    #[test]
+    fn test_list_literal() {
+        let lowered = parse_literal("[1, \"two\", three]").unwrap().lower(&local("xs"));
+        assert_eq!(actions(&lowered), ["CreateList"]);
+        assert!(lowered[0].serialize().contains(r#"{"item":{"id":"var","data":{"name":"three","scope":"local"}},"slot":3}"#));
+        assert_eq!(actions(&parse_literal("[]").unwrap().lower(&local("xs"))), ["CreateList"]);
+    }
+
+    #[test]
+    fn test_long_list() {
+        let long = List::new(local("xs")).create((0..30).map(|n| Value::Number(Number::from(n))).collect());
+        assert_eq!(actions(&long), ["CreateList", "AppendValue"]);
+    }
+
+    #[test]
+    fn test_dict_literal() {
+        // Small dictionaries are set entry by entry, larger ones go through key and value lists
+        assert_eq!(actions(&parse_literal(r#"{"a": 1, "b": 2}"#).unwrap().lower(&local("d"))), ["CreateDict", "SetDictValue", "SetDictValue"]);
+        let big = parse_literal(r#"{"a": 1, "b": 2, "c": x,}"#).unwrap().lower(&local("d"));
+        assert_eq!(actions(&big), ["CreateList", "CreateList", "CreateDict"]);
+        assert!(big[2].serialize().contains(r#""name":"d.keys""#));
+        assert!(matches!(parse_literal("{}"), Ok(Literal::Dict(e)) if e.is_empty()));
+    }
+
+    #[test]
+    fn test_literal_errors() {
+        assert!(matches!(parse_literal("[1, [2]]"), Err(LiteralError::Nested { at: 4 })));
+        assert!(matches!(parse_literal("{\"a\" 1}"), Err(LiteralError::Expected { expected: "a colon", .. })));
+        assert!(matches!(parse_literal("[1] 2"), Err(LiteralError::Trailing { at: 4 })));
+    }
+
+    #[test]
+    fn test_helpers_type_check() {
+        let (xs, d) = (List::new(local("xs")), Dict::new(local("d")));
+        let mut body = xs.create(vec![Value::Number(Number::from(1))]);
+        body.extend(xs.for_each(&local("x"), vec![d.set(Value::Text(Text(String::from("last"))), var("x"))]));
+        body.extend(d.for_each(&local("k"), &local("v"), vec![xs.set(Value::Number(Number::from(1)), var("v"))]));
+        body.push(d.keys(&local("ks")));
+        body.push(d.size(&local("n")));
+        body.push(xs.get(Value::Number(Number::from(1)), &local("first")));
+        body.insert(0, Statement::PlayerEvent(String::from("Join")));
+        let p = Program::new_from(vec![CodeLine::new_from(body)]);
+        let line = TypeChecker::new(&p).check_line(&p.lines()[0]);
+        assert!(line.errors.is_empty());
+        assert_eq!(line.types.get(&local("ks")), Some(&ValueType::List));
+        assert_eq!(line.types.get(&local("n")), Some(&ValueType::Number));
+        assert_eq!(line.types.get(&local("k")), Some(&ValueType::Text));
+    }
+
+    #[test]
+    fn test_temporaries() {
+        let dict = Dict::new(local("d")).temporaries(VariableScope::Local);
+        assert_eq!(dict.key_list().scope, VariableScope::Local);
+        assert_eq!(Dict::new(local("d")).value_list().scope, VariableScope::Line);
+        // Temporaries of a dictionary built through lists follow the chosen scope
+        let built = Dict::new(local("d")).temporaries(VariableScope::Local).create((0..3).map(|i| (Value::Text(Text(i.to_string())), Value::Number(Number::from(i)))).collect());
+        assert!(built[2].serialize().contains(r#"{"id":"var","data":{"name":"d.keys","scope":"local"}}"#));
+    }
+}
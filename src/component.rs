@@ -0,0 +1,488 @@
+use std::fmt::Display;
+
+use crate::formatting::{self, Format, Token};
+
+// Styled text is stored the way DiamondFire stores it, as MiniMessage.
+// https://docs.advntr.dev/minimessage/format.html
+
+pub const COLORS: &[&str] = &[
+    "black", "dark_blue", "dark_green", "dark_aqua", "dark_red", "dark_purple", "gold", "gray",
+    "dark_gray", "blue", "green", "aqua", "red", "light_purple", "yellow", "white"
+];
+pub const DECORATIONS: &[&str] = &["bold", "italic", "underlined", "strikethrough", "obfuscated"];
+
+#[derive(Debug, PartialEq)]
+pub enum ComponentError {
+    // A `<` that is never closed by `>`
+    Unterminated { at: usize },
+    UnknownTag { tag: String, at: usize },
+    BadArgument { tag: String, argument: String },
+    // A closing tag for something that isn't open
+    UnexpectedClose { tag: String, at: usize }
+}
+impl Display for ComponentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ComponentError::Unterminated { at } => write!(f, "tag at {at} is never closed with >"),
+            ComponentError::UnknownTag { tag, at } => write!(f, "unknown tag <{tag}> at {at}"),
+            ComponentError::BadArgument { tag, argument } => write!(f, "invalid argument {argument:?} for <{tag}>"),
+            ComponentError::UnexpectedClose { tag, at } => write!(f, "</{tag}> at {at} closes a tag that isn't open"),
+        }
+    }
+}
+
+// Valid MiniMessage. Build one with parse, the builder or from_legacy.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Component(String);
+
+#[derive(Debug, PartialEq)]
+pub enum Node {
+    Text(String),
+    Open { tag: String, args: Vec<String> },
+    Close(String)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClickAction {
+    OpenUrl, RunCommand, SuggestCommand, CopyToClipboard, ChangePage
+}
+impl ClickAction {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "open_url" => Some(ClickAction::OpenUrl),
+            "run_command" => Some(ClickAction::RunCommand),
+            "suggest_command" => Some(ClickAction::SuggestCommand),
+            "copy_to_clipboard" => Some(ClickAction::CopyToClipboard),
+            "change_page" => Some(ClickAction::ChangePage),
+            _ => None
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClickAction::OpenUrl => "open_url",
+            ClickAction::RunCommand => "run_command",
+            ClickAction::SuggestCommand => "suggest_command",
+            ClickAction::CopyToClipboard => "copy_to_clipboard",
+            ClickAction::ChangePage => "change_page",
+        }
+    }
+}
+
+pub fn is_hex_color(s: &str) -> bool {
+    s.len() == 7 && s.starts_with('#') && s[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+fn is_color(s: &str) -> bool {
+    is_hex_color(s) || COLORS.contains(&canonical_color(s))
+}
+fn canonical_color(s: &str) -> &str {
+    match s {
+        "grey" => "gray",
+        "dark_grey" => "dark_gray",
+        s => s
+    }
+}
+
+// Short forms share a name with the tag they stand for, so closing tags can be matched
+fn canonical(tag: &str) -> &str {
+    match tag.trim_start_matches('!') {
+        "b" => "bold",
+        "i" | "em" => "italic",
+        "u" => "underlined",
+        "st" => "strikethrough",
+        "obf" => "obfuscated",
+        "colour" | "c" => "color",
+        "tr" | "translate" => "lang",
+        "sel" => "selector",
+        t if is_color(t) => "color",
+        t => t
+    }
+}
+
+// Escapes text so none of it is read as a tag
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('<', "\\<")
+}
+
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+// Splits tag contents on `:`, keeping quoted arguments whole
+fn split_args(inner: &str) -> Vec<String> {
+    let mut out = vec![String::new()];
+    let mut quote: Option<char> = None;
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', Some(_)) => if let Some(n) = chars.next() {
+                out.last_mut().unwrap().push(n);
+            },
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (':', None) => out.push(String::new()),
+            (c, _) => out.last_mut().unwrap().push(c)
+        }
+    }
+    out
+}
+
+pub fn parse(source: &str) -> Result<Vec<Node>, ComponentError> {
+    let mut nodes = vec![];
+    let mut text = String::new();
+    let mut open: Vec<String> = vec![];
+    let mut chars = source.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        match c {
+            '\\' => match chars.peek() {
+                Some((_, n @ ('<' | '\\'))) => {
+                    text.push(*n);
+                    chars.next();
+                }
+                _ => text.push('\\')
+            },
+            '<' => {
+                // Find the matching > outside of quotes
+                let mut quote: Option<char> = None;
+                let mut end = None;
+                let mut escaped = false;
+                for (i, c) in source[at + 1..].char_indices() {
+                    match (c, quote) {
+                        _ if escaped => escaped = false,
+                        ('\\', Some(_)) => escaped = true,
+                        ('\'' | '"', None) => quote = Some(c),
+                        (c, Some(q)) if c == q => quote = None,
+                        ('>', None) => {
+                            end = Some(at + 1 + i);
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                let end = end.ok_or(ComponentError::Unterminated { at })?;
+                let inner = &source[at + 1..end];
+                while chars.peek().is_some_and(|(i, _)| *i <= end) {
+                    chars.next();
+                }
+                if !text.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text)));
+                }
+                if let Some(name) = inner.strip_prefix('/') {
+                    let name = split_args(name).remove(0);
+                    let tag = canonical(&name);
+                    let Some(index) = open.iter().rposition(|t| t == tag) else {
+                        return Err(ComponentError::UnexpectedClose { tag: name, at });
+                    };
+                    // Closing a tag also closes everything opened inside it
+                    open.truncate(index);
+                    nodes.push(Node::Close(name));
+                    continue;
+                }
+                let self_closing = inner.ends_with('/');
+                let mut args = split_args(inner.trim_end_matches('/'));
+                let tag = args.remove(0);
+                let pushes = check_tag(&tag, &args, at)?;
+                if tag == "reset" {
+                    open.clear();
+                } else if pushes && !self_closing {
+                    open.push(String::from(canonical(&tag)));
+                }
+                nodes.push(Node::Open { tag, args });
+            }
+            c => text.push(c)
+        }
+    }
+    if !text.is_empty() {
+        nodes.push(Node::Text(text));
+    }
+    Ok(nodes)
+}
+
+// Whether the tag styles what follows it, as opposed to inserting something
+fn check_tag(tag: &str, args: &[String], at: usize) -> Result<bool, ComponentError> {
+    let bad = |argument: &str| Err(ComponentError::BadArgument { tag: String::from(tag), argument: String::from(argument) });
+    match canonical(tag) {
+        "color" if is_color(tag) => Ok(true),
+        "color" => match args {
+            [c] if is_color(c) => Ok(true),
+            [c] => bad(c),
+            _ => bad(&args.join(":"))
+        },
+        t if DECORATIONS.contains(&t) => match args {
+            [] => Ok(true),
+            [b] if b == "false" || b == "true" => Ok(true),
+            [a, ..] => bad(a)
+        },
+        "gradient" | "transition" => {
+            // Colors, optionally followed by a phase between -1 and 1
+            let (phase, colors) = match args.split_last() {
+                Some((p, rest)) if p.parse::<f32>().is_ok() => (p.parse::<f32>().ok(), rest),
+                _ => (None, args)
+            };
+            if let Some(c) = colors.iter().find(|c| !is_color(c)) {
+                return bad(c);
+            }
+            match phase {
+                Some(p) if !(-1.0..=1.0).contains(&p) => bad(&p.to_string()),
+                _ => Ok(true)
+            }
+        }
+        "rainbow" => match args {
+            [] => Ok(true),
+            [p] if p.trim_start_matches('!').is_empty() || p.trim_start_matches('!').parse::<i32>().is_ok() => Ok(true),
+            [a, ..] => bad(a)
+        },
+        "hover" => match args {
+            [action, text] if action == "show_text" => {
+                parse(text)?;
+                Ok(true)
+            }
+            [action, ..] if action == "show_item" || action == "show_entity" => Ok(true),
+            _ => bad(&args.join(":"))
+        },
+        "click" => match args {
+            [action, _] if ClickAction::parse(action).is_some() => Ok(true),
+            _ => bad(&args.join(":"))
+        },
+        "insertion" | "font" => match args {
+            [_] => Ok(true),
+            _ => bad(&args.join(":"))
+        },
+        "key" | "lang" | "selector" | "score" | "nbt" => match args {
+            [] => bad(""),
+            _ => Ok(false)
+        },
+        "newline" | "br" | "reset" => Ok(false),
+        _ => Err(ComponentError::UnknownTag { tag: String::from(tag), at })
+    }
+}
+
+impl Component {
+    pub fn parse(source: &str) -> Result<Self, ComponentError> {
+        parse(source)?;
+        Ok(Component(String::from(source)))
+    }
+    pub fn builder() -> ComponentBuilder {
+        ComponentBuilder::new()
+    }
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+    // For rewrites that only touch the text codes, such as renaming variables.
+    // Text that isn't valid MiniMessage is refused and the component kept as it was.
+    pub fn set_source(&mut self, source: String) -> Result<(), ComponentError> {
+        parse(&source)?;
+        self.0 = source;
+        Ok(())
+    }
+    pub fn check(&self) -> Result<(), ComponentError> {
+        parse(&self.0).map(|_| ())
+    }
+    // The text with every tag removed
+    pub fn to_plain(&self) -> String {
+        parse(&self.0).unwrap_or_default().into_iter().filter_map(|n| match n {
+            Node::Text(t) => Some(t),
+            Node::Open { tag, .. } if tag == "newline" || tag == "br" => Some(String::from("\n")),
+            _ => None
+        }).collect()
+    }
+    // Converts § and & formatting codes, including hex colors.
+    // Like in game, a color code turns off the formatting before it.
+    pub fn from_legacy(legacy: &str) -> Self {
+        let mut out = String::new();
+        let mut styled = false;
+        for token in formatting::tokens(legacy) {
+            let tag = match token {
+                Token::Char(c) => {
+                    out += &escape(&c.to_string());
+                    continue;
+                }
+                Token::Format(Format::Color(c)) => c.name(),
+                Token::Format(Format::Reset) => String::from("reset"),
+                Token::Format(Format::Obfuscated) => String::from("obfuscated"),
+                Token::Format(Format::Bold) => String::from("bold"),
+                Token::Format(Format::Strikethrough) => String::from("strikethrough"),
+                Token::Format(Format::Underlined) => String::from("underlined"),
+                Token::Format(Format::Italic) => String::from("italic"),
+            };
+            if tag == "reset" || is_color(&tag) {
+                if styled {
+                    out += "<reset>";
+                }
+                styled = false;
+                if tag == "reset" {
+                    continue;
+                }
+            }
+            out += &format!("<{tag}>");
+            styled = true;
+        }
+        Component(out)
+    }
+}
+
+pub struct ComponentBuilder {
+    out: String,
+    open: Vec<String>
+}
+impl Default for ComponentBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ComponentBuilder {
+    pub fn new() -> Self {
+        Self { out: String::new(), open: vec![] }
+    }
+    fn open(mut self, tag: &str, args: &[String]) -> Self {
+        self.out += "<";
+        self.out += tag;
+        for a in args {
+            self.out += ":";
+            self.out += a;
+        }
+        self.out += ">";
+        self.open.push(String::from(tag));
+        self
+    }
+    pub fn text(mut self, text: &str) -> Self {
+        self.out += &escape(text);
+        self
+    }
+    // Adds already styled text as is
+    pub fn append(mut self, component: &Component) -> Self {
+        self.out += &component.0;
+        self
+    }
+    pub fn newline(mut self) -> Self {
+        self.out += "<newline>";
+        self
+    }
+    // A named color or #rrggbb
+    pub fn color(self, color: &str) -> Self {
+        self.open("color", &[String::from(color)])
+    }
+    pub fn bold(self) -> Self {
+        self.open("bold", &[])
+    }
+    pub fn italic(self) -> Self {
+        self.open("italic", &[])
+    }
+    pub fn underlined(self) -> Self {
+        self.open("underlined", &[])
+    }
+    pub fn strikethrough(self) -> Self {
+        self.open("strikethrough", &[])
+    }
+    pub fn obfuscated(self) -> Self {
+        self.open("obfuscated", &[])
+    }
+    pub fn gradient(self, colors: &[&str]) -> Self {
+        let colors: Vec<String> = colors.iter().map(|c| String::from(*c)).collect();
+        self.open("gradient", &colors)
+    }
+    pub fn hover(self, text: &Component) -> Self {
+        self.open("hover", &[String::from("show_text"), quote(&text.0)])
+    }
+    pub fn click(self, action: ClickAction, value: &str) -> Self {
+        self.open("click", &[String::from(action.name()), quote(value)])
+    }
+    // Ends the most recently opened style
+    pub fn close(mut self) -> Self {
+        if let Some(tag) = self.open.pop() {
+            self.out += &format!("</{tag}>");
+        }
+        self
+    }
+    pub fn build(mut self) -> Result<Component, ComponentError> {
+        while !self.open.is_empty() {
+            self = self.close();
+        }
+        Component::parse(&self.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code_line::CodeLine, decompile::{parse_templates, DecompileError}, params::ParamBuilder, program::Program, serialization::DFSerializable, statements::Statement, typeck::TypeChecker, values::{Component, Selector, Value}};
+
+    fn welcome() -> Component {
+        Component::builder()
+            .color("gold").bold().text("Welcome").close().close()
+            .text(" <player> ")
+            .hover(&Component::parse("<gray>Click to warp").unwrap()).click(ClickAction::RunCommand, "/warp 'spawn'").text("[spawn]")
+            .build()
+            .unwrap()
+    }
+    // A title whose styled text reads a variable
+    fn title() -> CodeLine {
+        CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            Statement::PlayerAction { action: String::from("SendTitle"), parameters: ParamBuilder::new().param(Value::Component(Component::parse("<red>%var(x)").unwrap())).complete_unchecked(), selector: Selector::Default },
+        ])
+    }
+
+    #[test]
+    fn test_builder() {
+        assert_eq!(welcome().as_str(), r"<color:gold><bold>Welcome</bold></color> \<player> <hover:show_text:'<gray>Click to warp'><click:run_command:'/warp \'spawn\''>[spawn]</click></hover>");
+    }
+
+    #[test]
+    fn test_plain() {
+        assert_eq!(welcome().to_plain(), "Welcome <player> [spawn]");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(Component::parse("<gradient:#ff0000:blue:0.5>ok</gradient><b>!<reset>").is_ok());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Component::parse("<blink>hi"), Err(ComponentError::UnknownTag { tag: String::from("blink"), at: 0 }));
+        assert_eq!(Component::parse("hi</bold>"), Err(ComponentError::UnexpectedClose { tag: String::from("bold"), at: 2 }));
+        assert_eq!(Component::parse("<color:pink>"), Err(ComponentError::BadArgument { tag: String::from("color"), argument: String::from("pink") }));
+        assert_eq!(Component::parse("a <red"), Err(ComponentError::Unterminated { at: 2 }));
+    }
+
+    #[test]
+    fn test_builder_bad_color() {
+        assert!(Component::builder().color("#12345").build().is_err());
+    }
+
+    #[test]
+    fn test_from_legacy() {
+        assert_eq!(Component::from_legacy("§a§lHi §rthere&cTom & Jerry").as_str(), "<green><bold>Hi <reset>there<red>Tom & Jerry");
+        assert_eq!(Component::from_legacy("&x&f&f&8&8&5&5Compiled &8»").as_str(), "<#ff8855>Compiled <reset><dark_gray>»");
+    }
+
+    #[test]
+    fn test_text_codes_type_check() {
+        let p = Program::new_from(vec![ title() ]);
+        assert!(TypeChecker::new(&p).check_line(&p.lines()[0]).errors.is_empty());
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let json = title().serialize();
+        assert!(json.contains(r#"{"id":"comp","data":{"name":"<red>%var(x)"}}"#));
+        assert_eq!(parse_templates(&json).unwrap()[0].serialize(), json);
+    }
+
+    #[test]
+    fn test_decompile_checks_tags() {
+        let json = title().serialize();
+        assert!(matches!(parse_templates(&json.replace("<red>", "<blink>")), Err(DecompileError::Malformed(_))));
+    }
+
+    #[test]
+    fn test_set_source() {
+        // Rewrites can't leave the MiniMessage broken
+        let mut c = Component::parse("<red>%var(x)").unwrap();
+        assert!(c.set_source(String::from("<red>%var(x)</bold>")).is_err());
+        assert_eq!(c.as_str(), "<red>%var(x)");
+        assert!(c.set_source(String::from("<red>%var(y)")).is_ok());
+        assert_eq!(c.as_str(), "<red>%var(y)");
+    }
+}
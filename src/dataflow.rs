@@ -0,0 +1,405 @@
+use std::collections::BTreeSet;
+
+use crate::{textcode, catalog::action_spec, cfg::Cfg, code_line::CodeLine, statements::Statement, values::{Number, Text, Value, Variable, VariableScope}};
+
+// A variable as the analyses see it. Parameters are line variables in game, so
+// they share a key with line variables of the same name.
+pub type Key = (VariableScope, String);
+
+pub fn key(var: &Variable) -> Key {
+    let scope = if var.scope.is_line() { VariableScope::Line } else { var.scope };
+    (scope, var.name.clone())
+}
+
+// Sets of local and line variables. Calls can read or write any local of the
+// caller, which is what all_locals stands for; line variables stay with their line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VarSet {
+    pub all_locals: bool,
+    pub all_lines: bool,
+    pub vars: BTreeSet<Key>
+}
+impl VarSet {
+    pub fn empty() -> Self {
+        Self { all_locals: false, all_lines: false, vars: BTreeSet::new() }
+    }
+    pub fn all() -> Self {
+        Self { all_locals: true, all_lines: true, vars: BTreeSet::new() }
+    }
+    fn locals() -> Self {
+        Self { all_locals: true, ..Self::empty() }
+    }
+    fn only(vars: &BTreeSet<Key>) -> Self {
+        Self { vars: vars.clone(), ..Self::empty() }
+    }
+    pub fn contains(&self, key: &Key) -> bool {
+        match key.0 {
+            VariableScope::Local if self.all_locals => true,
+            VariableScope::Line if self.all_lines => true,
+            _ => self.vars.contains(key)
+        }
+    }
+    fn union(&self, other: &VarSet) -> VarSet {
+        Self {
+            all_locals: self.all_locals || other.all_locals,
+            all_lines: self.all_lines || other.all_lines,
+            vars: self.vars.union(&other.vars).cloned().collect()
+        }
+    }
+    fn intersection(&self, other: &VarSet) -> VarSet {
+        Self {
+            all_locals: self.all_locals && other.all_locals,
+            all_lines: self.all_lines && other.all_lines,
+            vars: self.vars.iter().filter(|k| other.contains(k))
+                .chain(other.vars.iter().filter(|k| self.contains(k)))
+                .cloned()
+                .collect()
+        }
+    }
+    fn without(&self, keys: &BTreeSet<Key>) -> VarSet {
+        Self { vars: self.vars.difference(keys).cloned().collect(), ..self.clone() }
+    }
+}
+
+// Set variable actions that read the variable they write to
+const UPDATES: &[&str] = &[
+    "+=", "-=", "AppendValue", "AppendList", "SetListValue", "InsertListValue", "RemoveListValue", "RemoveListIndex",
+    "SortList", "ReverseList", "RandomizeList", "DedupList", "TrimList", "SetDictValue", "RemoveDictEntry", "ClearDict",
+    "SortDict", "AppendDict"
+];
+
+// What one block reads and writes, for local and line variables only
+#[derive(Default, Debug, PartialEq)]
+pub struct Access {
+    pub uses: BTreeSet<Key>,
+    pub defs: BTreeSet<Key>,
+    // Names read through text codes, which could be either scope
+    pub codes: BTreeSet<String>,
+    // Calls can read and write anything
+    pub call: bool
+}
+
+fn local(value: &Value) -> Option<&Variable> {
+    match value {
+        Value::Variable(v) if v.scope == VariableScope::Local || v.scope.is_line() => Some(v),
+        _ => None
+    }
+}
+
+// Names read through %var(...) text codes
+fn text_uses(text: &str, out: &mut BTreeSet<String>) {
+    if let Ok(code) = textcode::parse(text) {
+        out.extend(code.variables().into_iter().map(String::from));
+        return;
+    }
+    let mut rest = text;
+    while let Some(start) = rest.find("%var(") {
+        rest = &rest[start + 5..];
+        let Some(end) = rest.find(')') else { break };
+        out.insert(String::from(&rest[..end]));
+        rest = &rest[end + 1..];
+    }
+}
+
+pub fn access(stmnt: &Statement) -> Access {
+    let mut out = Access::default();
+    match stmnt {
+        Statement::CallFunction { .. } | Statement::CallProcess { .. } => out.call = true,
+        // Parameters arrive as line variables, and older templates read them as locals
+        Statement::Function { parameters, .. } | Statement::Process { parameters, .. } => {
+            for value in parameters.iter().flatten() {
+                if let Value::Parameter(p) = value {
+                    out.defs.insert((VariableScope::Line, p.name.clone()));
+                    out.defs.insert((VariableScope::Local, p.name.clone()));
+                }
+            }
+            return out;
+        }
+        _ => {}
+    }
+    let Some(parameters) = stmnt.parameters() else { return out };
+    let writes = match (stmnt, stmnt.action()) {
+        (Statement::SetVariable { .. }, Some(action)) => Some(!UPDATES.contains(&action)),
+        (Statement::Repeat { .. }, Some(action)) => {
+            let count = parameters.iter().flatten().filter(|a| !matches!(a, Value::Tag(_))).count();
+            action_spec("repeat", action)
+                .filter(|spec| spec.takes_target(count))
+                .map(|_| true)
+        }
+        _ => None
+    };
+    for (slot, value) in parameters.iter().enumerate() {
+        let Some(value) = value else { continue };
+        match (slot, writes, local(value)) {
+            (0, Some(overwrites), Some(v)) => {
+                out.defs.insert(key(v));
+                if !overwrites {
+                    out.uses.insert(key(v));
+                }
+            }
+            // The entry's value variable is written too
+            (1, Some(_), Some(v)) if matches!(stmnt, Statement::Repeat { action, .. } if action == "ForEachEntry") => {
+                out.defs.insert(key(v));
+            }
+            (_, _, Some(v)) => {
+                out.uses.insert(key(v));
+            }
+            _ => {}
+        }
+        match value {
+            Value::Text(Text(t)) => text_uses(t, &mut out.codes),
+            Value::Component(c) => text_uses(c.as_str(), &mut out.codes),
+            Value::Number(Number::Math(e)) => text_uses(&e.to_code(), &mut out.codes),
+            _ => {}
+        }
+    }
+    out
+}
+
+impl Access {
+    // Everything this block may read, counting a text code as a read of both scopes
+    fn reads(&self) -> BTreeSet<Key> {
+        let mut out = self.uses.clone();
+        for name in &self.codes {
+            out.insert((VariableScope::Local, name.clone()));
+            out.insert((VariableScope::Line, name.clone()));
+        }
+        out
+    }
+}
+
+pub struct Liveness {
+    pub live_in: Vec<VarSet>,
+    pub live_out: Vec<VarSet>
+}
+impl Liveness {
+    // What has to survive past the end of the line: nothing for an event,
+    // since its locals die with it, but a function's caller may read any local
+    pub fn compute(line: &CodeLine, cfg: &Cfg) -> Self {
+        let accesses: Vec<Access> = line.body().iter().map(access).collect();
+        let at_exit = match line.body().first() {
+            Some(Statement::PlayerEvent(_)) | Some(Statement::EntityEvent(_)) => VarSet::empty(),
+            _ => VarSet::locals()
+        };
+        let mut live_in = vec![VarSet::empty(); cfg.exit + 1];
+        let mut live_out = vec![VarSet::empty(); cfg.exit + 1];
+        live_in[cfg.exit] = at_exit;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for n in (0..cfg.exit).rev() {
+                let out = cfg.successors[n].iter().fold(VarSet::empty(), |acc, s| acc.union(&live_in[*s]));
+                let a = &accesses[n];
+                // A call may read any local, but not this line's line variables
+                let inn = if a.call { out.union(&VarSet::locals()) } else { out.without(&a.defs).union(&VarSet::only(&a.reads())) };
+                if inn != live_in[n] || out != live_out[n] {
+                    live_in[n] = inn;
+                    live_out[n] = out;
+                    changed = true;
+                }
+            }
+        }
+        Self { live_in, live_out }
+    }
+    pub fn is_live_after(&self, statement: usize, var: &Variable) -> bool {
+        self.live_out.get(statement).is_some_and(|s| s.contains(&key(var)))
+    }
+    // For the temporary allocator: the first candidate holding nothing that is
+    // read after `statement`, so it can be reused there
+    pub fn free_after<'a>(&self, statement: usize, candidates: &'a [Variable]) -> Option<&'a Variable> {
+        candidates.iter().find(|v| (v.scope == VariableScope::Local || v.scope == VariableScope::Line) && !self.is_live_after(statement, v))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FlowWarning {
+    UseBeforeAssignment { statement: usize, variable: String },
+    DeadStore { statement: usize, variable: String }
+}
+
+// Variables read before anything on some path assigned them. Functions and
+// processes can be handed locals by their caller, so there only line variables count.
+pub fn uses_before_assignment(line: &CodeLine, cfg: &Cfg) -> Vec<FlowWarning> {
+    let accesses: Vec<Access> = line.body().iter().map(access).collect();
+    // Definitely assigned on entry to each block; everything until shown otherwise
+    let mut assigned = vec![VarSet::all(); cfg.exit + 1];
+    assigned[0] = match line.body().first() {
+        Some(Statement::PlayerEvent(_)) | Some(Statement::EntityEvent(_)) => VarSet::empty(),
+        _ => VarSet::locals()
+    };
+    let out = |n: usize, assigned: &[VarSet]| {
+        let after = assigned[n].union(&VarSet::only(&accesses[n].defs));
+        if accesses[n].call { after.union(&VarSet::locals()) } else { after }
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for n in 1..cfg.exit {
+            let inn = cfg.predecessors[n].iter()
+                .fold(VarSet::all(), |acc, p| acc.intersection(&out(*p, &assigned)));
+            if inn != assigned[n] {
+                assigned[n] = inn;
+                changed = true;
+            }
+        }
+    }
+    let unreachable = cfg.unreachable();
+    let mut warnings = vec![];
+    for (n, a) in accesses.iter().enumerate() {
+        if unreachable.contains(&n) {
+            continue;
+        }
+        let mut unassigned: BTreeSet<&String> = a.uses.iter().filter(|k| !assigned[n].contains(k)).map(|(_, name)| name).collect();
+        // A text code is fine when either scope holds the name
+        unassigned.extend(a.codes.iter().filter(|name| {
+            !assigned[n].contains(&(VariableScope::Local, (*name).clone())) && !assigned[n].contains(&(VariableScope::Line, (*name).clone()))
+        }));
+        for name in unassigned {
+            warnings.push(FlowWarning::UseBeforeAssignment { statement: n, variable: name.clone() });
+        }
+    }
+    warnings
+}
+
+// Set Variable blocks whose result is never read
+pub fn dead_stores(line: &CodeLine, liveness: &Liveness) -> Vec<FlowWarning> {
+    let mut warnings = vec![];
+    for (n, stmnt) in line.body().iter().enumerate() {
+        if !matches!(stmnt, Statement::SetVariable { .. }) {
+            continue;
+        }
+        for (scope, name) in access(stmnt).defs {
+            if !liveness.live_out[n].contains(&(scope, name.clone())) {
+                warnings.push(FlowWarning::DeadStore { statement: n, variable: name });
+            }
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{params::ParamBuilder, testing::{local, set, var, variable}, textcode::Expr, values::{FunctionParam, Selector, ValueType}};
+
+    fn send(value: Value) -> Statement {
+        Statement::PlayerAction { action: String::from("SendMessage"), parameters: ParamBuilder::new().param(value).complete_unchecked(), selector: Selector::Default }
+    }
+
+    // `a` is set on both branches, `b` only on one, and `unused` is never read
+    fn branches() -> CodeLine {
+        let if_var = || Statement::IfVariable { action: String::from("="), parameters: ParamBuilder::new().param(var("flag")).param(Value::Number(Number::from(1))).complete_unchecked(), not: false };
+        CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),                                       // 0
+            set(local("flag"), Value::Number(Number::from(1))),                                 // 1
+            if_var(),                                                                           // 2
+            set(local("a"), Value::Number(Number::from(1))),                                    // 3
+            Statement::Close,                                                                   // 4
+            Statement::Else,                                                                    // 5
+            set(local("a"), Value::Number(Number::from(2))),                                    // 6
+            set(local("b"), Value::Number(Number::from(2))),                                    // 7
+            Statement::Close,                                                                   // 8
+            send(var("a")),                                                                     // 9
+            send(Value::Text(Text(String::from("%var(b)")))),                                   // 10
+            set(local("unused"), var("a")),                                                     // 11
+            Statement::Control { action: String::from("Return"), parameters: ParamBuilder::new().complete_unchecked() }, // 12
+            send(var("missing")),                                                               // 13
+        ])
+    }
+
+    #[test]
+    fn test_use_before_assignment() {
+        let line = branches();
+        let cfg = Cfg::build(&line);
+        assert_eq!(uses_before_assignment(&line, &cfg), vec![
+            FlowWarning::UseBeforeAssignment { statement: 10, variable: String::from("b") },
+        ]);
+    }
+
+    #[test]
+    fn test_liveness() {
+        let line = branches();
+        let liveness = Liveness::compute(&line, &Cfg::build(&line));
+        let a = local("a");
+        assert!(liveness.is_live_after(3, &a));
+        assert!(liveness.is_live_after(9, &a));
+        assert!(!liveness.is_live_after(11, &a));
+    }
+
+    #[test]
+    fn test_dead_stores() {
+        let line = branches();
+        let liveness = Liveness::compute(&line, &Cfg::build(&line));
+        assert_eq!(dead_stores(&line, &liveness), vec![
+            FlowWarning::DeadStore { statement: 11, variable: String::from("unused") },
+        ]);
+    }
+
+    #[test]
+    fn test_free_after() {
+        let line = branches();
+        let liveness = Liveness::compute(&line, &Cfg::build(&line));
+        let temps = [local("flag"), local("a")];
+        assert_eq!(liveness.free_after(2, &temps).map(|v| v.name.as_str()), Some("flag"));
+        assert_eq!(liveness.free_after(11, &temps[1..]).map(|v| v.name.as_str()), Some("a"));
+        assert_eq!(liveness.free_after(3, &temps[1..]), None);
+    }
+
+    #[test]
+    fn test_line_variables() {
+        // A line variable and a local with the same name are different variables,
+        // and parameters are line variables
+        let line = CodeLine::new_from(vec![
+            Statement::Function { name: String::from("f"), parameters: ParamBuilder::new().param(Value::Parameter(FunctionParam::new("p", ValueType::Number))).complete_unchecked() },
+            set(local("x"), Value::Number(Number::from(1))),                                    // 1
+            send(Value::Variable(variable("x", VariableScope::Line))),                          // 2
+            send(Value::Variable(variable("p", VariableScope::Parameter))),                     // 3
+            set(variable("y", VariableScope::Line), Value::Number(Number::from(1))),            // 4
+            Statement::CallFunction { name: String::from("g"), parameters: ParamBuilder::new().complete_unchecked() }, // 5
+        ]);
+        let cfg = Cfg::build(&line);
+        assert_eq!(uses_before_assignment(&line, &cfg), vec![
+            FlowWarning::UseBeforeAssignment { statement: 2, variable: String::from("x") },
+        ]);
+        let liveness = Liveness::compute(&line, &cfg);
+        // The caller can read the local, but nothing reads the line variable
+        assert!(liveness.is_live_after(1, &local("x")));
+        assert!(!liveness.is_live_after(2, &variable("x", VariableScope::Line)));
+        assert_eq!(dead_stores(&line, &liveness), vec![
+            FlowWarning::DeadStore { statement: 4, variable: String::from("y") },
+        ]);
+    }
+
+    #[test]
+    fn test_repeat_multiple() {
+        // Repeat Multiple only writes its first slot when a count follows it
+        let repeat = |parameters| Statement::Repeat { action: String::from("Multiple"), subaction: None, parameters, not: false };
+        let line = CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            set(local("n"), Value::Number(Number::from(3))),                                    // 1
+            repeat(ParamBuilder::new().param(var("n")).complete_unchecked()),                   // 2
+            Statement::Close,                                                                   // 3
+            repeat(ParamBuilder::new().param(var("i")).param(var("n")).complete_unchecked()),   // 4
+            send(var("i")),                                                                     // 5
+            Statement::Close,                                                                   // 6
+        ]);
+        let cfg = Cfg::build(&line);
+        assert!(uses_before_assignment(&line, &cfg).is_empty());
+        let liveness = Liveness::compute(&line, &cfg);
+        assert!(liveness.is_live_after(1, &local("n")));
+        assert!(dead_stores(&line, &liveness).is_empty());
+    }
+
+    #[test]
+    fn test_math_reads() {
+        // A %math number counts as a read of what it mentions
+        let items = local("items");
+        let line = CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            set(items.clone(), Value::Number(Number::from(2))),
+            Statement::Control { action: String::from("Wait"), parameters: ParamBuilder::new().param(Value::Number((Expr::var(&items) * Expr::literal(20.0)).into_number())).complete_unchecked() },
+        ]);
+        let cfg = Cfg::build(&line);
+        assert!(dead_stores(&line, &Liveness::compute(&line, &cfg)).is_empty());
+    }
+}
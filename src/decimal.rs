@@ -0,0 +1,184 @@
+use std::fmt::Display;
+
+// An exact decimal number, so literals come out the way they were written:
+// 0.1 stays 0.1, 1.50 stays 1.50 and 1234567890123 isn't rounded like it would
+// be as an f32. Always written without exponent notation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    mantissa: i128,
+    // Digits after the decimal point
+    scale: u32
+}
+
+impl Decimal {
+    // Arithmetic results drop their trailing zeros; parsed literals keep them
+    fn normalized(mantissa: i128, scale: u32) -> Self {
+        let mut d = Decimal { mantissa, scale };
+        while d.scale > 0 && d.mantissa % 10 == 0 {
+            d.mantissa /= 10;
+            d.scale -= 1;
+        }
+        d
+    }
+    // Accepts an optional sign, digits with an optional fraction and an optional exponent
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let (number, exponent) = match s.find(['e', 'E']) {
+            Some(i) => (&s[..i], s[i + 1..].parse::<i32>().ok()?),
+            None => (s, 0)
+        };
+        let (negative, number) = match number.as_bytes().first() {
+            Some(b'-') => (true, &number[1..]),
+            Some(b'+') => (false, &number[1..]),
+            _ => (false, number)
+        };
+        let (int, frac) = number.split_once('.').unwrap_or((number, ""));
+        if int.is_empty() && frac.is_empty() || !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let mut mantissa: i128 = 0;
+        for c in int.chars().chain(frac.chars()) {
+            mantissa = mantissa.checked_mul(10)?.checked_add(c.to_digit(10)? as i128)?;
+        }
+        if mantissa == 0 {
+            let scale = (frac.len() as i64 - exponent as i64).clamp(0, 38) as u32;
+            return Some(Decimal { mantissa: 0, scale });
+        }
+        // Anything further out doesn't fit in the mantissa either way
+        if exponent.unsigned_abs() as usize > 38 + int.len() + frac.len() {
+            return None;
+        }
+        let mut scale = frac.len() as i64 - exponent as i64;
+        while scale < 0 {
+            mantissa = mantissa.checked_mul(10)?;
+            scale += 1;
+        }
+        // Trailing zeros can bring a long fraction back in range
+        while scale > 38 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        }
+        let scale = u32::try_from(scale).ok().filter(|s| *s <= 38)?;
+        Some(Decimal { mantissa: if negative { -mantissa } else { mantissa }, scale })
+    }
+    pub fn is_positive(self) -> bool {
+        self.mantissa > 0
+    }
+    fn aligned(&self, other: &Decimal) -> Option<(i128, i128, u32)> {
+        let scale = self.scale.max(other.scale);
+        let a = self.mantissa.checked_mul(10i128.checked_pow(scale - self.scale)?)?;
+        let b = other.mantissa.checked_mul(10i128.checked_pow(scale - other.scale)?)?;
+        Some((a, b, scale))
+    }
+    // Exact, None on overflow
+    pub fn checked_add(&self, other: &Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.aligned(other)?;
+        Some(Decimal::normalized(a.checked_add(b)?, scale))
+    }
+    pub fn checked_sub(&self, other: &Decimal) -> Option<Decimal> {
+        let (a, b, scale) = self.aligned(other)?;
+        Some(Decimal::normalized(a.checked_sub(b)?, scale))
+    }
+    pub fn checked_mul(&self, other: &Decimal) -> Option<Decimal> {
+        let scale = self.scale + other.scale;
+        (scale <= 38).then_some(())?;
+        Some(Decimal::normalized(self.mantissa.checked_mul(other.mantissa)?, scale))
+    }
+    // Exact, so None when the quotient doesn't terminate within 38 digits
+    pub fn checked_div(&self, other: &Decimal) -> Option<Decimal> {
+        if other.mantissa == 0 {
+            return None;
+        }
+        // self / other = (a * 10^other.scale) / (b * 10^self.scale)
+        let num = self.mantissa.checked_mul(10i128.checked_pow(other.scale)?)?;
+        let den = other.mantissa.checked_mul(10i128.checked_pow(self.scale)?)?;
+        for scale in 0..=38 {
+            let n = num.checked_mul(10i128.checked_pow(scale)?)?;
+            if n % den == 0 {
+                return Some(Decimal::normalized(n / den, scale));
+            }
+        }
+        None
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(n: i64) -> Self {
+        Decimal { mantissa: n as i128, scale: 0 }
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{sign}{digits}");
+        }
+        let digits = format!("{digits:0>width$}", width = scale + 1);
+        let (int, frac) = digits.split_at(digits.len() - scale);
+        write!(f, "{sign}{int}.{frac}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code_line::CodeLine, decompile::parse_templates, optimize::Optimizer, params::ParamBuilder, program::Program, serialization::DFSerializable, statements::Statement, testing::{local, set_var}, values::{Location, Number, Selector, Value}};
+
+    fn d(s: &str) -> Decimal {
+        Decimal::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(d("0.1").to_string(), "0.1");
+        assert_eq!(d("1234567890123").to_string(), "1234567890123");
+        assert_eq!(d("+007.500").to_string(), "7.500");
+        assert_eq!(d("1.50").to_string(), "1.50");
+        assert_eq!(d("-0.0").to_string(), "0.0");
+        assert_eq!(d("1.5e-7").to_string(), "0.00000015");
+        assert_eq!(d("2E20").to_string(), "200000000000000000000");
+        assert_eq!(d(".5").to_string(), "0.5");
+        assert!(Decimal::parse("1.2.3").is_none() && Decimal::parse("").is_none() && Decimal::parse("%var(x)").is_none());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(d("0.1").checked_add(&d("0.2")), Some(d("0.3")));
+        assert_eq!(d("1.50").checked_add(&d("1.50")).map(|n| n.to_string()).as_deref(), Some("3"));
+        assert_eq!(d("1.5").checked_mul(&d("-4")), Some(d("-6")));
+    }
+
+    #[test]
+    fn test_division() {
+        assert_eq!(d("1").checked_div(&d("0")), None);
+        assert_eq!(d("1").checked_div(&d("8")), Some(d("0.125")));
+        assert_eq!(d("0.3").checked_div(&d("-0.1")), Some(d("-3")));
+        assert_eq!(d("1").checked_div(&d("3")), None);
+    }
+
+    #[test]
+    fn test_exponents() {
+        assert_eq!(d("0e2000000000").to_string(), "0");
+        assert_eq!(d("0e-50").to_string(), format!("0.{}", "0".repeat(38)));
+        assert_eq!(d("1000e-40").to_string(), format!("0.{}10", "0".repeat(36)));
+        assert!(Decimal::parse("1e2000000000").is_none() && Decimal::parse("1e-2000000000").is_none());
+    }
+
+    #[test]
+    fn test_exact_through_templates() {
+        // Exact through templates, optimizer folding and decompiling
+        let mut p = Program::new_from(vec![ CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            set_var("+", local("id"), vec![Value::Number(Number::Literal(d("1234567890123.1"))), Value::Number(Number::Literal(d("0.2")))]),
+            Statement::PlayerAction { action: String::from("Teleport"), parameters: ParamBuilder::new().param(Value::Location(Location::new(0.1, 64.0, -1234567.25).with_rotation(0.0, 90.0))).complete_unchecked(), selector: Selector::Default },
+        ]) ]);
+        Optimizer::new().optimize(&mut p);
+        let json = p.lines()[0].serialize();
+        assert!(json.contains(r#"{"id":"num","data":{"name":"1234567890123.3"}}"#));
+        assert!(json.contains(r#""loc":{"x":0.1,"y":64,"z":-1234567.25,"pitch":0,"yaw":90}"#));
+        assert_eq!(parse_templates(&json).unwrap()[0].serialize(), json);
+    }
+}
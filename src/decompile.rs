@@ -0,0 +1,243 @@
+use std::{fmt::Display, io::Read};
+
+use flate2::read::GzDecoder;
+use serde_json::Value as Json;
+
+use crate::{code_line::CodeLine, diagnostics::{Diagnostic, SourceId, SourceMap, Span}, formatting::compact_hex, serialization::{json_escape, DFSerializable}, statements::Statement, template::Template, validate::opens_bracket, values::{Number, Selector, Text, Value}};
+
+#[derive(Debug, PartialEq)]
+pub enum DecompileError {
+    Encoding(String),
+    // The offset into the source, when the source was the JSON rather than a code
+    Json { message: String, at: Option<usize> },
+    Malformed(String),
+    Unsupported(String)
+}
+impl DecompileError {
+    pub fn to_diagnostic(&self, sources: &SourceMap, source: SourceId) -> Diagnostic {
+        let d = Diagnostic::error(match self {
+            DecompileError::Encoding(_) => "E0801",
+            DecompileError::Json { .. } => "E0802",
+            DecompileError::Malformed(_) => "E0803",
+            DecompileError::Unsupported(_) => "E0804",
+        }, self.to_string());
+        match self {
+            DecompileError::Json { at: Some(at), .. } => d.primary(Span { source, range: *at..*at }, "invalid JSON here"),
+            _ => d.note(format!("in {}", sources.get(source).name))
+        }
+    }
+}
+impl Display for DecompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecompileError::Encoding(e) => write!(f, "template code is not valid gzip+base64: {e}"),
+            DecompileError::Json { message, .. } => write!(f, "template is not valid JSON: {message}"),
+            DecompileError::Malformed(e) => write!(f, "malformed template: {e}"),
+            DecompileError::Unsupported(e) => write!(f, "unsupported in templates: {e}"),
+        }
+    }
+}
+
+// Inverse of DFCompile::compile
+pub fn decode_template(code: &str) -> Result<String, DecompileError> {
+    let bytes = base64::decode(code.trim()).map_err(|e| DecompileError::Encoding(e.to_string()))?;
+    let mut out = String::new();
+    GzDecoder::new(&bytes[..]).read_to_string(&mut out).map_err(|e| DecompileError::Encoding(e.to_string()))?;
+    Ok(out)
+}
+
+// Accepts either a template code string or template JSON, and either one
+// template or an array of them.
+pub fn parse_templates(source: &str) -> Result<Vec<CodeLine>, DecompileError> {
+    let trimmed = source.trim();
+    let direct = trimmed.starts_with('{') || trimmed.starts_with('[');
+    let json = if direct { String::from(trimmed) } else { decode_template(trimmed)? };
+    let parsed = serde_json::from_str(&json).map_err(|e| {
+        // serde_json counts lines and columns from 1, and columns in bytes
+        let at = direct.then(|| {
+            let line_start: usize = json.split_inclusive('\n').take(e.line().saturating_sub(1)).map(str::len).sum();
+            (source.len() - source.trim_start().len() + line_start + e.column().saturating_sub(1)).min(source.len())
+        });
+        // The label gives the position, counted in the source rather than the trimmed JSON
+        let message = e.to_string();
+        let message = match at {
+            Some(_) => message.strip_suffix(&format!(" at line {} column {}", e.line(), e.column())).unwrap_or(&message).to_string(),
+            None => message
+        };
+        DecompileError::Json { message, at }
+    })?;
+    match parsed {
+        Json::Array(templates) => templates.iter().map(parse_line).collect(),
+        template => Ok(vec![parse_line(&template)?])
+    }
+}
+
+pub fn parse_line(template: &Json) -> Result<CodeLine, DecompileError> {
+    let template: Template = serde_json::from_value(template.clone()).map_err(|e| DecompileError::Malformed(e.to_string()))?;
+    template.to_line()
+}
+
+// A readable listing of decompiled lines: one block per row with its arguments,
+// indented inside brackets. `--format json` gives the template JSON instead.
+pub fn render(lines: &[CodeLine]) -> String {
+    let mut out = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            out += "\n";
+        }
+        let mut depth: usize = 0;
+        for stmnt in line.body() {
+            if matches!(stmnt, Statement::Close | Statement::CloseRepeat) {
+                depth = depth.saturating_sub(1);
+                out += &format!("{}}}\n", "    ".repeat(depth));
+                continue;
+            }
+            out += &"    ".repeat(depth);
+            out += &render_statement(stmnt);
+            if opens_bracket(stmnt) {
+                out += " {";
+                depth += 1;
+            }
+            out += "\n";
+        }
+    }
+    out
+}
+
+fn render_statement(stmnt: &Statement) -> String {
+    let mut out = stmnt.name();
+    let (subaction, selector, not) = match stmnt {
+        Statement::PlayerAction { selector, .. } | Statement::EntityAction { selector, .. } => (None, Some(selector), false),
+        Statement::IfPlayer { selector, not, .. } | Statement::IfEntity { selector, not, .. } => (None, Some(selector), *not),
+        Statement::IfVariable { not, .. } | Statement::IfGame { not, .. } => (None, None, *not),
+        Statement::SelectObject { subaction, not, .. } | Statement::Repeat { subaction, not, .. } => (subaction.as_deref(), None, *not),
+        _ => (None, None, false)
+    };
+    if not {
+        out = out.replacen(": ", ": not ", 1);
+    }
+    if let Some(subaction) = subaction {
+        out += &format!(" / {subaction}");
+    }
+    if let Some(selector) = selector.filter(|s| **s != Selector::Default) {
+        out += &format!(" @{}", selector.serialize());
+    }
+    let args: Vec<String> = stmnt.parameters().into_iter().flatten().flatten().map(render_value).collect();
+    if !args.is_empty() {
+        out += &format!(" ({})", args.join(", "));
+    }
+    out
+}
+
+fn render_value(value: &Value) -> String {
+    let quote = |s: &str| format!("\"{}\"", json_escape(s));
+    match value {
+        Value::Text(Text(t)) => quote(&compact_hex(t)),
+        Value::Component(c) => format!("styled({})", quote(c.as_str())),
+        Value::Number(Number::Literal(n)) => n.to_string(),
+        Value::Number(Number::Math(e)) => quote(&e.to_code()),
+        Value::Location(l) if l.is_block => format!("block({}, {}, {})", l.x, l.y, l.z),
+        Value::Location(l) => format!("loc({}, {}, {}, {}, {})", l.x, l.y, l.z, l.pitch, l.yaw),
+        Value::Vector(v) => format!("vec({}, {}, {})", v.x, v.y, v.z),
+        Value::Sound(s) => match &s.variant {
+            Some(variant) => format!("sound({}, {}, {}, {})", quote(&s.sound), quote(variant), s.pitch, s.volume),
+            None => format!("sound({}, {}, {})", quote(&s.sound), s.pitch, s.volume)
+        },
+        Value::Particle(p) => format!("particle({}, {})", quote(&p.particle), p.amount),
+        Value::Potion(p) => format!("potion({}, {}, {})", quote(p.effect.name()), p.ticks, p.level),
+        Value::Variable(v) => format!("{} {}", v.scope.serialize(), v.name),
+        Value::GameValue(g) => match g.selector.filter(|s| *s != Selector::Default) {
+            Some(selector) => format!("value({} @{})", quote(&g.name), selector.serialize()),
+            None => format!("value({})", quote(&g.name))
+        },
+        Value::Item(i) => match &i.snbt {
+            Some(snbt) => format!("item({snbt})"),
+            None => format!("item({}, {})", quote(&i.material), i.count)
+        },
+        Value::Tag(t) => format!("{} = {}", quote(&t.name), quote(&t.option)),
+        Value::Parameter(p) => {
+            let mut out = format!("{}: {}", p.name, p.value_type.serialize());
+            if p.plural {
+                out += "*";
+            }
+            if p.optional {
+                out += "?";
+            }
+            if let Some(default) = &p.default {
+                out += &format!(" = {}", render_value(default));
+            }
+            out
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile::DFCompile, formatting::{Color, LegacyText}, params::ParamBuilder, testing::{set, local, var, variable}, values::{FunctionParam, Tag, ValueType, Variable, VariableScope}};
+
+    fn join() -> CodeLine {
+        CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            Statement::IfPlayer { action: String::from("HasPermission"), parameters: ParamBuilder::new().tag(Tag{name: String::from("Permission"), option: String::from("Developer"), var: None}).complete_unchecked(), selector: Selector::Default, not: true },
+            Statement::PlayerAction { action: String::from("SendMessage"), parameters: ParamBuilder::new().param(Value::Text(LegacyText::new().color(Color::Green).text("%default joined!").into_text())).complete_unchecked(), selector: Selector::AllPlayers },
+            Statement::Close,
+            Statement::Repeat { action: String::from("Multiple"), subaction: None, parameters: ParamBuilder::new().param(var("i")).param(Value::Number(Number::from(3))).complete_unchecked(), not: false },
+            Statement::CloseRepeat,
+        ])
+    }
+
+    #[test]
+    fn test_decompile_roundtrip() {
+        let line = join();
+        let decompiled = parse_templates(&line.compile()).unwrap();
+        assert_eq!(decompiled.len(), 1);
+        assert_eq!(decompiled[0].serialize(), line.serialize());
+    }
+
+    #[test]
+    fn test_render() {
+        let decompiled = parse_templates(&join().compile()).unwrap();
+        assert_eq!(render(&decompiled), "\
+Player Event: Join
+If Player: not HasPermission (\"Permission\" = \"Developer\") {
+    Player Action: SendMessage @AllPlayers (\"§a%default joined!\")
+}
+Repeat: Multiple (local i, 3) {
+}
+");
+    }
+
+    #[test]
+    fn test_not_a_template() {
+        assert_eq!(parse_templates("not a template").err().map(|e| matches!(e, DecompileError::Encoding(_))), Some(true));
+    }
+
+    #[test]
+    fn test_json_error_span() {
+        // Template JSON that doesn't parse is pointed at in the source
+        let source = "\n{\"blocks\": [}";
+        let e = parse_templates(source).err().unwrap();
+        let mut sources = SourceMap::new();
+        let id = sources.add("join.json", String::from(source));
+        let d = e.to_diagnostic(&sources, id);
+        assert_eq!((d.code, d.labels[0].span.range.clone()), ("E0802", 13..13));
+        assert_eq!(&source[13..], "}");
+    }
+
+    #[test]
+    fn test_line_scope_roundtrip() {
+        let function = CodeLine::new_from(vec![
+            Statement::Function { name: String::from("heal"), parameters: ParamBuilder::new().param(Value::Parameter(FunctionParam::new("amount", ValueType::Number))).complete_unchecked() },
+            set(variable("scratch", VariableScope::Line), Value::Variable(variable("amount", VariableScope::Parameter))),
+            set(local("heal.return"), Value::Variable(variable("scratch", VariableScope::Line))),
+        ]);
+        let json = function.serialize();
+        assert!(json.contains(r#"{"id":"var","data":{"name":"amount","scope":"line"}}"#));
+        let decompiled = parse_templates(&json).unwrap();
+        assert_eq!(decompiled[0].serialize(), json);
+        let Statement::SetVariable { parameters, .. } = &decompiled[0].body()[1] else { panic!() };
+        assert!(matches!(&parameters[0], Some(Value::Variable(Variable { scope: VariableScope::Line, .. }))));
+        assert!(matches!(&parameters[1], Some(Value::Variable(Variable { scope: VariableScope::Parameter, .. }))));
+    }
+}
@@ -0,0 +1,283 @@
+use std::{fmt::Display, ops::Range};
+
+use crate::serialization::json_escape;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error, Warning
+}
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+pub type SourceId = usize;
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: String
+}
+impl SourceFile {
+    // The offset, moved back to the start of the character it falls inside
+    fn boundary(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+    // 1-based line and column of a byte offset, with the column counted in characters
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let before = &self.text[..self.boundary(offset)];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        (line, column)
+    }
+    // How many characters a byte range covers
+    pub fn width(&self, range: &Range<usize>) -> usize {
+        let start = self.boundary(range.start);
+        self.text[start..self.boundary(range.end).max(start)].chars().count()
+    }
+    pub fn line_text(&self, line: usize) -> &str {
+        self.text.lines().nth(line - 1).unwrap_or("")
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct SourceMap {
+    files: Vec<SourceFile>
+}
+impl SourceMap {
+    pub fn new() -> Self {
+        Self { files: vec![] }
+    }
+    pub fn add(&mut self, name: &str, text: String) -> SourceId {
+        self.files.push(SourceFile { name: String::from(name), text });
+        self.files.len() - 1
+    }
+    pub fn get(&self, id: SourceId) -> &SourceFile {
+        &self.files[id]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub source: SourceId,
+    pub range: Range<usize>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub span: Span,
+    pub message: String,
+    pub replacement: String
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub suggestions: Vec<Suggestion>
+}
+impl Diagnostic {
+    pub fn error(code: &'static str, message: String) -> Self {
+        Self { severity: Severity::Error, code, message, labels: vec![], notes: vec![], suggestions: vec![] }
+    }
+    pub fn warning(code: &'static str, message: String) -> Self {
+        Self { severity: Severity::Warning, code, message, labels: vec![], notes: vec![], suggestions: vec![] }
+    }
+    pub fn primary(mut self, span: Span, message: &str) -> Self {
+        self.labels.push(Label { span, message: String::from(message), primary: true });
+        self
+    }
+    pub fn secondary(mut self, span: Span, message: &str) -> Self {
+        self.labels.push(Label { span, message: String::from(message), primary: false });
+        self
+    }
+    pub fn note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+    pub fn suggest(mut self, span: Span, message: &str, replacement: &str) -> Self {
+        self.suggestions.push(Suggestion { span, message: String::from(message), replacement: String::from(replacement) });
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct Report {
+    pub sources: SourceMap,
+    pub diagnostics: Vec<Diagnostic>
+}
+// A report of one problem that isn't tied to any block, like a failed send
+impl From<Diagnostic> for Report {
+    fn from(diagnostic: Diagnostic) -> Self {
+        Self { sources: SourceMap::new(), diagnostics: vec![diagnostic] }
+    }
+}
+impl Report {
+    pub fn new(sources: SourceMap) -> Self {
+        Self { sources, diagnostics: vec![] }
+    }
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.severity == Severity::Error)
+    }
+    pub fn render(&self, color: bool) -> String {
+        let paint = |code: &str, s: &str| if color { format!("\x1b[{code}m{s}\x1b[0m") } else { String::from(s) };
+        let mut out = String::new();
+        for d in &self.diagnostics {
+            let severity_color = match d.severity {
+                Severity::Error => "1;31",
+                Severity::Warning => "1;33",
+            };
+            out += &format!("{}{}\n", paint(severity_color, &format!("{}[{}]", d.severity, d.code)), paint("1", &format!(": {}", d.message)));
+            let gutter = d.labels.iter()
+                .map(|l| self.sources.get(l.span.source).position(l.span.range.start).0.to_string().len())
+                .max()
+                .unwrap_or(1);
+            let pad = " ".repeat(gutter);
+            let bar = paint("1;34", "|");
+            let mut labels: Vec<&Label> = d.labels.iter().collect();
+            labels.sort_by_key(|l| !l.primary);
+            if let Some(first) = labels.first() {
+                let file = self.sources.get(first.span.source);
+                let (line, column) = file.position(first.span.range.start);
+                out += &format!("{pad}{} {}:{line}:{column}\n", paint("1;34", "-->"), file.name);
+            }
+            labels.sort_by_key(|l| (l.span.source, l.span.range.start));
+            for label in labels {
+                let file = self.sources.get(label.span.source);
+                let (line, column) = file.position(label.span.range.start);
+                let width = file.width(&label.span.range).max(1);
+                let (marker, marker_color) = if label.primary { ("^", severity_color) } else { ("-", "1;34") };
+                out += &format!("{pad} {bar}\n");
+                out += &format!("{} {bar} {}\n", paint("1;34", &format!("{line:>gutter$}")), file.line_text(line));
+                out += &format!(
+                    "{pad} {bar} {}{}\n",
+                    " ".repeat(column - 1),
+                    paint(marker_color, format!("{} {}", marker.repeat(width), label.message).trim_end())
+                );
+            }
+            for note in &d.notes {
+                out += &format!("{pad} {} {}: {note}\n", paint("1;34", "="), paint("1", "note"));
+            }
+            for s in &d.suggestions {
+                out += &format!("{pad} {} {}: {}: `{}`\n", paint("1;34", "="), paint("1", "help"), s.message, s.replacement);
+            }
+            out += "\n";
+        }
+        out
+    }
+    pub fn to_json(&self) -> String {
+        let span = |s: &Span| {
+            let file = self.sources.get(s.source);
+            let (line, column) = file.position(s.range.start);
+            format!(
+                r#""file":"{}","start":{},"end":{},"line":{line},"column":{column}"#,
+                json_escape(&file.name), s.range.start, s.range.end
+            )
+        };
+        format!(
+            "[{}]",
+            self.diagnostics.iter().map(|d| format!(
+                r#"{{"severity":"{}","code":"{}","message":"{}","labels":[{}],"notes":[{}],"suggestions":[{}]}}"#,
+                d.severity,
+                d.code,
+                json_escape(&d.message),
+                d.labels.iter().map(|l| format!(r#"{{{},"primary":{},"message":"{}"}}"#, span(&l.span), l.primary, json_escape(&l.message))).collect::<Vec<String>>().join(","),
+                d.notes.iter().map(|n| format!(r#""{}""#, json_escape(n))).collect::<Vec<String>>().join(","),
+                d.suggestions.iter().map(|s| format!(r#"{{{},"message":"{}","replacement":"{}"}}"#, span(&s.span), json_escape(&s.message), json_escape(&s.replacement))).collect::<Vec<String>>().join(","),
+            )).collect::<Vec<String>>().join(",")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code_line::CodeLine, params::ParamBuilder, program::Program, statements::Statement, validate::validate, values::{Selector, Text, Value}};
+
+    // A Repeat closed with the wrong bracket, around an action given the wrong type
+    fn mismatched() -> Report {
+        validate(&Program::new_from(vec![
+            CodeLine::new_from(vec![
+                Statement::PlayerEvent(String::from("Join")),
+                Statement::Repeat { action: String::from("Forever"), subaction: None, parameters: ParamBuilder::new().complete_unchecked(), not: false },
+                Statement::PlayerAction { action: String::from("SetHealth"), parameters: ParamBuilder::new().param(Value::Text(Text(String::from("full")))).complete_unchecked(), selector: Selector::Default },
+                Statement::Close,
+            ])
+        ]))
+    }
+
+    #[test]
+    fn test_render() {
+        let report = mismatched();
+        assert!(report.has_errors());
+        assert_eq!(report.render(false), "\
+error[E0003]: mismatched closing bracket
+ --> line 1 (Player Event: Join):4:1
+  |
+2 | Repeat: Forever
+  | --------------- opened here
+  |
+4 | Close Bracket
+  | ^^^^^^^^^^^^^ closes the wrong kind of bracket
+  = help: use the matching bracket: `Close Repeat Bracket`
+
+error[E0103]: mismatched types in Player Action: SetHealth
+ --> line 1 (Player Event: Join):3:3
+  |
+3 |   Player Action: SetHealth
+  |   ^^^^^^^^^^^^^^^^^^^^^^^^ expected Number, found Text
+  = note: argument `health` takes a Number
+
+");
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            mismatched().to_json(),
+            r#"[{"severity":"error","code":"E0003","message":"mismatched closing bracket","labels":[{"file":"line 1 (Player Event: Join)","start":62,"end":75,"line":4,"column":1,"primary":true,"message":"closes the wrong kind of bracket"},{"file":"line 1 (Player Event: Join)","start":19,"end":34,"line":2,"column":1,"primary":false,"message":"opened here"}],"notes":[],"suggestions":[{"file":"line 1 (Player Event: Join)","start":62,"end":75,"line":4,"column":1,"message":"use the matching bracket","replacement":"Close Repeat Bracket"}]},{"severity":"error","code":"E0103","message":"mismatched types in Player Action: SetHealth","labels":[{"file":"line 1 (Player Event: Join)","start":37,"end":61,"line":3,"column":3,"primary":true,"message":"expected Number, found Text"}],"notes":["argument `health` takes a Number"],"suggestions":[]}]"#
+        );
+    }
+
+    #[test]
+    fn test_multibyte_offsets() {
+        // Offsets inside a multi-byte character don't panic, and columns count characters
+        let mut sources = SourceMap::new();
+        let file = sources.add("line 1 (Function: Café)", String::from("Function: Café\n  Send « é »"));
+        let mut report = Report::new(sources);
+        report.push(Diagnostic::warning("E0999", String::from("odd text")).primary(Span { source: file, range: 27..31 }, "here").secondary(Span { source: file, range: 14..15 }, "named"));
+        assert_eq!(report.sources.get(file).position(14), (1, 14));
+        assert_eq!(report.render(false), "\
+warning[E0999]: odd text
+ --> line 1 (Function: Café):2:10
+  |
+1 | Function: Café
+  |              - named
+  |
+2 |   Send « é »
+  |          ^^^ here
+
+");
+    }
+}
@@ -0,0 +1,206 @@
+use std::{collections::HashMap, io::Write};
+
+use flate2::{write::GzEncoder, Compression};
+
+use crate::{diagnostics::{Diagnostic, Report}, nbt::Nbt, program::Program, serialization::{json_escape, DFSerializable}, statements::Statement};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json, McFunction, Schematic
+}
+impl ExportFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "json" => Some(ExportFormat::Json),
+            "mcfunction" => Some(ExportFormat::McFunction),
+            "schematic" => Some(ExportFormat::Schematic),
+            _ => None
+        }
+    }
+}
+
+// 1.20.1
+pub const DEFAULT_DATA_VERSION: i32 = 3465;
+
+pub fn data_version(minecraft_version: &str) -> Option<i32> {
+    Some(match minecraft_version {
+        "1.19.4" => 3337,
+        "1.20" => 3463,
+        "1.20.1" => 3465,
+        "1.20.2" => 3578,
+        "1.20.3" => 3698,
+        "1.20.4" => 3700,
+        "1.20.5" => 3837,
+        "1.20.6" => 3839,
+        "1.21" => 3953,
+        "1.21.1" => 3955,
+        _ => return None
+    })
+}
+
+pub fn export(program: &Program, format: ExportFormat, data_version: i32) -> Result<Vec<u8>, Report> {
+    match format {
+        ExportFormat::Json => Ok(export_json(program).into_bytes()),
+        ExportFormat::McFunction => Ok(export_mcfunction(program).into_bytes()),
+        ExportFormat::Schematic => export_schematic(program, data_version).map_err(Report::from),
+    }
+}
+
+// The same template JSON the CLI reads back in
+pub fn export_json(program: &Program) -> String {
+    format!("[{}]", program.lines().iter().map(|l| l.serialize()).collect::<Vec<String>>().join(","))
+}
+
+pub fn export_mcfunction(program: &Program) -> String {
+    program.lines().iter()
+        .map(|l| format!("give @p ender_chest{}\n", program.template_tag(l)))
+        .collect()
+}
+
+// Sponge schematic (version 2) of the code blocks, brackets and signs. Lines run
+// along +x, three blocks apart on z. Parameter chests are placed but left empty.
+pub fn export_schematic(program: &Program, data_version: i32) -> Result<Vec<u8>, Diagnostic> {
+    let width = program.lines().iter().map(|l| l.body().len() * 2).max().unwrap_or(0).max(1);
+    let length = (program.lines().len() * 3).max(1);
+    let height = 2;
+    // Sizes are stored as shorts
+    let short = |n: usize, along: &str| i16::try_from(n).map_err(|_| {
+        Diagnostic::error("E0504", format!("the schematic would be {n} blocks {along}, but schematics can't be over {} blocks", i16::MAX))
+    });
+    let (short_width, short_length) = (short(width, "wide")?, short(length, "long")?);
+    let mut palette: HashMap<String, i32> = HashMap::new();
+    palette.insert(String::from("minecraft:air"), 0);
+    let mut blocks = vec![0; width * length * height];
+    let mut entities = vec![];
+    let mut set = |x: usize, y: usize, z: usize, block: &str| {
+        let next = palette.len() as i32;
+        let id = *palette.entry(format!("minecraft:{block}")).or_insert(next);
+        blocks[x + z * width + y * width * length] = id;
+    };
+    for (li, line) in program.lines().iter().enumerate() {
+        let z = li * 3 + 1;
+        for (si, stmnt) in line.body().iter().enumerate() {
+            let x = si * 2;
+            match stmnt {
+                Statement::Close => set(x, 0, z, "piston[facing=west]"),
+                Statement::CloseRepeat => set(x, 0, z, "sticky_piston[facing=west]"),
+                _ => {
+                    set(x, 0, z, block_material(stmnt));
+                    set(x + 1, 0, z, match stmnt {
+                        Statement::Repeat { .. } => "sticky_piston[facing=east]",
+                        Statement::IfVariable { .. } | Statement::IfPlayer { .. } | Statement::IfEntity { .. } |
+                        Statement::IfGame { .. } | Statement::Else => "piston[facing=east]",
+                        _ => "stone"
+                    });
+                    set(x, 0, z - 1, "oak_wall_sign[facing=north]");
+                    entities.push(sign(x, z - 1, sign_text(stmnt)));
+                    if stmnt.parameters().is_some_and(|p| p.iter().any(|v| v.is_some())) {
+                        set(x, 1, z, "chest[facing=north]");
+                    }
+                }
+            }
+        }
+    }
+    let mut palette: Vec<(String, i32)> = palette.into_iter().collect();
+    palette.sort_by_key(|(_, id)| *id);
+    let schematic = Nbt::Compound(vec![
+        (String::from("Version"), Nbt::Int(2)),
+        (String::from("DataVersion"), Nbt::Int(data_version)),
+        (String::from("Width"), Nbt::Short(short_width)),
+        (String::from("Height"), Nbt::Short(height as i16)),
+        (String::from("Length"), Nbt::Short(short_length)),
+        (String::from("PaletteMax"), Nbt::Int(palette.len() as i32)),
+        (String::from("Palette"), Nbt::Compound(palette.into_iter().map(|(name, id)| (name, Nbt::Int(id))).collect())),
+        (String::from("BlockData"), Nbt::ByteArray(blocks.into_iter().flat_map(varint).collect())),
+        (String::from("BlockEntities"), Nbt::List(entities)),
+    ]);
+    let compress = |e: std::io::Error| Diagnostic::error("E0502", format!("could not compress the schematic: {e}"));
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&schematic.to_bytes("Schematic")).map_err(compress)?;
+    encoder.finish().map_err(compress)
+}
+
+fn block_material(stmnt: &Statement) -> &'static str {
+    match stmnt {
+        Statement::PlayerEvent(_) => "diamond_block",
+        Statement::EntityEvent(_) => "gold_block",
+        Statement::Function { .. } => "lapis_block",
+        Statement::Process { .. } => "emerald_block",
+        Statement::PlayerAction { .. } => "cobblestone",
+        Statement::EntityAction { .. } => "mossy_cobblestone",
+        Statement::SetVariable { .. } => "iron_block",
+        Statement::GameAction { .. } => "netherrack",
+        Statement::Control { .. } => "coal_block",
+        Statement::SelectObject { .. } => "purpur_block",
+        Statement::IfVariable { .. } => "obsidian",
+        Statement::IfPlayer { .. } => "oak_planks",
+        Statement::IfEntity { .. } => "bricks",
+        Statement::IfGame { .. } => "red_nether_bricks",
+        Statement::Else => "end_stone",
+        Statement::Repeat { .. } => "prismarine",
+        Statement::CallFunction { .. } => "lapis_ore",
+        Statement::CallProcess { .. } => "emerald_ore",
+        Statement::Close | Statement::CloseRepeat => "piston",
+    }
+}
+
+fn sign_text(stmnt: &Statement) -> [String; 4] {
+    let title = stmnt.technical_name().replace('_', " ").to_uppercase();
+    let not = |n: &bool| String::from(if *n { "NOT" } else { "" });
+    match stmnt {
+        Statement::PlayerEvent(e) | Statement::EntityEvent(e) => [title, e.clone(), String::new(), String::new()],
+        Statement::Function { name, .. } | Statement::Process { name, .. } |
+        Statement::CallFunction { name, .. } | Statement::CallProcess { name, .. } => [title, name.clone(), String::new(), String::new()],
+        Statement::PlayerAction { action, selector, .. } | Statement::EntityAction { action, selector, .. } =>
+            [title, action.clone(), selector.serialize(), String::new()],
+        Statement::IfPlayer { action, selector, not: n, .. } | Statement::IfEntity { action, selector, not: n, .. } =>
+            [title, action.clone(), selector.serialize(), not(n)],
+        Statement::IfVariable { action, not: n, .. } | Statement::IfGame { action, not: n, .. } =>
+            [title, action.clone(), String::new(), not(n)],
+        Statement::SelectObject { action, subaction, not: n, .. } | Statement::Repeat { action, subaction, not: n, .. } =>
+            [title, action.clone(), subaction.clone().unwrap_or_default(), not(n)],
+        _ => [title, stmnt.action().map(String::from).unwrap_or_default(), String::new(), String::new()]
+    }
+}
+
+fn sign(x: usize, z: usize, text: [String; 4]) -> Nbt {
+    Nbt::Compound(vec![
+        (String::from("Pos"), Nbt::IntArray(vec![x as i32, 0, z as i32])),
+        (String::from("Id"), Nbt::String(String::from("minecraft:sign"))),
+        (String::from("front_text"), Nbt::Compound(vec![
+            (String::from("messages"), Nbt::List(text.iter().map(|t| Nbt::String(format!(r#"{{"text":"{}"}}"#, json_escape(t)))).collect())),
+        ])),
+    ])
+}
+
+fn varint(mut n: i32) -> Vec<u8> {
+    let mut out = vec![];
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n = ((n as u32) >> 7) as i32;
+        if n == 0 {
+            out.push(byte);
+            return out;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_line::CodeLine;
+
+    #[test]
+    fn test_schematic() {
+        let p = Program::new_from(vec![ CodeLine::new_from(vec![ Statement::PlayerEvent(String::from("Join")) ]) ]);
+        assert!(export(&p, ExportFormat::Schematic, DEFAULT_DATA_VERSION).is_ok_and(|b| b.starts_with(&[0x1f, 0x8b])));
+    }
+
+    #[test]
+    fn test_schematic_too_long() {
+        // A line too long for a schematic's short width is reported, not wrapped around
+        let wide = Program::new_from(vec![ CodeLine::new_from((0..16384).map(|_| Statement::Else).collect()) ]);
+        assert!(export(&wide, ExportFormat::Schematic, DEFAULT_DATA_VERSION).is_err_and(|r| r.diagnostics[0].code == "E0504"));
+    }
+}
@@ -0,0 +1,372 @@
+use crate::values::Text;
+
+// Legacy § formatting codes, as used by plain text values and template names.
+// & is accepted everywhere § is, and hex colors may be written §x§r§r§g§g§b§b or &#rrggbb.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Color {
+    Black, DarkBlue, DarkGreen, DarkAqua, DarkRed, DarkPurple, Gold, Gray,
+    DarkGray, Blue, Green, Aqua, Red, LightPurple, Yellow, White,
+    Rgb(u8, u8, u8)
+}
+const NAMED: [Color; 16] = [
+    Color::Black, Color::DarkBlue, Color::DarkGreen, Color::DarkAqua, Color::DarkRed, Color::DarkPurple, Color::Gold, Color::Gray,
+    Color::DarkGray, Color::Blue, Color::Green, Color::Aqua, Color::Red, Color::LightPurple, Color::Yellow, Color::White
+];
+impl Color {
+    pub fn parse(s: &str) -> Option<Self> {
+        if let Some(hex) = s.strip_prefix('#') {
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            let n = u32::from_str_radix(hex, 16).ok()?;
+            return Some(Color::Rgb((n >> 16) as u8, (n >> 8) as u8, n as u8));
+        }
+        let s = s.replace("grey", "gray");
+        NAMED.iter().find(|c| c.name() == s).copied()
+    }
+    pub fn from_code(c: char) -> Option<Self> {
+        c.to_digit(16).map(|n| NAMED[n as usize])
+    }
+    // The name used in text components, like dark_gray or #ff8855
+    pub fn name(&self) -> String {
+        String::from(match self {
+            Color::Black => "black",
+            Color::DarkBlue => "dark_blue",
+            Color::DarkGreen => "dark_green",
+            Color::DarkAqua => "dark_aqua",
+            Color::DarkRed => "dark_red",
+            Color::DarkPurple => "dark_purple",
+            Color::Gold => "gold",
+            Color::Gray => "gray",
+            Color::DarkGray => "dark_gray",
+            Color::Blue => "blue",
+            Color::Green => "green",
+            Color::Aqua => "aqua",
+            Color::Red => "red",
+            Color::LightPurple => "light_purple",
+            Color::Yellow => "yellow",
+            Color::White => "white",
+            Color::Rgb(r, g, b) => return format!("#{r:02x}{g:02x}{b:02x}"),
+        })
+    }
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        match self {
+            Color::Black => (0x00, 0x00, 0x00),
+            Color::DarkBlue => (0x00, 0x00, 0xaa),
+            Color::DarkGreen => (0x00, 0xaa, 0x00),
+            Color::DarkAqua => (0x00, 0xaa, 0xaa),
+            Color::DarkRed => (0xaa, 0x00, 0x00),
+            Color::DarkPurple => (0xaa, 0x00, 0xaa),
+            Color::Gold => (0xff, 0xaa, 0x00),
+            Color::Gray => (0xaa, 0xaa, 0xaa),
+            Color::DarkGray => (0x55, 0x55, 0x55),
+            Color::Blue => (0x55, 0x55, 0xff),
+            Color::Green => (0x55, 0xff, 0x55),
+            Color::Aqua => (0x55, 0xff, 0xff),
+            Color::Red => (0xff, 0x55, 0x55),
+            Color::LightPurple => (0xff, 0x55, 0xff),
+            Color::Yellow => (0xff, 0xff, 0x55),
+            Color::White => (0xff, 0xff, 0xff),
+            Color::Rgb(r, g, b) => (*r, *g, *b),
+        }
+    }
+    pub fn code(&self) -> String {
+        match self {
+            Color::Rgb(..) => self.name()[1..].chars().fold(String::from("§x"), |out, c| format!("{out}§{c}")),
+            named => format!("§{:x}", NAMED.iter().position(|c| c == named).unwrap())
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    Color(Color), Obfuscated, Bold, Strikethrough, Underlined, Italic, Reset
+}
+impl Format {
+    pub fn from_code(c: char) -> Option<Self> {
+        match c.to_ascii_lowercase() {
+            'k' => Some(Format::Obfuscated),
+            'l' => Some(Format::Bold),
+            'm' => Some(Format::Strikethrough),
+            'n' => Some(Format::Underlined),
+            'o' => Some(Format::Italic),
+            'r' => Some(Format::Reset),
+            c => Color::from_code(c).map(Format::Color)
+        }
+    }
+    pub fn code(&self) -> String {
+        match self {
+            Format::Color(c) => c.code(),
+            Format::Obfuscated => String::from("§k"),
+            Format::Bold => String::from("§l"),
+            Format::Strikethrough => String::from("§m"),
+            Format::Underlined => String::from("§n"),
+            Format::Italic => String::from("§o"),
+            Format::Reset => String::from("§r"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Token {
+    Format(Format),
+    Char(char)
+}
+
+pub fn tokens(s: &str) -> Vec<Token> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out = vec![];
+    let mut i = 0;
+    let hex_at = |start: usize, marker: Option<char>| -> Option<Color> {
+        let digits: String = match marker {
+            // §x§r§r§g§g§b§b
+            Some(m) => (0..6).map(|n| chars.get(start + n * 2).filter(|c| **c == m).and(chars.get(start + n * 2 + 1)).copied()).collect::<Option<_>>()?,
+            // &#rrggbb
+            None => chars.get(start..start + 6)?.iter().collect()
+        };
+        Color::parse(&format!("#{digits}"))
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (format, len) = match (c, next) {
+            ('§' | '&', Some('x' | 'X')) => match hex_at(i + 2, Some(c)) {
+                Some(color) => (Some(Format::Color(color)), 14),
+                None => (None, 1)
+            },
+            ('&', Some('#')) => match hex_at(i + 2, None) {
+                Some(color) => (Some(Format::Color(color)), 8),
+                None => (None, 1)
+            },
+            ('§' | '&', Some(n)) => (Format::from_code(n), 2),
+            _ => (None, 1)
+        };
+        match format {
+            Some(f) => {
+                out.push(Token::Format(f));
+                i += len;
+            }
+            None => {
+                out.push(Token::Char(c));
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+fn write(tokens: &[Token]) -> String {
+    tokens.iter().map(|t| match t {
+        Token::Format(f) => f.code(),
+        Token::Char(c) => c.to_string()
+    }).collect()
+}
+
+// Every code written with §
+pub fn to_section(s: &str) -> String {
+    write(&tokens(s))
+}
+// Every code written with &, as in template names
+pub fn to_ampersand(s: &str) -> String {
+    tokens(s).iter().map(|t| match t {
+        Token::Format(f) => f.code().replace('§', "&"),
+        Token::Char(c) => c.to_string()
+    }).collect()
+}
+// Hex colors written &#rrggbb instead of §x§r§r§g§g§b§b, leaving everything else as written
+pub fn compact_hex(s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let run: String = chars[i..chars.len().min(i + 14)].iter().collect();
+        match tokens(&run).first() {
+            Some(Token::Format(Format::Color(c @ Color::Rgb(..)))) if run.starts_with("§x") || run.starts_with("§X") => {
+                out += &format!("&{}", c.name());
+                i += 14;
+            }
+            _ => {
+                out.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+pub fn strip(s: &str) -> String {
+    tokens(s).iter().filter_map(|t| match t {
+        Token::Char(c) => Some(*c),
+        _ => None
+    }).collect()
+}
+
+#[derive(Copy, Clone, Default, PartialEq, Eq)]
+struct Style {
+    color: Option<Color>,
+    bold: bool,
+    italic: bool
+}
+
+// Builds a § string, only writing codes where the style changes
+pub struct LegacyText {
+    out: String,
+    style: Style,
+    written: Style
+}
+impl Default for LegacyText {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LegacyText {
+    pub fn new() -> Self {
+        Self { out: String::new(), style: Style::default(), written: Style::default() }
+    }
+    pub fn color(mut self, color: Color) -> Self {
+        self.style.color = Some(color);
+        self
+    }
+    pub fn bold(mut self, bold: bool) -> Self {
+        self.style.bold = bold;
+        self
+    }
+    pub fn italic(mut self, italic: bool) -> Self {
+        self.style.italic = italic;
+        self
+    }
+    // Back to unformatted text
+    pub fn plain(mut self) -> Self {
+        self.style = Style::default();
+        self
+    }
+    fn apply(&mut self) {
+        let (style, written) = (self.style, self.written);
+        if style == written {
+            return;
+        }
+        // Color codes turn off bold and italic, so anything dropped needs one
+        let dropped = (written.bold && !style.bold) || (written.italic && !style.italic);
+        let (bold, italic) = if style.color != written.color || dropped {
+            self.out += &style.color.map_or_else(|| Format::Reset.code(), |c| c.code());
+            (style.bold, style.italic)
+        } else {
+            (style.bold && !written.bold, style.italic && !written.italic)
+        };
+        if bold {
+            self.out += &Format::Bold.code();
+        }
+        if italic {
+            self.out += &Format::Italic.code();
+        }
+        self.written = style;
+    }
+    pub fn text(mut self, text: &str) -> Self {
+        if !text.is_empty() {
+            self.apply();
+            self.out += text;
+        }
+        self
+    }
+    // Fades between the colors across the text; spaces don't get a color of their own
+    pub fn gradient(mut self, text: &str, colors: &[Color]) -> Self {
+        let visible = text.chars().filter(|c| !c.is_whitespace()).count();
+        let mut index = 0;
+        for c in text.chars() {
+            if !c.is_whitespace() && !colors.is_empty() {
+                self.style.color = Some(gradient_at(colors, index as f32 / (visible.max(2) - 1) as f32));
+                index += 1;
+            }
+            self = self.text(&c.to_string());
+        }
+        self
+    }
+    pub fn build(self) -> String {
+        self.out
+    }
+    pub fn into_text(self) -> Text {
+        Text::legacy(&self.out)
+    }
+}
+
+fn gradient_at(colors: &[Color], t: f32) -> Color {
+    if colors.len() == 1 {
+        return colors[0];
+    }
+    let position = t.clamp(0.0, 1.0) * (colors.len() - 1) as f32;
+    let from = (position.floor() as usize).min(colors.len() - 2);
+    let local = position - from as f32;
+    let (a, b) = (colors[from].rgb(), colors[from + 1].rgb());
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * local).round() as u8;
+    Color::Rgb(mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2))
+}
+
+impl Text {
+    // Accepts & codes and &#rrggbb colors and stores them the way DiamondFire expects
+    pub fn legacy(s: &str) -> Self {
+        Text(to_section(s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code_line::CodeLine, compile::DFCompile, decompile::{parse_templates, render}, params::ParamBuilder, serialization::DFSerializable, statements::Statement, values::{Selector, Value}};
+
+    #[test]
+    fn test_legacy_text() {
+        assert_eq!(LegacyText::new().color(Color::Yellow).text("[DEV] ").color(Color::Green).text("joined").build(), "§e[DEV] §ajoined");
+        assert_eq!(LegacyText::new().color(Color::Gold).text("Big").text(" small").plain().text(" none").build(), "§6Big small§r none");
+        assert_eq!(LegacyText::new().color(Color::Gold).bold(true).text("Big").bold(false).text(" small").plain().text(" none").build(), "§6§lBig§6 small§r none");
+        assert_eq!(LegacyText::new().italic(true).text("a").bold(true).text("b").build(), "§oa§lb");
+    }
+
+    #[test]
+    fn test_unchanged_style() {
+        // Nothing is re-emitted while the style stays the same
+        assert_eq!(LegacyText::new().color(Color::Red).bold(true).text("a").color(Color::Red).bold(true).text("b").italic(false).text("c").build(), "§c§labc");
+    }
+
+    #[test]
+    fn test_gradient() {
+        assert_eq!(
+            LegacyText::new().bold(true).gradient("ab c", &[Color::Rgb(0, 0, 0), Color::White]).build(),
+            "§x§0§0§0§0§0§0§la§x§8§0§8§0§8§0§lb §x§f§f§f§f§f§f§lc"
+        );
+        assert_eq!(LegacyText::new().gradient("aa", &[Color::Red]).build(), "§caa");
+    }
+
+    #[test]
+    fn test_rgb_text() {
+        assert_eq!(LegacyText::new().color(Color::Rgb(0xff, 0x88, 0x55)).text("&lHi").into_text().0, Text::legacy("&#ff8855&lHi").0);
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(to_ampersand("§x§f§f§8§8§5§5Compiled §8»"), "&x&f&f&8&8&5&5Compiled &8»");
+        assert_eq!(to_section("&#FF8855Hi &lthere & you"), "§x§f§f§8§8§5§5Hi §lthere & you");
+        assert_eq!(compact_hex("§x§f§f§8§8§5§5Hi §7x"), "&#ff8855Hi §7x");
+        assert_eq!(compact_hex("&x&f&f&8&8&5&5Q&A §x§1"), "&x&f&f&8&8&5&5Q&A §x§1");
+        assert_eq!(strip("§x§f§f§8§8§5§5Hi &l&zthere"), "Hi &zthere");
+    }
+
+    #[test]
+    fn test_color_names() {
+        assert_eq!(Color::parse("dark_grey"), Some(Color::DarkGray));
+        assert_eq!(Color::parse("#12345g"), None);
+    }
+
+    #[test]
+    fn test_text_sent_as_given() {
+        // Text is sent exactly as given; only legacy text turns & into §, and hex colors are listed in their short form
+        let line = CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            Statement::PlayerAction { action: String::from("SendMessage"), parameters: ParamBuilder::new().param(Value::Text(Text(String::from("Q&A and R&D")))).param(Value::Text(Text::legacy("&#ff8855Hi"))).complete_unchecked(), selector: Selector::Default },
+        ]);
+        let json = line.serialize();
+        assert!(json.contains(r#""name":"Q&A and R&D""#) && json.contains("§x§f§f§8§8§5§5Hi"));
+        assert_eq!(parse_templates(&line.compile()).unwrap()[0].serialize(), json);
+        assert!(render(&[line]).contains(r#"("Q&A and R&D", "&#ff8855Hi")"#));
+    }
+}
@@ -0,0 +1,158 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{program::Program, statements::Statement, values::{FunctionParam, ParameterList, Value, ValueType, Variable, VariableScope}, params::ParamBuilder, typeck::{match_args, ArgMismatch}};
+
+pub struct FunctionSignature<'a> {
+    pub name: &'a str,
+    pub params: Vec<&'a FunctionParam>
+}
+impl<'a> FunctionSignature<'a> {
+    pub fn of(stmnt: &'a Statement) -> Option<Self> {
+        match stmnt {
+            Statement::Function { name, parameters } => Some(Self {
+                name,
+                params: parameters.iter().filter_map(|p| match p {
+                    Some(Value::Parameter(p)) => Some(p),
+                    _ => None
+                }).collect()
+            }),
+            _ => None
+        }
+    }
+    pub fn check_call(&self, caller: &str, args: &ParameterList) -> Result<(), CallError> {
+        let args: Vec<ValueType> = args.iter()
+            .flatten()
+            .filter(|a| !matches!(a, Value::Tag(_)))
+            .map(|a| a.value_type())
+            .collect();
+        let caller = String::from(caller);
+        let function = String::from(self.name);
+        match match_args(&self.params, &args) {
+            Ok(()) => Ok(()),
+            Err(ArgMismatch::TooFew { expected, found }) => Err(CallError::TooFewArguments { caller, function, expected, found }),
+            Err(ArgMismatch::TooMany { expected, found }) => Err(CallError::TooManyArguments { caller, function, expected, found }),
+            Err(ArgMismatch::Type { parameter, expected, found }) => Err(CallError::TypeMismatch { caller, function, parameter, expected, found }),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CallError {
+    TooFewArguments { caller: String, function: String, expected: usize, found: usize },
+    TooManyArguments { caller: String, function: String, expected: usize, found: usize },
+    TypeMismatch { caller: String, function: String, parameter: String, expected: ValueType, found: ValueType }
+}
+impl Display for CallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallError::TooFewArguments { caller, function, expected, found } =>
+                write!(f, "{caller}: call to {function} needs at least {expected} argument(s), found {found}"),
+            CallError::TooManyArguments { caller, function, expected, found } =>
+                write!(f, "{caller}: call to {function} takes at most {expected} argument(s) here, found {found}"),
+            CallError::TypeMismatch { caller, function, parameter, expected, found } =>
+                write!(f, "{caller}: parameter {parameter} of {function} expects {expected}, found {found}"),
+        }
+    }
+}
+
+// Calls to functions that aren't defined in this program are left for the linker
+pub fn check_calls(program: &Program) -> Vec<CallError> {
+    let signatures: HashMap<&str, FunctionSignature> = program.lines().iter()
+        .filter_map(|l| l.body().first())
+        .filter_map(FunctionSignature::of)
+        .map(|s| (s.name, s))
+        .collect();
+    let mut errors = vec![];
+    for line in program.lines() {
+        for stmnt in line.body() {
+            if let Statement::CallFunction { name, parameters } = stmnt {
+                if let Some(sig) = signatures.get(name.as_str()) {
+                    if let Err(e) = sig.check_call(&line.name(), parameters) {
+                        errors.push(e);
+                    }
+                }
+            }
+        }
+    }
+    errors
+}
+
+pub fn return_variable(function: &str) -> Variable {
+    Variable { name: format!("{function}.return"), scope: VariableScope::Local }
+}
+
+// Stores the value in the function's return variable and leaves the function
+pub fn return_value(function: &str, value: Value) -> Vec<Statement> {
+    vec![
+        Statement::SetVariable {
+            action: String::from("="),
+            parameters: ParamBuilder::new().param(Value::Variable(return_variable(function))).param(value).complete_unchecked()
+        },
+        Statement::Control { action: String::from("Return"), parameters: ParamBuilder::new().complete_unchecked() }
+    ]
+}
+
+// Lowers `let dest = function(args)`
+pub fn call_with_result(function: &str, args: ParameterList, dest: Variable) -> Vec<Statement> {
+    vec![
+        Statement::CallFunction { name: String::from(function), parameters: args },
+        Statement::SetVariable {
+            action: String::from("="),
+            parameters: ParamBuilder::new().param(Value::Variable(dest)).param(Value::Variable(return_variable(function))).complete_unchecked()
+        }
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code_line::CodeLine, serialization::DFSerializable, testing::local, values::{Location, Number}};
+
+    #[test]
+    fn test_function_params() {
+        let f = Statement::Function {
+            name: String::from("distance"),
+            parameters: ParamBuilder::new()
+                .param(Value::Parameter(FunctionParam::new("a", ValueType::Location)))
+                .param(Value::Parameter(FunctionParam::new("b", ValueType::Location).with_default(Value::Location(Location::new(0.0, 0.0, 0.0))).unwrap()))
+                .complete_unchecked()
+        };
+        assert_eq!(
+            f.serialize(),
+            r#"{"id":"block","block":"func","args":{"items":[{"item":{"id":"pn_el","data":{"name":"a","type":"loc","plural":false,"optional":false}},"slot":0},{"item":{"id":"pn_el","data":{"name":"b","type":"loc","plural":false,"optional":true,"default_value":{"id":"loc","data":{"isBlock":false,"loc":{"x":0,"y":0,"z":0,"pitch":0,"yaw":0}}}}},"slot":1}]},"data":"distance"}"#
+        );
+    }
+
+    #[test]
+    fn test_function_calls() {
+        let loc = || Value::Location(Location::new(1.0, 2.0, 3.0));
+        let p = Program::new_from(vec![
+            CodeLine::new_from(vec![
+                Statement::Function { name: String::from("sum"), parameters: ParamBuilder::new()
+                    .param(Value::Parameter(FunctionParam::new("first", ValueType::Number)))
+                    .param(Value::Parameter(FunctionParam::new("rest", ValueType::Number).plural()))
+                    .complete_unchecked() },
+            ]),
+            CodeLine::new_from(vec![
+                Statement::PlayerEvent(String::from("Join")),
+                Statement::CallFunction { name: String::from("sum"), parameters: ParamBuilder::new().param(Value::Number(Number::from(1))).param(Value::Number(Number::from(2))).param(Value::Number(Number::from(3))).complete_unchecked() },
+                Statement::CallFunction { name: String::from("sum"), parameters: ParamBuilder::new().param(Value::Number(Number::from(1))).complete_unchecked() },
+                Statement::CallFunction { name: String::from("sum"), parameters: ParamBuilder::new().param(Value::Number(Number::from(1))).param(loc()).complete_unchecked() },
+                Statement::CallFunction { name: String::from("elsewhere"), parameters: ParamBuilder::new().param(loc()).complete_unchecked() },
+            ]),
+        ]);
+        assert_eq!(check_calls(&p), vec![
+            CallError::TooFewArguments { caller: String::from("Player Event: Join"), function: String::from("sum"), expected: 2, found: 1 },
+            CallError::TypeMismatch { caller: String::from("Player Event: Join"), function: String::from("sum"), parameter: String::from("rest"), expected: ValueType::Number, found: ValueType::Location },
+        ]);
+    }
+
+    #[test]
+    fn test_call_with_result() {
+        let line = CodeLine::new_from(call_with_result("distance", ParamBuilder::new().complete_unchecked(), local("d")));
+        assert_eq!(
+            line.serialize(),
+            r#"{"blocks":[{"id":"block","block":"call_func","args":{"items":[]},"data":"distance"},{"id":"block","block":"set_var","args":{"items":[{"item":{"id":"var","data":{"name":"d","scope":"local"}},"slot":0},{"item":{"id":"var","data":{"name":"distance.return","scope":"local"}},"slot":1}]},"action":"="}]}"#
+        );
+    }
+}
@@ -0,0 +1,39 @@
+pub mod values;
+pub mod statements;
+pub mod serialization;
+pub mod code_line;
+pub mod compile;
+pub mod program;
+pub mod params;
+pub mod functions;
+pub mod catalog;
+pub mod typeck;
+pub mod diagnostics;
+pub mod validate;
+pub mod decompile;
+pub mod transport;
+pub mod nbt;
+pub mod export;
+pub mod cli;
+pub mod project;
+pub mod linker;
+pub mod reachability;
+pub mod callgraph;
+pub mod optimize;
+pub mod visit;
+pub mod passes;
+pub mod cfg;
+pub mod dataflow;
+pub mod minify;
+pub mod size;
+pub mod template;
+pub mod component;
+pub mod formatting;
+pub mod textcode;
+pub mod decimal;
+pub mod location;
+pub mod assets;
+pub mod collections;
+
+#[cfg(test)]
+mod testing;
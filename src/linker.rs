@@ -0,0 +1,524 @@
+use std::{collections::{BTreeMap, BTreeSet, HashMap, HashSet}, fmt::Display};
+
+use crate::{code_line::CodeLine, diagnostics::Diagnostic, params::ParamBuilder, program::Program, statements::Statement, values::{Variable, VariableScope}, visit::{walk_variable, Visitor}};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DefinitionKind {
+    Function, Process
+}
+impl Display for DefinitionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DefinitionKind::Function => "function",
+            DefinitionKind::Process => "process",
+        })
+    }
+}
+
+// A unit of linking. Functions and processes it defines end up named
+// `module.name`; an empty module name keeps them as they are.
+pub struct Module {
+    pub name: String,
+    pub lines: Vec<CodeLine>
+}
+impl Module {
+    pub fn qualified(&self, name: &str) -> String {
+        qualify(&self.name, name)
+    }
+}
+
+fn qualify(module: &str, name: &str) -> String {
+    if module.is_empty() {
+        String::from(name)
+    } else {
+        format!("{module}.{name}")
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LinkError {
+    Duplicate { kind: DefinitionKind, name: String, first: String, second: String },
+    Unresolved { kind: DefinitionKind, name: String, module: String, caller: String },
+    Private { kind: DefinitionKind, name: String, module: String, caller: String },
+    Ambiguous { kind: DefinitionKind, name: String, module: String, caller: String, candidates: Vec<String> },
+    // A function already has the name a merged event handler needs
+    HandlerName { name: String, event: String }
+}
+impl LinkError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            LinkError::Duplicate { .. } => Diagnostic::error("E0601", self.to_string()),
+            LinkError::Unresolved { .. } => Diagnostic::error("E0602", self.to_string()),
+            LinkError::Private { .. } => Diagnostic::error("E0603", self.to_string())
+                .note(String::from("names starting with an underscore can only be called from their own module")),
+            LinkError::Ambiguous { .. } => Diagnostic::error("E0604", self.to_string())
+                .note(String::from("call it by its qualified name, as in module.name")),
+            LinkError::HandlerName { .. } => Diagnostic::error("E0605", self.to_string())
+                .note(String::from("rename the function; merged event handlers are named _on followed by the event")),
+        }
+    }
+}
+impl Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let module_name = |m: &str| if m.is_empty() { String::from("the root module") } else { format!("module {m}") };
+        match self {
+            LinkError::Duplicate { kind, name, first, second } =>
+                write!(f, "{kind} {name} is defined in both {} and {}", module_name(first), module_name(second)),
+            LinkError::Unresolved { kind, name, module, caller } =>
+                write!(f, "{caller} ({}) calls {kind} {name}, which is not defined anywhere", module_name(module)),
+            LinkError::Private { kind, name, module, caller } =>
+                write!(f, "{caller} ({}) calls {kind} {name}, which is private to its module", module_name(module)),
+            LinkError::Ambiguous { kind, name, module, caller, candidates } =>
+                write!(f, "{caller} ({}) calls {kind} {name}, which could be any of {}", module_name(module), candidates.join(", ")),
+            LinkError::HandlerName { name, event } =>
+                write!(f, "a handler for {event} would be named {name}, but a function already has that name"),
+        }
+    }
+}
+
+// Merged handlers run one after another in the same thread, so they can get in
+// each other's way
+#[derive(Debug, PartialEq)]
+pub enum LinkWarning {
+    // The handlers after this one only run once it is done waiting
+    Wait { event: String, handler: String },
+    // Local variables are shared by everything the event line calls
+    SharedLocal { event: String, variable: String, handlers: Vec<String> }
+}
+impl LinkWarning {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            LinkWarning::Wait { handler, .. } => Diagnostic::warning("W0601", format!("{self}"))
+                .note(format!("move the wait into a process started from {handler} to let the other handlers run")),
+            LinkWarning::SharedLocal { handlers, .. } => Diagnostic::warning("W0602", format!("{self}"))
+                .note(format!("used by {}", handlers.join(", "))),
+        }
+    }
+}
+impl Display for LinkWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkWarning::Wait { event, handler } =>
+                write!(f, "{handler} waits, holding back the {event} handlers that run after it"),
+            LinkWarning::SharedLocal { event, variable, handlers } =>
+                write!(f, "local variable `{variable}` is used by {} handlers of {event}", handlers.len()),
+        }
+    }
+}
+
+fn definition(stmnt: &Statement) -> Option<(DefinitionKind, &str)> {
+    match stmnt {
+        Statement::Function { name, .. } => Some((DefinitionKind::Function, name)),
+        Statement::Process { name, .. } => Some((DefinitionKind::Process, name)),
+        _ => None
+    }
+}
+
+// Names starting with an underscore can only be called from their own module
+fn is_private(name: &str) -> bool {
+    name.starts_with('_')
+}
+
+struct Definition {
+    module: usize,
+    name: String,
+    qualified: String
+}
+
+#[derive(Default)]
+pub struct Linker {
+    modules: Vec<Module>,
+    priorities: HashMap<String, i32>
+}
+impl Linker {
+    pub fn new() -> Self {
+        Self { modules: vec![], priorities: HashMap::new() }
+    }
+    pub fn module(mut self, module: Module) -> Self {
+        self.modules.push(module);
+        self
+    }
+    // When several modules handle the same event, their handlers run lowest
+    // priority first. Modules default to 0; ties run in declaration order.
+    pub fn priority(mut self, module: &str, priority: i32) -> Self {
+        self.priorities.insert(String::from(module), priority);
+        self
+    }
+    pub fn program(self, name: &str, program: Program) -> Self {
+        self.module(Module { name: String::from(name), lines: program.into_lines() })
+    }
+    // Calls resolve to the caller's own module first, then to a fully qualified
+    // name, then to the only public definition with that name in another module.
+    // Outside the root module, a root definition sharing its name with a public
+    // definition elsewhere is ambiguous rather than preferred.
+    // Calls with a text code for a name are dynamic and left alone.
+    pub fn link(self) -> Result<Program, Vec<LinkError>> {
+        self.link_with_warnings().map(|(program, _)| program)
+    }
+    pub fn link_with_warnings(mut self) -> Result<(Program, Vec<LinkWarning>), Vec<LinkError>> {
+        let mut errors = vec![];
+        let mut definitions: Vec<(DefinitionKind, Definition)> = vec![];
+        let mut by_qualified: HashMap<(DefinitionKind, String), usize> = HashMap::new();
+        for (mi, module) in self.modules.iter().enumerate() {
+            for line in &module.lines {
+                let Some((kind, name)) = line.body().first().and_then(definition) else { continue };
+                let qualified = module.qualified(name);
+                if let Some(&first) = by_qualified.get(&(kind, qualified.clone())) {
+                    errors.push(LinkError::Duplicate {
+                        kind,
+                        name: qualified,
+                        first: self.modules[definitions[first].1.module].name.clone(),
+                        second: module.name.clone()
+                    });
+                    continue;
+                }
+                by_qualified.insert((kind, qualified.clone()), definitions.len());
+                definitions.push((kind, Definition { module: mi, name: String::from(name), qualified }));
+            }
+        }
+        let mut renames: Vec<Vec<(usize, usize, String)>> = vec![];
+        for (mi, module) in self.modules.iter().enumerate() {
+            let mut module_renames = vec![];
+            for (li, line) in module.lines.iter().enumerate() {
+                for (si, stmnt) in line.body().iter().enumerate() {
+                    let (kind, name) = match stmnt {
+                        Statement::CallFunction { name, .. } => (DefinitionKind::Function, name),
+                        Statement::CallProcess { name, .. } => (DefinitionKind::Process, name),
+                        Statement::Function { name, .. } | Statement::Process { name, .. } => {
+                            module_renames.push((li, si, module.qualified(name)));
+                            continue;
+                        }
+                        _ => continue
+                    };
+                    if name.contains('%') {
+                        continue;
+                    }
+                    let candidates: Vec<&Definition> = definitions.iter()
+                        .filter(|(k, d)| *k == kind && d.module != mi && d.name == *name && !is_private(&d.name))
+                        .map(|(_, d)| d)
+                        .collect();
+                    // A root definition doesn't hide public ones of the same name in other modules
+                    let own = by_qualified.get(&(kind, module.qualified(name))).copied();
+                    let qualified = by_qualified.get(&(kind, name.clone())).copied().filter(|&d| {
+                        let def = &definitions[d].1;
+                        !(self.modules[def.module].name.is_empty() && candidates.len() > 1)
+                    });
+                    let resolved = match own.or(qualified) {
+                        Some(d) => {
+                            let (_, def) = &definitions[d];
+                            if def.module != mi && is_private(&def.name) {
+                                errors.push(LinkError::Private { kind, name: name.clone(), module: module.name.clone(), caller: line.name() });
+                                continue;
+                            }
+                            def.qualified.clone()
+                        }
+                        None => {
+                            let (name, module, caller) = (name.clone(), module.name.clone(), line.name());
+                            match candidates.len() {
+                                1 => {}
+                                0 => {
+                                    errors.push(LinkError::Unresolved { kind, name, module, caller });
+                                    continue;
+                                }
+                                _ => {
+                                    let candidates = candidates.iter().map(|d| d.qualified.clone()).collect();
+                                    errors.push(LinkError::Ambiguous { kind, name, module, caller, candidates });
+                                    continue;
+                                }
+                            }
+                            candidates[0].qualified.clone()
+                        }
+                    };
+                    module_renames.push((li, si, resolved));
+                }
+            }
+            renames.push(module_renames);
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let functions: HashSet<String> = by_qualified.into_keys()
+            .filter(|(kind, _)| *kind == DefinitionKind::Function)
+            .map(|(_, name)| name)
+            .collect();
+        let mut lines = vec![];
+        for (module, module_renames) in self.modules.iter_mut().zip(renames) {
+            for (li, si, resolved) in module_renames {
+                match &mut module.lines[li].body_mut()[si] {
+                    Statement::Function { name, .. } | Statement::Process { name, .. } |
+                    Statement::CallFunction { name, .. } | Statement::CallProcess { name, .. } => *name = resolved,
+                    _ => {}
+                }
+            }
+            lines.extend(module.lines.drain(..).map(|l| (module.name.clone(), l)));
+        }
+        merge_events(lines, &self.priorities, &functions).map(|(lines, warnings)| (Program::new_from(lines), warnings))
+    }
+}
+
+// Whether it is an entity event, and which
+fn event(line: &CodeLine) -> Option<(bool, String)> {
+    match line.body().first() {
+        Some(Statement::PlayerEvent(e)) => Some((false, e.clone())),
+        Some(Statement::EntityEvent(e)) => Some((true, e.clone())),
+        _ => None
+    }
+}
+
+// DiamondFire allows one line per event. When several lines handle the same event,
+// each body becomes a private function of its module and a single event line, placed
+// where the first handler was, calls them by module priority, then in the order
+// they were declared.
+fn merge_events(lines: Vec<(String, CodeLine)>, priorities: &HashMap<String, i32>, functions: &HashSet<String>) -> Result<(Vec<CodeLine>, Vec<LinkWarning>), Vec<LinkError>> {
+    let mut handlers: HashMap<(bool, String), usize> = HashMap::new();
+    for (_, line) in &lines {
+        if let Some(key) = event(line) {
+            *handlers.entry(key).or_default() += 1;
+        }
+    }
+    let mut out: Vec<CodeLine> = vec![];
+    let mut merged: HashMap<(bool, String), usize> = HashMap::new();
+    // Per merged event line, its calls and their priorities
+    let mut calls: HashMap<usize, Vec<(i32, Statement)>> = HashMap::new();
+    let mut taken: HashMap<String, usize> = HashMap::new();
+    let mut errors = vec![];
+    let mut warnings = vec![];
+    // Per merged event, the local variables each handler uses
+    let mut locals: BTreeMap<String, BTreeMap<String, Vec<String>>> = BTreeMap::new();
+    for (module, mut line) in lines {
+        let Some(key) = event(&line) else {
+            out.push(line);
+            continue;
+        };
+        if handlers[&key] == 1 {
+            out.push(line);
+            continue;
+        }
+        let base = qualify(&module, &format!("_on{}{}", if key.0 { "Entity" } else { "" }, key.1));
+        let count = taken.entry(base.clone()).or_default();
+        *count += 1;
+        let name = if *count == 1 { base } else { format!("{base}.{count}") };
+        if functions.contains(&name) {
+            errors.push(LinkError::HandlerName { name: name.clone(), event: line.name() });
+        }
+        let header = line.body_mut().remove(0);
+        line.body_mut().insert(0, Statement::Function { name: name.clone(), parameters: ParamBuilder::new().complete_unchecked() });
+        // End would stop the whole event, skipping the handlers after this one
+        for stmnt in line.body_mut() {
+            if let Statement::Control { action, .. } = stmnt {
+                if action == "End" {
+                    *action = String::from("Return");
+                }
+            }
+        }
+        let event_name = header.name();
+        if line.body().iter().any(|s| matches!(s, Statement::Control { action, .. } if action == "Wait")) {
+            warnings.push(LinkWarning::Wait { event: event_name.clone(), handler: name.clone() });
+        }
+        let mut used = Locals(BTreeSet::new());
+        used.visit_line(&line);
+        for variable in used.0 {
+            locals.entry(event_name.clone()).or_default().entry(variable).or_default().push(name.clone());
+        }
+        let call = Statement::CallFunction { name, parameters: ParamBuilder::new().complete_unchecked() };
+        let priority = priorities.get(&module).copied().unwrap_or_default();
+        let i = *merged.entry(key).or_insert_with(|| {
+            out.push(CodeLine::new_from(vec![header]));
+            out.len() - 1
+        });
+        calls.entry(i).or_default().push((priority, call));
+        out.push(line);
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    for (i, mut calls) in calls {
+        // Stable, so equal priorities keep their declaration order
+        calls.sort_by_key(|(priority, _)| *priority);
+        out[i].body_mut().extend(calls.into_iter().map(|(_, call)| call));
+    }
+    for (event, variables) in locals {
+        for (variable, handlers) in variables {
+            if handlers.len() > 1 {
+                warnings.push(LinkWarning::SharedLocal { event: event.clone(), variable, handlers });
+            }
+        }
+    }
+    Ok((out, warnings))
+}
+
+struct Locals(BTreeSet<String>);
+impl Visitor for Locals {
+    fn visit_variable(&mut self, var: &Variable) {
+        if var.scope == VariableScope::Local {
+            self.0.insert(var.name.clone());
+        }
+        walk_variable(self, var);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cfg::Cfg, testing::{local, set, var}, values::{Number, Selector, Value}};
+
+    fn func(name: &str) -> Statement {
+        Statement::Function { name: String::from(name), parameters: ParamBuilder::new().complete_unchecked() }
+    }
+    fn call(name: &str) -> Statement {
+        Statement::CallFunction { name: String::from(name), parameters: ParamBuilder::new().complete_unchecked() }
+    }
+    fn join() -> Statement {
+        Statement::PlayerEvent(String::from("Join"))
+    }
+    fn action(name: &str) -> Statement {
+        Statement::PlayerAction { action: String::from(name), parameters: ParamBuilder::new().complete_unchecked(), selector: Selector::Default }
+    }
+    fn names(p: &Program) -> Vec<Vec<String>> {
+        p.lines().iter().map(|l| l.body().iter().map(|s| s.name()).collect()).collect()
+    }
+
+    #[test]
+    fn test_linker() {
+        let p = Linker::new()
+            .module(Module { name: String::from("util.math"), lines: vec![
+                CodeLine::new_from(vec![ func("square") ]),
+                CodeLine::new_from(vec![ func("_helper") ]),
+            ] })
+            .module(Module { name: String::from("main"), lines: vec![
+                CodeLine::new_from(vec![ func("_helper"), call("square") ]),
+                CodeLine::new_from(vec![ join(), call("util.math.square"), call("_helper"), call("%var(target)") ]),
+            ] })
+            .link()
+            .unwrap();
+        assert_eq!(names(&p), vec![
+            vec![String::from("Function: util.math.square")],
+            vec![String::from("Function: util.math._helper")],
+            vec![String::from("Function: main._helper"), String::from("Call: util.math.square")],
+            vec![String::from("Player Event: Join"), String::from("Call: util.math.square"), String::from("Call: main._helper"), String::from("Call: %var(target)")],
+        ]);
+    }
+
+    #[test]
+    fn test_linker_errors() {
+        let errors = Linker::new()
+            .module(Module { name: String::from("a"), lines: vec![ CodeLine::new_from(vec![ func("shared") ]), CodeLine::new_from(vec![ func("_secret") ]) ] })
+            .module(Module { name: String::from("b"), lines: vec![ CodeLine::new_from(vec![ func("shared") ]), CodeLine::new_from(vec![ func("shared") ]) ] })
+            .program("main", Program::new_from(vec![
+                CodeLine::new_from(vec![ join(), call("shared"), call("a._secret"), call("missing") ]),
+            ]))
+            .link()
+            .err()
+            .unwrap();
+        let event = || String::from("Player Event: Join");
+        assert_eq!(errors, vec![
+            LinkError::Duplicate { kind: DefinitionKind::Function, name: String::from("b.shared"), first: String::from("b"), second: String::from("b") },
+            LinkError::Ambiguous { kind: DefinitionKind::Function, name: String::from("shared"), module: String::from("main"), caller: event(), candidates: vec![String::from("a.shared"), String::from("b.shared")] },
+            LinkError::Private { kind: DefinitionKind::Function, name: String::from("a._secret"), module: String::from("main"), caller: event() },
+            LinkError::Unresolved { kind: DefinitionKind::Function, name: String::from("missing"), module: String::from("main"), caller: event() },
+        ]);
+    }
+
+    // A root module and a library that both define `spawn`, called from `caller`
+    fn link_spawn(caller: &str) -> Result<Program, Vec<LinkError>> {
+        Linker::new()
+            .module(Module { name: String::new(), lines: vec![ CodeLine::new_from(vec![ func("spawn") ]) ] })
+            .module(Module { name: String::from("lib"), lines: vec![ CodeLine::new_from(vec![ func("spawn") ]) ] })
+            .module(Module { name: String::from(caller), lines: vec![ CodeLine::new_from(vec![ join(), call("spawn") ]) ] })
+            .link()
+    }
+
+    #[test]
+    fn test_root_module_ambiguity() {
+        // Another module calling a name the root module and a library both define
+        // can't tell which one it means
+        assert_eq!(link_spawn("game").err().unwrap(), vec![
+            LinkError::Ambiguous { kind: DefinitionKind::Function, name: String::from("spawn"), module: String::from("game"), caller: String::from("Player Event: Join"), candidates: vec![String::from("spawn"), String::from("lib.spawn")] },
+        ]);
+    }
+
+    #[test]
+    fn test_root_module_calls_its_own() {
+        let p = link_spawn("").unwrap();
+        assert!(p.lines().iter().any(|l| matches!(l.body(), [Statement::PlayerEvent(_), Statement::CallFunction { name, .. }] if name == "spawn")));
+    }
+
+    #[test]
+    fn test_merged_events() {
+        let p = Linker::new()
+            .module(Module { name: String::from("greet"), lines: vec![ CodeLine::new_from(vec![ join(), action("SendMessage") ]) ] })
+            .module(Module { name: String::from("kit"), lines: vec![
+                CodeLine::new_from(vec![ join(), action("GiveItems") ]),
+                CodeLine::new_from(vec![ Statement::PlayerEvent(String::from("Quit")), action("SendMessage") ]),
+                CodeLine::new_from(vec![ join(), action("SetHealth") ]),
+            ] })
+            .link()
+            .unwrap();
+        assert_eq!(names(&p), vec![
+            vec![String::from("Player Event: Join"), String::from("Call: greet._onJoin"), String::from("Call: kit._onJoin"), String::from("Call: kit._onJoin.2")],
+            vec![String::from("Function: greet._onJoin"), String::from("Player Action: SendMessage")],
+            vec![String::from("Function: kit._onJoin"), String::from("Player Action: GiveItems")],
+            vec![String::from("Player Event: Quit"), String::from("Player Action: SendMessage")],
+            vec![String::from("Function: kit._onJoin.2"), String::from("Player Action: SetHealth")],
+        ]);
+    }
+
+    // Two modules that both handle Join
+    fn greet_and_kit() -> Linker {
+        Linker::new()
+            .module(Module { name: String::from("greet"), lines: vec![ CodeLine::new_from(vec![ join(), action("SendMessage") ]) ] })
+            .module(Module { name: String::from("kit"), lines: vec![ CodeLine::new_from(vec![ join(), action("GiveItems") ]) ] })
+    }
+
+    #[test]
+    fn test_merged_event_priority() {
+        let p = greet_and_kit().priority("kit", -1).link().unwrap();
+        assert_eq!(names(&p)[0], vec![
+            String::from("Player Event: Join"), String::from("Call: kit._onJoin"), String::from("Call: greet._onJoin")
+        ]);
+    }
+
+    #[test]
+    fn test_handler_name_taken() {
+        // Handlers can't take a user function's name
+        let errors = greet_and_kit()
+            .module(Module { name: String::from("kit"), lines: vec![ CodeLine::new_from(vec![ func("_onJoin") ]) ] })
+            .link()
+            .err()
+            .unwrap();
+        assert_eq!(errors, vec![ LinkError::HandlerName { name: String::from("kit._onJoin"), event: String::from("Player Event: Join") } ]);
+        assert_eq!(errors[0].to_diagnostic().code, "E0605");
+    }
+
+    #[test]
+    fn test_merged_handler_end() {
+        let control = |a: &str| Statement::Control { action: String::from(a), parameters: ParamBuilder::new().complete_unchecked() };
+        let (p, warnings) = Linker::new()
+            .module(Module { name: String::from("greet"), lines: vec![ CodeLine::new_from(vec![ join(), action("SendMessage"), control("End") ]) ] })
+            .module(Module { name: String::from("kit"), lines: vec![ CodeLine::new_from(vec![ join(), action("GiveItems") ]) ] })
+            .link_with_warnings()
+            .unwrap();
+        assert!(warnings.is_empty());
+        // Ending the thread in the first handler would skip the call to the second
+        assert_eq!(names(&p)[1], vec![
+            String::from("Function: greet._onJoin"), String::from("Player Action: SendMessage"), String::from("Control: Return")
+        ]);
+        assert_eq!(p.lines()[0].body().last().unwrap().name(), "Call: kit._onJoin");
+        assert!(Cfg::build(&p.lines()[0]).unreachable().is_empty());
+    }
+
+    #[test]
+    fn test_merged_handler_warnings() {
+        let wait = Statement::Control { action: String::from("Wait"), parameters: ParamBuilder::new().complete_unchecked() };
+        let (_, warnings) = Linker::new()
+            .module(Module { name: String::from("greet"), lines: vec![ CodeLine::new_from(vec![ join(), set(local("count"), Value::Number(Number::from(1))), wait ]) ] })
+            .module(Module { name: String::from("kit"), lines: vec![ CodeLine::new_from(vec![ join(), set(local("count"), Value::Number(Number::from(2))), set(local("kit"), var("count")) ]) ] })
+            .link_with_warnings()
+            .unwrap();
+        assert_eq!(warnings, vec![
+            LinkWarning::Wait { event: String::from("Player Event: Join"), handler: String::from("greet._onJoin") },
+            LinkWarning::SharedLocal { event: String::from("Player Event: Join"), variable: String::from("count"), handlers: vec![String::from("greet._onJoin"), String::from("kit._onJoin")] },
+        ]);
+        assert_eq!(warnings[1].to_diagnostic().code, "W0602");
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{program::Program, values::{Location, Value, Vector}, visit::{walk_value_mut, VisitorMut}};
+
+// Trig leaves values like 6.1e-17 where there should be a 0, or 29.999999999999996 for 30
+fn tidy(n: f64) -> f64 {
+    let whole = n.round();
+    if (n - whole).abs() < 1e-9 { whole + 0.0 } else { n }
+}
+
+impl Location {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z, pitch: 0., yaw: 0., is_block: false }
+    }
+    // The block at these coordinates, as picked with a location item on a block
+    pub fn block(x: i64, y: i64, z: i64) -> Self {
+        Self { is_block: true, ..Self::new(x as f64, y as f64, z as f64) }
+    }
+    pub fn with_rotation(mut self, pitch: f64, yaw: f64) -> Self {
+        self.pitch = pitch;
+        self.yaw = yaw;
+        self
+    }
+    // The block this location is in
+    pub fn to_block(self) -> Self {
+        Self::block(self.x.floor() as i64, self.y.floor() as i64, self.z.floor() as i64)
+    }
+    // The middle of the block this location is in
+    pub fn center(&self) -> Self {
+        let block = self.to_block();
+        Self::new(block.x + 0.5, block.y + 0.5, block.z + 0.5).with_rotation(self.pitch, self.yaw)
+    }
+    pub fn offset(&self, x: f64, y: f64, z: f64) -> Self {
+        Self { x: self.x + x, y: self.y + y, z: self.z + z, ..*self }
+    }
+    pub fn offset_by(&self, v: &Vector) -> Self {
+        self.offset(v.x, v.y, v.z)
+    }
+    // Unit vector the location is facing, with Minecraft's yaw 0 = south (+z) and pitch 90 = down
+    pub fn direction(&self) -> Vector {
+        let (pitch, yaw) = (self.pitch.to_radians(), self.yaw.to_radians());
+        Vector { x: tidy(-yaw.sin() * pitch.cos()), y: tidy(-pitch.sin()), z: tidy(yaw.cos() * pitch.cos()) }
+    }
+    // Turns to face along the vector; a zero vector leaves the rotation alone
+    pub fn facing(mut self, v: &Vector) -> Self {
+        let horizontal = (v.x * v.x + v.z * v.z).sqrt();
+        if horizontal == 0.0 && v.y == 0.0 {
+            return self;
+        }
+        self.pitch = tidy((-v.y).atan2(horizontal).to_degrees());
+        self.yaw = if horizontal == 0.0 { self.yaw } else { tidy((-v.x).atan2(v.z).to_degrees()) };
+        self
+    }
+    pub fn forward(&self, distance: f64) -> Self {
+        let d = self.direction();
+        self.offset(d.x * distance, d.y * distance, d.z * distance)
+    }
+}
+
+// Where a plot starts in the world. Code written against one origin can be
+// moved to another plot by converting through plot-relative coordinates.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct PlotOrigin {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64
+}
+impl PlotOrigin {
+    pub fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+    pub fn to_world(self, relative: &Location) -> Location {
+        relative.offset(self.x, self.y, self.z)
+    }
+    pub fn to_relative(self, world: &Location) -> Location {
+        world.offset(-self.x, -self.y, -self.z)
+    }
+}
+
+struct Relocate<'a> {
+    from: &'a PlotOrigin,
+    to: &'a PlotOrigin
+}
+impl VisitorMut for Relocate<'_> {
+    fn visit_value_mut(&mut self, value: &mut Value) {
+        if let Value::Location(l) = value {
+            let moved = self.to.to_world(&self.from.to_relative(l));
+            // Block locations stay on whole blocks when the origins are a fraction apart
+            *l = if l.is_block {
+                Location::block(moved.x.round() as i64, moved.y.round() as i64, moved.z.round() as i64).with_rotation(l.pitch, l.yaw)
+            } else {
+                moved
+            };
+        }
+        walk_value_mut(self, value);
+    }
+}
+
+// Moves every location in the program from one plot to another
+pub fn relocate(program: &mut Program, from: &PlotOrigin, to: &PlotOrigin) {
+    Relocate { from, to }.visit_program_mut(program);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code_line::CodeLine, decompile::parse_templates, params::ParamBuilder, serialization::DFSerializable, statements::Statement, values::Selector};
+
+    fn teleport(to: Vec<Location>) -> Program {
+        let parameters = to.into_iter().fold(ParamBuilder::new(), |b, l| b.param(Value::Location(l))).complete_unchecked();
+        Program::new_from(vec![ CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            Statement::PlayerAction { action: String::from("Teleport"), parameters, selector: Selector::Default },
+        ]) ])
+    }
+
+    #[test]
+    fn test_block_locations() {
+        let block = Location::block(10, 64, -3);
+        assert!(block.is_block);
+        assert_eq!(block.offset(1.0, -1.0, 0.5), Location { z: -2.5, ..Location::block(11, 63, -3) });
+        assert_eq!(Location::new(10.7, 64.2, -2.5).to_block(), block);
+        assert_eq!(Location::new(-0.5, 64.0, 3.2).center(), Location::new(-0.5, 64.5, 3.5));
+        assert_eq!(block.center().with_rotation(30.0, 45.0).center().to_block(), block);
+        assert_eq!(block.offset_by(&Vector { x: 1.0, y: -1.0, z: 0.5 }), Location { z: -2.5, ..Location::block(11, 63, -3) });
+    }
+
+    #[test]
+    fn test_directions() {
+        // yaw 0 faces south (+z), yaw 90 faces west (-x), pitch 90 faces down
+        assert_eq!(Location::new(0.0, 0.0, 0.0).direction(), Vector { x: 0.0, y: 0.0, z: 1.0 });
+        assert_eq!(Location::new(0.0, 0.0, 0.0).with_rotation(0.0, 90.0).direction(), Vector { x: -1.0, y: 0.0, z: 0.0 });
+        assert_eq!(Location::new(0.0, 0.0, 0.0).with_rotation(90.0, 0.0).direction(), Vector { x: 0.0, y: -1.0, z: 0.0 });
+        let facing = Location::new(0.0, 0.0, 0.0).facing(&Vector { x: 1.0, y: 1.0, z: 0.0 });
+        assert_eq!((facing.pitch, facing.yaw), (-45.0, -90.0));
+        assert_eq!(Location::new(1.0, 2.0, 3.0).with_rotation(0.0, 180.0).forward(2.0), Location::new(1.0, 2.0, 1.0).with_rotation(0.0, 180.0));
+    }
+
+    #[test]
+    fn test_rotation_roundtrip() {
+        // pitch/yaw -> vector -> pitch/yaw comes back to the same rotation
+        for (pitch, yaw) in [(0.0, 0.0), (-30.0, 45.0), (60.0, -120.0), (12.5, 170.0)] {
+            let turned = Location::new(0.0, 0.0, 0.0).with_rotation(pitch, yaw);
+            let back = Location::new(0.0, 0.0, 0.0).facing(&turned.direction());
+            assert_eq!((back.pitch, back.yaw), (pitch, yaw));
+        }
+        // and a unit vector survives vector -> pitch/yaw -> vector
+        let v = Location::new(0.0, 0.0, 0.0).facing(&Vector { x: 0.6, y: -0.8, z: 0.0 }).direction();
+        assert!((v.x - 0.6).abs() < 1e-9 && (v.y + 0.8).abs() < 1e-9 && v.z == 0.0);
+    }
+
+    #[test]
+    fn test_plot_origin() {
+        let origin = PlotOrigin::new(1000.0, 0.0, -2000.0);
+        let world = origin.to_world(&Location::block(5, 50, 5));
+        assert_eq!(world, Location::block(1005, 50, -1995));
+        assert_eq!(origin.to_relative(&world), Location::block(5, 50, 5));
+    }
+
+    #[test]
+    fn test_relocate() {
+        let mut p = teleport(vec![Location::block(5, 50, 5)]);
+        relocate(&mut p, &PlotOrigin::default(), &PlotOrigin::new(1000.0, 0.0, -2000.0));
+        let json = p.lines()[0].serialize();
+        assert!(json.contains(r#"{"id":"loc","data":{"isBlock":true,"loc":{"x":1005,"y":50,"z":-1995,"pitch":0,"yaw":0}}}"#));
+        assert!(matches!(&parse_templates(&json).unwrap()[0].body()[1].parameters().unwrap()[0], Some(Value::Location(l)) if l.is_block));
+    }
+
+    #[test]
+    fn test_relocate_by_fractions() {
+        // Block locations move by whole blocks, other locations move exactly
+        let mut p = teleport(vec![Location::block(5, 50, 5), Location::new(5.0, 50.0, 5.0)]);
+        relocate(&mut p, &PlotOrigin::default(), &PlotOrigin::new(0.25, 0.0, 10.5));
+        let json = p.lines()[0].serialize();
+        assert!(json.contains(r#""isBlock":true,"loc":{"x":5,"y":50,"z":16,"pitch":0,"yaw":0}"#));
+        assert!(json.contains(r#""isBlock":false,"loc":{"x":5.25,"y":50,"z":15.5,"pitch":0,"yaw":0}"#));
+    }
+}
@@ -1,13 +1,6 @@
-mod values;
-mod statements;
-mod serialization;
-mod code_line;
-mod compile;
-mod program;
-mod tests;
-mod params;
+use kindling::cli;
 
 fn main() {
-    println!("Hello, world!");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    std::process::exit(cli::run(&args));
 }
-
@@ -0,0 +1,371 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::{program::Program, reachability::is_kept, serialization::json_escape, statements::Statement, visit::{walk_value, walk_value_mut, walk_variable, walk_variable_mut, Visitor, VisitorMut}, values::{Value, Variable, VariableScope}};
+
+// a..z, A..Z, then aa, ab, ...
+pub fn short_name(mut n: usize) -> String {
+    const LETTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let mut out = vec![];
+    loop {
+        out.push(LETTERS[n % LETTERS.len()]);
+        n /= LETTERS.len();
+        if n == 0 {
+            break;
+        }
+        n -= 1;
+    }
+    out.reverse();
+    String::from_utf8(out).unwrap()
+}
+
+// Names can't be renamed when they are themselves built from text codes
+fn is_dynamic(name: &str) -> bool {
+    name.contains('%')
+}
+
+#[derive(Default)]
+struct Usage {
+    // Names per line and how often each appears. Parameters are line variables in
+    // game, so their declarations and references count as line variables.
+    counts: HashMap<(VariableScope, String), usize>,
+    // Names read through text codes, whatever their scope
+    codes: HashSet<String>
+}
+impl Visitor for Usage {
+    fn visit_value(&mut self, value: &Value) {
+        if let Value::Parameter(p) = value {
+            *self.counts.entry((VariableScope::Line, p.name.clone())).or_default() += 1;
+        }
+        walk_value(self, value);
+    }
+    fn visit_variable(&mut self, var: &Variable) {
+        if !is_dynamic(&var.name) {
+            let scope = if var.scope == VariableScope::Parameter { VariableScope::Line } else { var.scope };
+            *self.counts.entry((scope, var.name.clone())).or_default() += 1;
+        }
+        walk_variable(self, var);
+    }
+    fn visit_code_name(&mut self, name: &str) {
+        self.codes.insert(String::from(name));
+    }
+}
+
+struct Renamer<'a> {
+    locals: &'a HashMap<String, String>,
+    line: &'a HashMap<String, String>,
+    globals: &'a HashMap<String, String>
+}
+impl VisitorMut for Renamer<'_> {
+    fn visit_value_mut(&mut self, value: &mut Value) {
+        if let Value::Parameter(p) = value {
+            if let Some(short) = self.line.get(&p.name) {
+                p.name = short.clone();
+            }
+        }
+        walk_value_mut(self, value);
+    }
+    fn visit_variable_mut(&mut self, var: &mut Variable) {
+        let map = match var.scope {
+            VariableScope::Local => self.locals,
+            VariableScope::Line | VariableScope::Parameter => self.line,
+            VariableScope::Global => self.globals,
+            VariableScope::Saved => return
+        };
+        if let Some(short) = map.get(&var.name) {
+            var.name = short.clone();
+        }
+        walk_variable_mut(self, var);
+    }
+    // A text code could mean a variable of any scope; line variables win, then
+    // locals, like they do in game
+    fn visit_code_name_mut(&mut self, name: &mut String) {
+        if let Some(short) = self.line.get(name).or_else(|| self.locals.get(name)).or_else(|| self.globals.get(name)) {
+            *name = short.clone();
+        }
+    }
+}
+
+// Functions share locals with whoever calls them, so lines joined by calls are
+// grouped and each group gets one set of local names. A call through a text code
+// could reach any function or process.
+fn local_groups(program: &Program) -> Vec<usize> {
+    let lines = program.lines();
+    let mut group: Vec<usize> = (0..lines.len()).collect();
+    fn root(group: &mut [usize], mut n: usize) -> usize {
+        while group[n] != n {
+            group[n] = group[group[n]];
+            n = group[n];
+        }
+        n
+    }
+    let defined: HashMap<(bool, &str), usize> = lines.iter().enumerate().filter_map(|(i, l)| match l.body().first() {
+        Some(Statement::Function { name, .. }) => Some(((false, name.as_str()), i)),
+        Some(Statement::Process { name, .. }) => Some(((true, name.as_str()), i)),
+        _ => None
+    }).collect();
+    for (i, line) in lines.iter().enumerate() {
+        for stmnt in line.body() {
+            let (process, name) = match stmnt {
+                Statement::CallFunction { name, .. } => (false, name),
+                Statement::CallProcess { name, .. } => (true, name),
+                _ => continue
+            };
+            let targets: Vec<usize> = if is_dynamic(name) {
+                defined.values().copied().collect()
+            } else {
+                defined.get(&(process, name.as_str())).copied().into_iter().collect()
+            };
+            for t in targets {
+                let (a, b) = (root(&mut group, i), root(&mut group, t));
+                group[a] = b;
+            }
+        }
+    }
+    (0..lines.len()).map(|i| root(&mut group, i)).collect()
+}
+
+// Original name to minified name
+pub type Renames = BTreeMap<String, String>;
+
+#[derive(Default, Debug, PartialEq)]
+pub struct MinifyMap {
+    pub globals: Renames,
+    // Locals that appear in more than one line keep one name everywhere, since
+    // functions share locals with whoever called them
+    pub shared: Renames,
+    // Per line: its name, its locals and its line variables
+    pub lines: Vec<(String, Renames, Renames)>
+}
+impl MinifyMap {
+    pub fn to_json(&self) -> String {
+        let map = |m: &Renames| format!(
+            "{{{}}}",
+            m.iter().map(|(k, v)| format!(r#""{}":"{}""#, json_escape(k), json_escape(v))).collect::<Vec<String>>().join(",")
+        );
+        format!(
+            r#"{{"globals":{},"shared":{},"lines":[{}]}}"#,
+            map(&self.globals),
+            map(&self.shared),
+            self.lines.iter().map(|(name, locals, line)| format!(
+                r#"{{"line":"{}","locals":{}{}}}"#,
+                json_escape(name),
+                map(locals),
+                if line.is_empty() { String::new() } else { format!(r#","line_vars":{}"#, map(line)) }
+            )).collect::<Vec<String>>().join(",")
+        )
+    }
+}
+
+// Gives the most used names the shortest replacements, skipping any taken
+fn assign(mut names: Vec<(String, usize)>, taken: &HashSet<String>) -> HashMap<String, String> {
+    names.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let mut next = 0;
+    let mut out = HashMap::new();
+    for (name, _) in names {
+        let short = loop {
+            let s = short_name(next);
+            next += 1;
+            if !taken.contains(&s) {
+                break s;
+            }
+        };
+        out.insert(name, short);
+    }
+    out
+}
+
+// Renames locals and line variables to the shortest names that are unique among
+// the lines that can see them, and optionally globals across the program. Saved
+// variables are never renamed, and no new name clashes with one that is kept.
+#[derive(Clone, Default)]
+pub struct Minifier {
+    globals: bool,
+    exported: Vec<String>
+}
+impl Minifier {
+    pub fn new() -> Self {
+        Self { globals: false, exported: vec![] }
+    }
+    pub fn globals(mut self, globals: bool) -> Self {
+        self.globals = globals;
+        self
+    }
+    // Globals other plots or hand-written code rely on; same patterns as --keep
+    pub fn export(mut self, pattern: &str) -> Self {
+        self.exported.push(String::from(pattern));
+        self
+    }
+    pub fn minify(&self, program: &mut Program) -> MinifyMap {
+        let usages: Vec<Usage> = program.lines().iter().map(|l| {
+            let mut usage = Usage::default();
+            usage.visit_line(l);
+            usage
+        }).collect();
+        // Names that are left as they are, and names text codes read, can't be handed out
+        let mut taken: HashSet<String> = HashSet::new();
+        let mut global_counts: HashMap<String, usize> = HashMap::new();
+        let mut local_lines: HashMap<String, usize> = HashMap::new();
+        let mut local_counts: HashMap<String, usize> = HashMap::new();
+        for usage in &usages {
+            taken.extend(usage.codes.iter().cloned());
+            for ((scope, name), count) in &usage.counts {
+                match scope {
+                    VariableScope::Global if self.globals && !is_kept(name, &self.exported) =>
+                        *global_counts.entry(name.clone()).or_default() += count,
+                    VariableScope::Global | VariableScope::Saved => {
+                        taken.insert(name.clone());
+                    }
+                    VariableScope::Local => {
+                        *local_lines.entry(name.clone()).or_default() += 1;
+                        *local_counts.entry(name.clone()).or_default() += count;
+                    }
+                    VariableScope::Line | VariableScope::Parameter => {}
+                }
+            }
+        }
+        let globals = assign(global_counts.into_iter().collect(), &taken);
+        taken.extend(globals.values().cloned());
+        let shared = assign(
+            local_counts.iter().filter(|(name, _)| local_lines[*name] > 1).map(|(n, c)| (n.clone(), *c)).collect(),
+            &taken
+        );
+        taken.extend(shared.values().cloned());
+        let mut map = MinifyMap {
+            globals: globals.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            shared: shared.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            lines: vec![]
+        };
+        let private = |usage: &Usage| -> Vec<(String, usize)> {
+            usage.counts.iter()
+                .filter(|((scope, name), _)| *scope == VariableScope::Local && local_lines[name] == 1)
+                .map(|((_, name), count)| (name.clone(), *count))
+                .collect()
+        };
+        let groups = local_groups(program);
+        let mut group_locals: HashMap<usize, HashMap<String, String>> = HashMap::new();
+        for group in groups.iter().copied().collect::<BTreeSet<usize>>() {
+            let names = usages.iter().zip(&groups).filter(|(_, g)| **g == group).flat_map(|(u, _)| private(u)).collect();
+            group_locals.insert(group, assign(names, &taken));
+        }
+        for ((line, usage), group) in program.lines_mut().iter_mut().zip(&usages).zip(&groups) {
+            let group_locals = &group_locals[group];
+            let mut locals: HashMap<String, String> = private(usage).into_iter().map(|(name, _)| {
+                let short = group_locals[&name].clone();
+                (name, short)
+            }).collect();
+            // A text code could mean either scope, so line variables and parameters
+            // stay clear of every local name in the group
+            let mut line_taken = taken.clone();
+            line_taken.extend(group_locals.values().cloned());
+            let line_vars = assign(
+                usage.counts.iter().filter(|((scope, _), _)| *scope == VariableScope::Line).map(|((_, name), count)| (name.clone(), *count)).collect(),
+                &line_taken
+            );
+            map.lines.push((
+                line.name(),
+                locals.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+                line_vars.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+            ));
+            locals.extend(shared.iter().map(|(k, v)| (k.clone(), v.clone())));
+            Renamer { locals: &locals, line: &line_vars, globals: &globals }.visit_line_mut(line);
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code_line::CodeLine, params::ParamBuilder, serialization::DFSerializable, testing::{local, set, var, variable}, values::{FunctionParam, Number, Text, ValueType}};
+
+    #[test]
+    fn test_minify() {
+        let mut p = Program::new_from(vec![
+            CodeLine::new_from(vec![
+                Statement::PlayerEvent(String::from("Join")),
+                set(local("player.score"), Value::Number(Number::from(1))),
+                set(local("player.name"), var("player.score")),
+                set(local("add.return"), var("player.score")),
+                set(variable("total.joins", VariableScope::Saved), var("player.name")),
+                set(variable("online", VariableScope::Global), Value::Text(Text(String::from("%var(player.name) %var(online) %var(%default)")))),
+                set(variable("api.count", VariableScope::Global), var("%default.kills")),
+            ]),
+            CodeLine::new_from(vec![
+                Statement::Function { name: String::from("add"), parameters: ParamBuilder::new().param(Value::Parameter(FunctionParam::new("amount", ValueType::Number))).complete_unchecked() },
+                set(local("add.return"), Value::Variable(variable("amount", VariableScope::Parameter))),
+            ]),
+        ]);
+        let map = Minifier::new().globals(true).export("api.*").minify(&mut p);
+        assert_eq!(map.to_json(), concat!(
+            r#"{"globals":{"online":"a"},"shared":{"add.return":"b"},"#,
+            r#""lines":[{"line":"Player Event: Join","locals":{"player.name":"d","player.score":"c"}},{"line":"Function: add","locals":{},"line_vars":{"amount":"c"}}]}"#
+        ));
+        let join = p.lines()[0].serialize();
+        assert!(join.contains(r#""name":"total.joins","scope":"saved""#));
+        assert!(join.contains(r#""name":"api.count","scope":"unsaved""#));
+        assert!(join.contains(r#""name":"%default.kills","scope":"local""#));
+        assert!(join.contains("%var(d) %var(a) %var(%default)"));
+        assert!(p.lines()[1].serialize().contains(r#""id":"pn_el","data":{"name":"c""#));
+        assert!(p.lines()[1].serialize().contains(r#"{"id":"var","data":{"name":"c","scope":"line"}}"#));
+    }
+
+    #[test]
+    fn test_parameter_read_as_line_variable() {
+        // A body that reads its parameter as a line variable keeps reading the renamed parameter
+        let mut p = Program::new_from(vec![
+            CodeLine::new_from(vec![
+                Statement::Function { name: String::from("heal"), parameters: ParamBuilder::new().param(Value::Parameter(FunctionParam::new("amount", ValueType::Number))).complete_unchecked() },
+                set(local("healed"), Value::Variable(variable("amount", VariableScope::Line))),
+                set(local("healed"), Value::Variable(variable("amount", VariableScope::Parameter))),
+            ]),
+        ]);
+        let map = Minifier::new().minify(&mut p);
+        assert_eq!(map.to_json(), r#"{"globals":{},"shared":{},"lines":[{"line":"Function: heal","locals":{"healed":"a"},"line_vars":{"amount":"b"}}]}"#);
+        let heal = p.lines()[0].serialize();
+        assert!(heal.contains(r#""id":"pn_el","data":{"name":"b""#));
+        assert!(heal.contains(r#"{"id":"var","data":{"name":"b","scope":"line"}}"#) && !heal.contains("amount"));
+    }
+
+    #[test]
+    fn test_names_dont_collide() {
+        // A function shares locals with its caller, so their private names can't overlap,
+        // and no new name may take one that is kept or read by a text code
+        let mut p = Program::new_from(vec![
+            CodeLine::new_from(vec![
+                Statement::PlayerEvent(String::from("Join")),
+                set(local("y"), Value::Number(Number::from(1))),
+                set(variable("b", VariableScope::Saved), var("y")),
+                Statement::CallFunction { name: String::from("helper"), parameters: ParamBuilder::new().complete_unchecked() },
+                set(variable("c", VariableScope::Global), Value::Text(Text(String::from("%var(d) %var(y)")))),
+                set(local("slot_%var(y)"), var("y")),
+            ]),
+            CodeLine::new_from(vec![
+                Statement::Function { name: String::from("helper"), parameters: ParamBuilder::new().complete_unchecked() },
+                set(local("tmp"), Value::Number(Number::from(2))),
+            ]),
+        ]);
+        let map = Minifier::new().minify(&mut p);
+        assert_eq!(map.to_json(), r#"{"globals":{},"shared":{},"lines":[{"line":"Player Event: Join","locals":{"y":"a"}},{"line":"Function: helper","locals":{"tmp":"e"}}]}"#);
+        let join = p.lines()[0].serialize();
+        assert!(join.contains("%var(d) %var(a)") && join.contains(r#""name":"slot_%var(a)""#));
+    }
+
+    #[test]
+    fn test_short_name() {
+        assert_eq!((short_name(0), short_name(51), short_name(52), short_name(53)), (String::from("a"), String::from("Z"), String::from("aa"), String::from("ab")));
+    }
+
+    #[test]
+    fn test_line_variables() {
+        let mut p = Program::new_from(vec![ CodeLine::new_from(vec![
+            Statement::Function { name: String::from("heal"), parameters: ParamBuilder::new().param(Value::Parameter(FunctionParam::new("amount", ValueType::Number))).complete_unchecked() },
+            set(variable("scratch", VariableScope::Line), Value::Variable(variable("amount", VariableScope::Parameter))),
+            set(local("heal.return"), Value::Variable(variable("scratch", VariableScope::Line))),
+        ]) ]);
+        let map = Minifier::new().minify(&mut p);
+        assert_eq!(map.to_json(),
+            r#"{"globals":{},"shared":{},"lines":[{"line":"Function: heal","locals":{"heal.return":"a"},"line_vars":{"amount":"b","scratch":"c"}}]}"#
+        );
+        assert!(p.lines()[0].serialize().contains(r#"{"id":"var","data":{"name":"c","scope":"line"}}"#));
+    }
+}
@@ -0,0 +1,69 @@
+// Just enough binary NBT to write schematics
+pub enum Nbt {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    String(String),
+    ByteArray(Vec<u8>),
+    IntArray(Vec<i32>),
+    List(Vec<Nbt>),
+    Compound(Vec<(String, Nbt)>)
+}
+impl Nbt {
+    fn id(&self) -> u8 {
+        match self {
+            Nbt::Byte(_) => 1,
+            Nbt::Short(_) => 2,
+            Nbt::Int(_) => 3,
+            Nbt::ByteArray(_) => 7,
+            Nbt::String(_) => 8,
+            Nbt::List(_) => 9,
+            Nbt::Compound(_) => 10,
+            Nbt::IntArray(_) => 11,
+        }
+    }
+    pub fn to_bytes(&self, root_name: &str) -> Vec<u8> {
+        let mut out = vec![self.id()];
+        write_string(&mut out, root_name);
+        self.write_payload(&mut out);
+        out
+    }
+    fn write_payload(&self, out: &mut Vec<u8>) {
+        match self {
+            Nbt::Byte(b) => out.extend(b.to_be_bytes()),
+            Nbt::Short(s) => out.extend(s.to_be_bytes()),
+            Nbt::Int(i) => out.extend(i.to_be_bytes()),
+            Nbt::String(s) => write_string(out, s),
+            Nbt::ByteArray(bytes) => {
+                out.extend((bytes.len() as i32).to_be_bytes());
+                out.extend(bytes);
+            }
+            Nbt::IntArray(ints) => {
+                out.extend((ints.len() as i32).to_be_bytes());
+                for i in ints {
+                    out.extend(i.to_be_bytes());
+                }
+            }
+            Nbt::List(items) => {
+                out.push(items.first().map_or(0, |i| i.id()));
+                out.extend((items.len() as i32).to_be_bytes());
+                for i in items {
+                    i.write_payload(out);
+                }
+            }
+            Nbt::Compound(entries) => {
+                for (name, value) in entries {
+                    out.push(value.id());
+                    write_string(out, name);
+                    value.write_payload(out);
+                }
+                out.push(0);
+            }
+        }
+    }
+}
+
+fn write_string(out: &mut Vec<u8>, s: &str) {
+    out.extend((s.len() as u16).to_be_bytes());
+    out.extend(s.as_bytes());
+}
@@ -0,0 +1,345 @@
+use std::{fmt::Display, mem};
+
+use crate::{code_line::CodeLine, decimal::Decimal, params::ParamBuilder, program::Program, statements::Statement, validate::opens_bracket, values::{Number, Text, Value, Variable}, visit::Fold};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Rewrite {
+    // If blocks with nothing inside, and Else blocks with nothing inside
+    EmptyBranches,
+    // Blocks after a Control End or Return, up to the end of the enclosing bracket
+    Unreachable,
+    // `x = a` directly followed by `x = b`
+    MergeAssignments,
+    // + - x / with only number literals becomes a plain `=`
+    FoldConstants,
+    // Waits of zero
+    NoopWaits
+}
+impl Rewrite {
+    pub const ALL: [Rewrite; 5] = [Rewrite::EmptyBranches, Rewrite::Unreachable, Rewrite::MergeAssignments, Rewrite::FoldConstants, Rewrite::NoopWaits];
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "empty-branches" => Some(Rewrite::EmptyBranches),
+            "unreachable" => Some(Rewrite::Unreachable),
+            "merge-assignments" => Some(Rewrite::MergeAssignments),
+            "fold-constants" => Some(Rewrite::FoldConstants),
+            "noop-waits" => Some(Rewrite::NoopWaits),
+            _ => None
+        }
+    }
+}
+impl Display for Rewrite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Rewrite::EmptyBranches => "empty-branches",
+            Rewrite::Unreachable => "unreachable",
+            Rewrite::MergeAssignments => "merge-assignments",
+            Rewrite::FoldConstants => "fold-constants",
+            Rewrite::NoopWaits => "noop-waits",
+        })
+    }
+}
+
+#[derive(Default, Debug, PartialEq)]
+pub struct OptimizeReport {
+    // How many times each rewrite applied and how many blocks that removed
+    pub applied: Vec<(Rewrite, usize, usize)>
+}
+impl OptimizeReport {
+    fn record(&mut self, rewrite: Rewrite, blocks: usize) {
+        match self.applied.iter_mut().find(|(r, _, _)| *r == rewrite) {
+            Some((_, times, saved)) => {
+                *times += 1;
+                *saved += blocks;
+            }
+            None => self.applied.push((rewrite, 1, blocks))
+        }
+    }
+    pub fn blocks_saved(&self) -> usize {
+        self.applied.iter().map(|(_, _, saved)| saved).sum()
+    }
+}
+impl Display for OptimizeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "optimizer saved {} block(s)", self.blocks_saved())?;
+        for (rewrite, times, saved) in &self.applied {
+            writeln!(f, "  {rewrite}: applied {times} time(s), saved {saved} block(s)")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct Optimizer {
+    enabled: Vec<Rewrite>
+}
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Optimizer {
+    pub fn new() -> Self {
+        Self { enabled: Rewrite::ALL.to_vec() }
+    }
+    pub fn none() -> Self {
+        Self { enabled: vec![] }
+    }
+    pub fn enable(mut self, rewrite: Rewrite) -> Self {
+        if !self.enabled.contains(&rewrite) {
+            self.enabled.push(rewrite);
+        }
+        self
+    }
+    pub fn disable(mut self, rewrite: Rewrite) -> Self {
+        self.enabled.retain(|r| *r != rewrite);
+        self
+    }
+    pub fn is_enabled(&self, rewrite: Rewrite) -> bool {
+        self.enabled.contains(&rewrite)
+    }
+    pub fn optimize(&self, program: &mut Program) -> OptimizeReport {
+        let mut report = OptimizeReport::default();
+        for line in program.lines_mut() {
+            self.optimize_line(line, &mut report);
+        }
+        report
+    }
+    // Rewrites can open up others (a folded constant can be merged, removing
+    // a wait can empty an if), so this runs until nothing changes
+    pub fn optimize_line(&self, line: &mut CodeLine, report: &mut OptimizeReport) {
+        loop {
+            let before = report.applied.iter().map(|(_, times, _)| times).sum::<usize>();
+            if self.is_enabled(Rewrite::FoldConstants) {
+                *line = ConstantFolder { report }.fold_line(mem::take(line));
+            }
+            if self.is_enabled(Rewrite::NoopWaits) {
+                *line = NoopWaits { report }.fold_line(mem::take(line));
+            }
+            let body = line.body_mut();
+            if self.is_enabled(Rewrite::Unreachable) {
+                unreachable(body, report);
+            }
+            if self.is_enabled(Rewrite::MergeAssignments) {
+                merge_assignments(body, report);
+            }
+            if self.is_enabled(Rewrite::EmptyBranches) {
+                empty_branches(body, report);
+            }
+            if report.applied.iter().map(|(_, times, _)| times).sum::<usize>() == before {
+                return;
+            }
+        }
+    }
+}
+
+fn is_if(stmnt: &Statement) -> bool {
+    opens_bracket(stmnt) && !matches!(stmnt, Statement::Else | Statement::Repeat { .. })
+}
+
+fn empty_branches(body: &mut Vec<Statement>, report: &mut OptimizeReport) {
+    let mut i = 0;
+    while i + 1 < body.len() {
+        if !matches!(body[i + 1], Statement::Close) {
+            i += 1;
+            continue;
+        }
+        if matches!(body[i], Statement::Else) {
+            body.drain(i..i + 2);
+            report.record(Rewrite::EmptyBranches, 2);
+            continue;
+        }
+        if !is_if(&body[i]) {
+            i += 1;
+            continue;
+        }
+        // An empty if with an else turns into the negated if
+        if matches!(body.get(i + 2), Some(Statement::Else)) {
+            if let Statement::IfVariable { not, .. } | Statement::IfPlayer { not, .. } |
+                   Statement::IfEntity { not, .. } | Statement::IfGame { not, .. } = &mut body[i] {
+                *not = !*not;
+            }
+            body.drain(i + 1..i + 3);
+        } else {
+            body.drain(i..i + 2);
+        }
+        report.record(Rewrite::EmptyBranches, 2);
+    }
+}
+
+fn unreachable(body: &mut Vec<Statement>, report: &mut OptimizeReport) {
+    let mut i = 0;
+    while i < body.len() {
+        let ends = matches!(&body[i], Statement::Control { action, .. } if action == "End" || action == "Return");
+        i += 1;
+        if !ends {
+            continue;
+        }
+        let mut depth = 0;
+        let mut end = i;
+        while end < body.len() {
+            match &body[end] {
+                Statement::Close | Statement::CloseRepeat if depth == 0 => break,
+                Statement::Close | Statement::CloseRepeat => depth -= 1,
+                s if opens_bracket(s) => depth += 1,
+                _ => {}
+            }
+            end += 1;
+        }
+        if end > i {
+            report.record(Rewrite::Unreachable, end - i);
+            body.drain(i..end);
+        }
+    }
+}
+
+fn assignment(stmnt: &Statement) -> Option<(&Variable, &Value)> {
+    match stmnt {
+        Statement::SetVariable { action, parameters } if action == "=" => match (&parameters[0], &parameters[1]) {
+            (Some(Value::Variable(var)), Some(value)) => Some((var, value)),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+// Could reading `value` see `var`, directly or through a text code?
+fn mentions(value: &Value, var: &Variable) -> bool {
+    match value {
+        Value::Variable(v) => v == var,
+        Value::Text(Text(t)) => t.contains('%'),
+        Value::Component(c) => c.as_str().contains('%'),
+        Value::Number(Number::Math(_)) => true,
+        _ => false
+    }
+}
+
+fn merge_assignments(body: &mut Vec<Statement>, report: &mut OptimizeReport) {
+    let mut i = 0;
+    while i + 1 < body.len() {
+        let redundant = match (assignment(&body[i]), assignment(&body[i + 1])) {
+            (Some((first, _)), Some((second, value))) => first == second && !mentions(value, second),
+            _ => false
+        };
+        if redundant {
+            body.remove(i);
+            report.record(Rewrite::MergeAssignments, 1);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+// `x = a + b` on number literals becomes `x = result`
+struct ConstantFolder<'a> {
+    report: &'a mut OptimizeReport
+}
+impl Fold for ConstantFolder<'_> {
+    fn fold_statement(&mut self, stmnt: Statement) -> Vec<Statement> {
+        match fold_constant(&stmnt) {
+            Some(folded) => {
+                self.report.record(Rewrite::FoldConstants, 0);
+                vec![folded]
+            }
+            None => vec![stmnt]
+        }
+    }
+}
+
+fn fold_constant(stmnt: &Statement) -> Option<Statement> {
+    let Statement::SetVariable { action, parameters } = stmnt else { return None };
+    let Some(Value::Variable(var)) = &parameters[0] else { return None };
+    let numbers: Vec<Decimal> = parameters[1..].iter()
+        .flatten()
+        .map(|v| match v {
+            Value::Number(Number::Literal(n)) => Some(*n),
+            _ => None
+        })
+        .collect::<Option<_>>()
+        .filter(|n: &Vec<Decimal>| !n.is_empty())?;
+    let rest = &mut numbers[1..].iter();
+    // Anything that overflows, divides by zero or divides into an endless fraction
+    // is left for the game to deal with
+    let result = match action.as_str() {
+        "+" => rest.try_fold(numbers[0], |a, b| a.checked_add(b)),
+        "x" => rest.try_fold(numbers[0], |a, b| a.checked_mul(b)),
+        "-" => rest.try_fold(numbers[0], |a, b| a.checked_sub(b)),
+        "/" => rest.try_fold(numbers[0], |a, b| a.checked_div(b)),
+        _ => None
+    }?;
+    Some(Statement::SetVariable {
+        action: String::from("="),
+        parameters: ParamBuilder::new().param(Value::Variable(var.clone())).param(Value::Number(Number::Literal(result))).complete_unchecked()
+    })
+}
+
+// Waits for zero or fewer ticks
+struct NoopWaits<'a> {
+    report: &'a mut OptimizeReport
+}
+impl Fold for NoopWaits<'_> {
+    fn fold_statement(&mut self, stmnt: Statement) -> Vec<Statement> {
+        match &stmnt {
+            Statement::Control { action, parameters } if action == "Wait" && matches!(&parameters[0], Some(Value::Number(Number::Literal(n))) if !n.is_positive()) => {
+                self.report.record(Rewrite::NoopWaits, 1);
+                vec![]
+            }
+            _ => vec![stmnt]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{serialization::DFSerializable, testing::{local, set, set_var, var}};
+
+    fn line() -> CodeLine {
+        let control = |action: &str, values: Vec<Value>| Statement::Control {
+            action: String::from(action),
+            parameters: values.into_iter().fold(ParamBuilder::new(), |b, v| b.param(v)).complete_unchecked()
+        };
+        let if_var = || Statement::IfVariable { action: String::from("="), parameters: ParamBuilder::new().param(var("x")).param(Value::Number(Number::from(1))).complete_unchecked(), not: false };
+        CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            set_var("+", local("x"), vec![ Value::Number(Number::from(2)), Value::Number(Number::from(3)) ]),
+            set(local("x"), Value::Number(Number::from(1))),
+            control("Wait", vec![ Value::Number(Number::from(0)) ]),
+            if_var(),
+            control("Wait", vec![ Value::Number(Number::from(0)) ]),
+            Statement::Close,
+            Statement::Else,
+            control("Return", vec![]),
+            set(local("x"), Value::Number(Number::from(4))),
+            Statement::Close,
+            control("End", vec![]),
+            control("Wait", vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_optimizer() {
+        let mut p = Program::new_from(vec![ line() ]);
+        let report = Optimizer::new().optimize(&mut p);
+        assert_eq!(report, OptimizeReport { applied: vec![
+            (Rewrite::FoldConstants, 1, 0),
+            (Rewrite::NoopWaits, 2, 2),
+            (Rewrite::Unreachable, 2, 2),
+            (Rewrite::MergeAssignments, 1, 1),
+            (Rewrite::EmptyBranches, 1, 2),
+        ] });
+        assert_eq!(report.blocks_saved(), 7);
+        let names: Vec<String> = p.lines()[0].body().iter().map(|s| s.name()).collect();
+        assert_eq!(names.len(), 6);
+        assert!(matches!(&p.lines()[0].body()[2], Statement::IfVariable { not: true, .. }));
+        assert_eq!(p.lines()[0].serialize().matches(r#""id":"num","data":{"name":"1"}"#).count(), 2);
+    }
+
+    #[test]
+    fn test_single_rewrite() {
+        let mut p = Program::new_from(vec![ line() ]);
+        let report = Optimizer::none().enable(Rewrite::NoopWaits).optimize(&mut p);
+        assert_eq!(report.blocks_saved(), 2);
+        assert_eq!(p.lines()[0].body().len(), 11);
+    }
+}
@@ -4,6 +4,12 @@ pub struct ParamBuilder {
     params: Vec<Value>,
     tags: Vec<Tag>
 }
+impl Default for ParamBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ParamBuilder {
     pub fn new() -> Self {
         Self {
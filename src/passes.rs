@@ -0,0 +1,316 @@
+use std::{fmt::Display, fs, path::PathBuf, time::{Duration, Instant}};
+
+use crate::{diagnostics::Report, minify::Minifier, optimize::Optimizer, program::Program, reachability::eliminate_dead_code, size::{check_limits, compress_error, measure, SizeLimits}, transport::Transport, statements::Statement, validate::{opens_bracket, validate}};
+
+// Looks at the program without changing it. Ok carries an optional summary for
+// the log, Err stops the pipeline.
+pub trait AnalysisPass {
+    fn name(&self) -> &str;
+    fn analyze(&mut self, program: &Program) -> Result<Option<String>, String>;
+}
+
+pub trait TransformPass {
+    fn name(&self) -> &str;
+    fn transform(&mut self, program: &mut Program) -> Result<Option<String>, String>;
+}
+
+pub enum Pass {
+    Analysis(Box<dyn AnalysisPass>),
+    Transform(Box<dyn TransformPass>)
+}
+impl Pass {
+    pub fn name(&self) -> &str {
+        match self {
+            Pass::Analysis(p) => p.name(),
+            Pass::Transform(p) => p.name(),
+        }
+    }
+}
+
+pub struct PassRecord {
+    pub name: String,
+    pub time: Duration,
+    pub summary: Option<String>,
+    // Only for transforms that were asked to be dumped
+    pub before: Option<String>,
+    pub after: Option<String>
+}
+
+#[derive(Default)]
+pub struct PassLog {
+    pub records: Vec<PassRecord>
+}
+impl PassLog {
+    pub fn total_time(&self) -> Duration {
+        self.records.iter().map(|r| r.time).sum()
+    }
+    pub fn timings(&self) -> String {
+        let mut out = String::new();
+        for r in &self.records {
+            out += &format!("{:>10.3}ms  {}\n", r.time.as_secs_f64() * 1000.0, r.name);
+        }
+        out += &format!("{:>10.3}ms  total\n", self.total_time().as_secs_f64() * 1000.0);
+        out
+    }
+    pub fn dumps(&self) -> String {
+        let mut out = String::new();
+        for r in &self.records {
+            if let (Some(before), Some(after)) = (&r.before, &r.after) {
+                out += &format!("=== before {} ===\n{before}=== after {} ===\n{after}", r.name, r.name);
+            }
+        }
+        out
+    }
+}
+
+#[derive(Debug)]
+pub struct PassError {
+    pub pass: String,
+    pub message: String
+}
+impl Display for PassError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// Runs passes in the order they were added
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<Pass>,
+    dump: Vec<String>
+}
+impl PassManager {
+    pub fn new() -> Self {
+        Self { passes: vec![], dump: vec![] }
+    }
+    pub fn analysis(mut self, pass: impl AnalysisPass + 'static) -> Self {
+        self.passes.push(Pass::Analysis(Box::new(pass)));
+        self
+    }
+    pub fn transform(mut self, pass: impl TransformPass + 'static) -> Self {
+        self.passes.push(Pass::Transform(Box::new(pass)));
+        self
+    }
+    // Keep the program as it was before and after this pass; "all" dumps every transform
+    pub fn dump(mut self, pass: &str) -> Self {
+        self.dump.push(String::from(pass));
+        self
+    }
+    pub fn passes(&self) -> impl Iterator<Item = &str> {
+        self.passes.iter().map(|p| p.name())
+    }
+    pub fn run(&mut self, program: &mut Program) -> Result<PassLog, PassError> {
+        let mut log = PassLog::default();
+        for pass in &mut self.passes {
+            let name = String::from(pass.name());
+            let dump = self.dump.iter().any(|d| d == "all" || *d == name);
+            let start = Instant::now();
+            let (result, before, after) = match pass {
+                Pass::Analysis(p) => (p.analyze(program), None, None),
+                Pass::Transform(p) => {
+                    let before = dump.then(|| dump_program(program));
+                    let result = p.transform(program);
+                    (result, before, dump.then(|| dump_program(program)))
+                }
+            };
+            let time = start.elapsed();
+            match result {
+                Ok(summary) => log.records.push(PassRecord { name, time, summary, before, after }),
+                Err(message) => return Err(PassError { pass: name, message })
+            }
+        }
+        Ok(log)
+    }
+}
+
+// Readable listing of a program: one block per row, indented by bracket depth,
+// with its arguments as template JSON
+pub fn dump_program(program: &Program) -> String {
+    let mut out = String::new();
+    for (i, line) in program.lines().iter().enumerate() {
+        out += &format!("line {} ({}):\n", i + 1, line.name());
+        let mut depth: usize = 1;
+        for stmnt in line.body() {
+            if matches!(stmnt, Statement::Close | Statement::CloseRepeat) {
+                depth = depth.saturating_sub(1);
+            }
+            out += &"  ".repeat(depth);
+            out += &stmnt.name();
+            let args: Vec<String> = stmnt.parameters().into_iter().flatten().flatten().map(|v| v.serialize_item(stmnt)).collect();
+            if !args.is_empty() {
+                out += &format!(" [{}]", args.join(", "));
+            }
+            out += "\n";
+            if opens_bracket(stmnt) {
+                depth += 1;
+            }
+        }
+    }
+    out
+}
+
+// Fails with the rendered report when validation finds errors, and otherwise
+// reports any warnings
+pub struct ValidatePass {
+    pub json: bool,
+    pub color: bool
+}
+impl AnalysisPass for ValidatePass {
+    fn name(&self) -> &str {
+        "validate"
+    }
+    fn analyze(&mut self, program: &Program) -> Result<Option<String>, String> {
+        let report = validate(program);
+        if report.has_errors() {
+            return Err(if self.json { report.to_json() } else { report.render(self.color) });
+        }
+        // Warnings become the summary, rendered like errors so they point at their blocks
+        Ok((!report.diagnostics.is_empty()).then(|| if self.json { report.to_json() } else { report.render(self.color).trim_end().to_string() }))
+    }
+}
+
+pub struct DeadCodePass {
+    pub keep: Vec<String>
+}
+impl TransformPass for DeadCodePass {
+    fn name(&self) -> &str {
+        "dead-code"
+    }
+    fn transform(&mut self, program: &mut Program) -> Result<Option<String>, String> {
+        let removed = eliminate_dead_code(program, &self.keep);
+        Ok((!removed.is_empty()).then(|| format!("removed {}", removed.join(", "))))
+    }
+}
+
+pub struct OptimizePass(pub Optimizer);
+impl TransformPass for OptimizePass {
+    fn name(&self) -> &str {
+        "optimize"
+    }
+    fn transform(&mut self, program: &mut Program) -> Result<Option<String>, String> {
+        let report = self.0.optimize(program);
+        Ok((report.blocks_saved() > 0).then(|| report.to_string().trim_end().to_string()))
+    }
+}
+
+// Writes the name mapping to `map` when given
+pub struct MinifyPass {
+    pub minifier: Minifier,
+    pub map: Option<PathBuf>
+}
+impl TransformPass for MinifyPass {
+    fn name(&self) -> &str {
+        "minify"
+    }
+    fn transform(&mut self, program: &mut Program) -> Result<Option<String>, String> {
+        let map = self.minifier.minify(program);
+        if let Some(path) = &self.map {
+            fs::write(path, map.to_json()).map_err(|e| format!("could not write {}: {e}", path.display()))?;
+        }
+        let renamed = map.globals.len() + map.shared.len() + map.lines.iter().map(|(_, locals, line)| locals.len() + line.len()).sum::<usize>();
+        Ok((renamed > 0).then(|| format!("renamed {renamed} variable(s)")))
+    }
+}
+
+// Fails when a line goes over a size limit, pointing at its biggest blocks
+pub struct SizePass {
+    pub limits: SizeLimits,
+    pub transport: Transport,
+    pub json: bool,
+    pub color: bool
+}
+impl AnalysisPass for SizePass {
+    fn name(&self) -> &str {
+        "size"
+    }
+    fn analyze(&mut self, program: &Program) -> Result<Option<String>, String> {
+        let report = match measure(program, self.transport) {
+            Ok(sizes) => check_limits(program, &sizes, &self.limits),
+            Err(e) => Report::from(compress_error(e))
+        };
+        if report.has_errors() {
+            return Err(if self.json { report.to_json() } else { report.render(self.color) });
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, rc::Rc};
+    use crate::{code_line::CodeLine, params::ParamBuilder, testing::{local, set}, values::{Number, Value}};
+
+    struct CountLines(Rc<Cell<usize>>);
+    impl AnalysisPass for CountLines {
+        fn name(&self) -> &str {
+            "count-lines"
+        }
+        fn analyze(&mut self, program: &Program) -> Result<Option<String>, String> {
+            self.0.set(program.lines().len());
+            Ok(None)
+        }
+    }
+
+    // A Join line with a no-op wait, and a function nothing calls
+    fn program() -> Program {
+        Program::new_from(vec![
+            CodeLine::new_from(vec![ Statement::PlayerEvent(String::from("Join")),
+                Statement::Control { action: String::from("Wait"), parameters: ParamBuilder::new().param(Value::Number(Number::from(0))).complete_unchecked() } ]),
+            CodeLine::new_from(vec![ Statement::Function { name: String::from("unused"), parameters: ParamBuilder::new().complete_unchecked() } ]),
+        ])
+    }
+    fn manager(seen: &Rc<Cell<usize>>) -> PassManager {
+        PassManager::new()
+            .transform(DeadCodePass { keep: vec![] })
+            .analysis(CountLines(seen.clone()))
+            .transform(OptimizePass(Optimizer::new()))
+            .dump("optimize")
+    }
+
+    #[test]
+    fn test_pass_order() {
+        let seen = Rc::new(Cell::new(0));
+        let mut passes = manager(&seen);
+        assert_eq!(passes.passes().collect::<Vec<&str>>(), vec!["dead-code", "count-lines", "optimize"]);
+        passes.run(&mut program()).unwrap();
+        // The analysis sees the program after dead code was removed
+        assert_eq!(seen.get(), 1);
+    }
+
+    #[test]
+    fn test_pass_log() {
+        let log = manager(&Rc::new(Cell::new(0))).run(&mut program()).unwrap();
+        assert_eq!(log.records[0].summary.as_deref(), Some("removed unused"));
+        assert!(log.records[0].before.is_none());
+        assert_eq!(log.timings().lines().count(), 4);
+    }
+
+    #[test]
+    fn test_dumps() {
+        let log = manager(&Rc::new(Cell::new(0))).run(&mut program()).unwrap();
+        assert_eq!(log.dumps(), concat!(
+            "=== before optimize ===\n",
+            "line 1 (Player Event: Join):\n",
+            "  Player Event: Join\n",
+            "  Control: Wait [{\"id\":\"num\",\"data\":{\"name\":\"0\"}}]\n",
+            "=== after optimize ===\n",
+            "line 1 (Player Event: Join):\n",
+            "  Player Event: Join\n",
+        ));
+    }
+
+    #[test]
+    fn test_validate_warnings() {
+        // Validation warnings are rendered, not just counted
+        let p = Program::new_from(vec![ CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            set(local("unused"), Value::Number(Number::from(1))),
+        ]) ]);
+        let summary = ValidatePass { json: false, color: false }.analyze(&p).unwrap().unwrap();
+        assert!(summary.starts_with("warning[W0302]: value assigned to `unused` is never read\n --> line 1 (Player Event: Join):2:"));
+        let summary = ValidatePass { json: true, color: false }.analyze(&p).unwrap().unwrap();
+        assert!(summary.starts_with(r#"[{"severity":"warning","code":"W0302""#));
+    }
+}
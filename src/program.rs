@@ -1,48 +1,239 @@
-use core::time;
-use std::fmt::Display;
-
-use websocket;
-use websocket::ClientBuilder;
-use websocket::ws::Sender;
-
-use crate::{code_line::CodeLine, compile::DFCompile};
+use crate::{code_line::CodeLine, compile::DFCompile, diagnostics::Report, formatting::{strip, to_ampersand, Color, LegacyText}, serialization::{json_escape, snbt_escape}, size::{check_limits, compress_error, measure, SizeLimits}, transport::{send, Transport}};
 
 pub struct Program {
     lines: Vec<CodeLine>,
-    owner: Option<String>
+    owner: Option<String>,
+    // The project's name, shown on the template items
+    name: Option<String>
+}
+impl Default for Program {
+    fn default() -> Self {
+        Self::new()
+    }
 }
+
 impl Program {
-    pub fn compile_program(self, target_size: u64) -> Vec<String> {
-        self.lines.iter().map(|x| {
-            format!(
-                r##"/give @p ender_chest{{display:{{Name:'{{"extra":[{{"italic":false,"color":"#FF8855","text":"Compiled "}},{{"italic":false,"color":"dark_gray","text":"» "}},{{"italic":false,"color":"#FFCC99","text":"{1}"}}],"text":""}}'}},PublicBukkitValues:{{"hypercube:codetemplatedata":'{{"author":"{0}","name":"&x&f&f&8&8&5&5Compiled &8» &x&f&f&c&c&9&9{1}","version":1,"code":"{2}"}}'}}}}"##, 
-                self.owner.as_ref().map_or_else(|| "Kindling", |n| n),
-                x.name(),
-                x.compile()
-            )
-        } ).collect()
-    }
-    pub fn compile_program_ws(self, target_size: u64) {
-        let mut client = ClientBuilder::new("ws://localhost:31371/codeutilities/item").unwrap()
-            .connect_insecure()
-            .unwrap();
-        for l in self.lines.iter() {
-            let message = format!(
-                r##"{{"source":"Kindling","type":"nbt","data":"{{\"id\":\"minecraft:ender_chest\",\"Count\":1,\"tag\":{{display:{{Name:\'{{\"extra\":[{{\"italic\":false,\"color\":\"#FF8855\",\"text\":\"Compiled \"}},{{\"italic\":false,\"color\":\"dark_gray\",\"text\":\"» \"}},{{\"italic\":false,\"color\":\"#FFCC99\",\"text\":\"{1}\"}}],\"text\":\"\"}}\'}},PublicBukkitValues:{{\"hypercube:codetemplatedata\":\'{{\"author\":\"{0}\",\"name\":\"&x&f&f&8&8&5&5Compiled &8» &x&f&f&c&c&9&9{1}\",\"version\":1,\"code\":\"{2}\"}}\'}}}}}}"}}"##, 
-                self.owner.as_ref().map_or_else(|| "Kindling", |n| n),
-                l.name(),
-                l.compile()
-            );
-            println!("{}", message);
-            client.send_message(&websocket::Message::text(message)).expect("Something went wrong while sending message to recode");
-            let _ = client.recv_message().unwrap();
-            std::thread::sleep(time::Duration::from_millis(100));
-        }
+    pub fn compile_program(self, target_size: u64) -> Result<Vec<String>, Report> {
+        self.check_size(target_size)?;
+        Ok(self.lines.iter().map(|x| self.give_command(x)).collect())
+    }
+    pub fn compile_program_ws(self, target_size: u64) -> Result<(), Report> {
+        self.check_size(target_size)?;
+        send(&self, Transport::Recode, None).map_err(|e| Report::from(e.to_diagnostic()))?;
+        Ok(())
+    }
+    // Every line has to fit in the code space of a plot this size
+    pub fn check_size(&self, target_size: u64) -> Result<(), Report> {
+        let report = match measure(self, Transport::Recode) {
+            Ok(sizes) => check_limits(self, &sizes, &SizeLimits::for_plot(target_size)),
+            Err(e) => Report::from(compress_error(e))
+        };
+        if report.has_errors() { Err(report) } else { Ok(()) }
+    }
+    // NBT tag of the ender chest item that holds a line's template
+    pub fn template_tag(&self, line: &CodeLine) -> String {
+        let (accent, name) = (Color::Rgb(0xff, 0x88, 0x55), Color::Rgb(0xff, 0xcc, 0x99));
+        let title = LegacyText::new()
+            .color(accent).text(&format!("{} ", strip(self.title())))
+            .color(Color::DarkGray).text("» ")
+            .color(name).text(&line.name())
+            .build();
+        let display = format!(
+            r#"{{"extra":[{{"italic":false,"color":"{}","text":"{} "}},{{"italic":false,"color":"dark_gray","text":"» "}},{{"italic":false,"color":"{}","text":"{}"}}],"text":""}}"#,
+            accent.name(),
+            json_escape(&strip(self.title())),
+            name.name(),
+            json_escape(&line.name())
+        );
+        let data = format!(
+            r#"{{"author":"{}","name":"{}","version":1,"code":"{}"}}"#,
+            json_escape(self.owner()),
+            json_escape(&to_ampersand(&title)),
+            line.compile()
+        );
+        format!("{{display:{{Name:'{}'}},PublicBukkitValues:{{\"hypercube:codetemplatedata\":'{}'}}}}", snbt_escape(&display), snbt_escape(&data))
+    }
+    pub fn give_command(&self, line: &CodeLine) -> String {
+        format!("/give @p ender_chest{}", self.template_tag(line))
+    }
+    pub fn with_owner(mut self, owner: &str) -> Self {
+        self.owner = Some(String::from(owner));
+        self
+    }
+    pub fn owner(&self) -> &str {
+        self.owner.as_deref().unwrap_or("Kindling")
+    }
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(String::from(name));
+        self
+    }
+    // Leads the template item names
+    pub fn title(&self) -> &str {
+        self.name.as_deref().unwrap_or("Compiled")
     }
     pub fn new() -> Self {
-        Self { lines: vec![], owner: None }
+        Self { lines: vec![], owner: None, name: None }
     }
     pub fn new_from(lines: Vec<CodeLine>) -> Self {
-        Self { lines, owner: None }
+        Self { lines, owner: None, name: None }
+    }
+    pub fn lines(&self) -> &[CodeLine] {
+        &self.lines
     }
-}
\ No newline at end of file
+    pub fn lines_mut(&mut self) -> &mut Vec<CodeLine> {
+        &mut self.lines
+    }
+    pub fn into_lines(self) -> Vec<CodeLine> {
+        self.lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{params::ParamBuilder, statements::Statement, values::{Location, Selector, Tag, Text, Value, Variable, VariableScope}};
+
+    #[test]
+    fn test_empty() {
+        let p = Program::new_from(vec![]);
+        assert_eq!(p.compile_program(50).unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_simple() {
+        let p = Program::new_from(vec![
+            CodeLine::new_from(vec![ Statement::PlayerEvent(String::from("Join")) ])
+        ]);
+        for l in p.compile_program(50).unwrap() {
+            println!("{}", l);
+        }
+    }
+
+    #[test]
+    fn test_simple2() {
+        let p = Program::new_from(vec![
+            CodeLine::new_from(vec![ 
+                Statement::PlayerEvent(String::from("Join")),
+                Statement::PlayerAction { action: String::from("SendMessage"), parameters: [Some(Value::Text(LegacyText::new().color(Color::Green).text("%default joined!").into_text())), None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None], selector: Selector::AllPlayers },
+            ])
+        ]);
+        for l in p.compile_program(50).unwrap() {
+            println!("{}", l);
+        }
+    }
+
+    #[test]
+    fn test_recode() {
+        let p = Program::new_from(vec![
+            CodeLine::new_from(vec![ 
+                Statement::PlayerEvent(String::from("Join")),
+                Statement::PlayerAction { action: String::from("SendMessage"), parameters: [Some(Value::Text(LegacyText::new().color(Color::Green).text("%default joined!").into_text())), None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None], selector: Selector::AllPlayers },
+            ])
+        ]);
+        p.compile_program_ws(50).unwrap();
+    }
+
+    #[test]
+    fn test_recode2() {
+        let p = Program::new_from(vec![
+            CodeLine::new_from(vec![ 
+                Statement::PlayerEvent(String::from("Join")),
+                Statement::IfPlayer { action: String::from("HasPermission"), parameters: ParamBuilder::new().tag(Tag{name: String::from("Permission"), option: String::from("Developer"), var: None}).complete_unchecked(), selector: Selector::Default, not: false },
+                Statement::PlayerAction { action: String::from("SendMessage"), parameters: ParamBuilder::new().param(Value::Text(Text::legacy("&e[DEV] &a%default joined!"))).complete_unchecked(), selector: Selector::AllPlayers },
+                Statement::Close,
+                Statement::Else,
+                Statement::PlayerAction { action: String::from("SendMessage"), parameters: ParamBuilder::new().param(Value::Text(LegacyText::new().color(Color::Green).text("%default joined!").into_text())).complete_unchecked(), selector: Selector::AllPlayers },
+                Statement::Close
+            ])
+        ]);
+        p.compile_program_ws(25).unwrap();
+    }
+
+    #[test]
+    fn test_recode3() {
+        let p = Program::new_from(vec![
+            CodeLine::new_from(vec![ 
+                Statement::PlayerEvent(String::from("RightClick")),
+                Statement::IfPlayer { action: String::from("IsLookingAt"), parameters: ParamBuilder::new().tag(Tag{name: String::from("Fluid Mode"), option: String::from("Ignore fluids"), var: Some(Variable{name: String::from("a"), scope: VariableScope::Local})}).param(Value::Location(Location::new(25.0, 49.0, 27.0))).complete_unchecked(), selector: Selector::Default, not: false },
+                Statement::PlayerAction { action: String::from("SendMessage"), parameters: ParamBuilder::new().param(Value::Text(LegacyText::new().color(Color::Gold).text("Let's go!").into_text())).complete_unchecked(), selector: Selector::Default },
+                Statement::Close,
+            ])
+        ]);
+        p.compile_program_ws(25).unwrap();
+    }
+
+
+    #[test]
+    fn test_recode4() {
+        let p = Program::new_from(vec![
+            CodeLine::new_from(vec![ 
+                Statement::PlayerEvent(String::from("Join")),
+                Statement::IfPlayer { action: String::from("HasPermission"), parameters: ParamBuilder::new().tag(Tag{name: String::from("Permission"), option: String::from("Developer"), var: None}).complete_unchecked(), selector: Selector::Default, not: false },
+                Statement::PlayerAction { action: String::from("SendMessage"), parameters: ParamBuilder::new().param(Value::Text(Text::legacy("&e[DEV] &a%default joined!"))).complete_unchecked(), selector: Selector::AllPlayers },
+                Statement::Close,
+                Statement::Else,
+                Statement::PlayerAction { action: String::from("SendMessage"), parameters: ParamBuilder::new().param(Value::Text(LegacyText::new().color(Color::Green).text("%default joined!").into_text())).complete_unchecked(), selector: Selector::AllPlayers },
+                Statement::Close
+            ]), 
+            CodeLine::new_from(vec![ 
+                Statement::PlayerEvent(String::from("RightClick")),
+                Statement::IfPlayer { action: String::from("IsLookingAt"), parameters: ParamBuilder::new().tag(Tag{name: String::from("Fluid Mode"), option: String::from("Ignore fluids"), var: None}).param(Value::Location(Location::new(25.0, 49.0, 27.0))).complete_unchecked(), selector: Selector::Default, not: false },
+                Statement::PlayerAction { action: String::from("SendMessage"), parameters: ParamBuilder::new().param(Value::Text(LegacyText::new().color(Color::Gold).text("Let's go!").into_text())).complete_unchecked(), selector: Selector::Default },
+                Statement::Close,
+            ]), 
+        ]);
+        p.compile_program_ws(25).unwrap();
+    }
+
+    #[test]
+    fn test_template_tag_name() {
+        let p = Program::new_from(vec![ CodeLine::new_from(vec![ Statement::PlayerEvent(String::from("Join")) ]) ]);
+        assert!(p.template_tag(&p.lines()[0]).contains(r#""name":"&x&f&f&8&8&5&5Compiled &8» &x&f&f&c&c&9&9Player Event: Join""#));
+    }
+
+    #[test]
+    fn test_plot_name() {
+        // Color codes in the plot name don't reach the item's display name
+        let p = Program::new_from(vec![ CodeLine::new_from(vec![ Statement::PlayerEvent(String::from("Join")) ]) ]).with_name("&6Arena");
+        assert!(p.template_tag(&p.lines()[0]).contains(r#""text":"Arena ""#));
+        assert!(p.give_command(&p.lines()[0]).contains(r#""text":"Arena ""#));
+    }
+
+    #[test]
+    fn test_template_tag_escaping() {
+        // Reads the single-quoted SNBT string that follows the marker
+        let quoted = |tag: &str, marker: &str| {
+            let mut chars = tag[tag.find(marker).unwrap() + marker.len()..].chars();
+            let mut out = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => out.push(chars.next().unwrap()),
+                    '\'' => break,
+                    c => out.push(c)
+                }
+            }
+            out
+        };
+        let p = Program::new_from(vec![ CodeLine::new_from(vec![ Statement::Function { name: String::from("it's \"done\""), parameters: ParamBuilder::new().complete_unchecked() } ]) ])
+            .with_owner("O'Brien \"x\"")
+            .with_name("Bob's \\ arena");
+        let tag = p.template_tag(&p.lines()[0]);
+        let data: serde_json::Value = serde_json::from_str(&quoted(&tag, "codetemplatedata\":'")).unwrap();
+        assert_eq!(data["author"], "O'Brien \"x\"");
+        assert!(data["name"].as_str().unwrap().ends_with("Function: it's \"done\""));
+        let display: serde_json::Value = serde_json::from_str(&quoted(&tag, "Name:'")).unwrap();
+        assert_eq!(display["extra"][0]["text"], "Bob's \\ arena ");
+        assert_eq!(display["extra"][2]["text"], "Function: it's \"done\"");
+    }
+
+    #[test]
+    fn test_compile_checks_plot_size() {
+        let p = Program::new_from(vec![ CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            Statement::IfVariable { action: String::from("="), parameters: ParamBuilder::new().complete_unchecked(), not: false },
+            Statement::PlayerAction { action: String::from("SendMessage"), parameters: ParamBuilder::new().param(Value::Text(Text("x".repeat(200)))).complete_unchecked(), selector: Selector::Default },
+            Statement::Close,
+        ]) ]);
+        assert_eq!(p.compile_program(6).err().map(|r| r.diagnostics.len()), Some(1));
+    }
+}
@@ -0,0 +1,316 @@
+use std::{fmt::Display, fs, ops::Range, path::{Path, PathBuf}};
+
+use serde::Deserialize;
+
+use crate::{decompile::{parse_templates, DecompileError}, diagnostics::{Diagnostic, Report, SourceMap, Span}, export::data_version, linker::{LinkError, LinkWarning, Linker, Module}, location::{relocate, PlotOrigin}, program::Program, size::{Metric, SizeLimits}, transport::Transport};
+
+pub const MANIFEST_NAME: &str = "Kindling.toml";
+
+#[derive(Debug)]
+pub enum ProjectError {
+    Io(String),
+    // The manifest's text, and where in it the problem is when that is known
+    Manifest { message: String, text: String, span: Option<Range<usize>> },
+    Source { path: String, text: String, error: DecompileError },
+    Link(Vec<LinkError>)
+}
+impl ProjectError {
+    pub fn to_report(&self) -> Report {
+        let mut sources = SourceMap::new();
+        let diagnostics = match self {
+            ProjectError::Io(e) => vec![Diagnostic::error("E0701", e.clone())],
+            ProjectError::Manifest { message, text, span } => {
+                let source = sources.add(MANIFEST_NAME, text.clone());
+                let d = Diagnostic::error("E0702", format!("invalid {MANIFEST_NAME}: {message}"));
+                vec![match span {
+                    Some(range) => d.primary(Span { source, range: range.clone() }, "here"),
+                    None => d
+                }]
+            }
+            ProjectError::Source { path, text, error } => {
+                let source = sources.add(path, text.clone());
+                vec![error.to_diagnostic(&sources, source)]
+            }
+            ProjectError::Link(errors) => errors.iter().map(LinkError::to_diagnostic).collect(),
+        };
+        let mut report = Report::new(sources);
+        for d in diagnostics {
+            report.push(d);
+        }
+        report
+    }
+}
+impl Display for ProjectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProjectError::Io(e) => write!(f, "{e}"),
+            ProjectError::Manifest { message, .. } => write!(f, "invalid {MANIFEST_NAME}: {message}"),
+            ProjectError::Source { path, error, .. } => write!(f, "{path}: {error}"),
+            ProjectError::Link(errors) => write!(f, "{}", errors.iter().map(|e| e.to_string()).collect::<Vec<String>>().join("\n")),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestFile {
+    plot: PlotSection,
+    #[serde(default)]
+    build: BuildSection,
+    #[serde(default)]
+    transport: TransportSection,
+    #[serde(default)]
+    limits: LimitsSection
+}
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct PlotSection {
+    name: String,
+    author: Option<String>,
+    size: Option<PlotSize>,
+    minecraft_version: Option<String>,
+    origin: Option<[f64; 3]>
+}
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PlotSize {
+    Named(String),
+    Blocks(u64)
+}
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct BuildSection {
+    sources: Option<Vec<PathBuf>>,
+    keep: Option<Vec<String>>,
+    #[serde(rename = "handler-order")]
+    handler_order: Option<Vec<String>>
+}
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct LimitsSection {
+    blocks: Option<usize>,
+    length: Option<usize>,
+    json_bytes: Option<usize>,
+    compressed_bytes: Option<usize>,
+    code_length: Option<usize>,
+    give_length: Option<usize>,
+    message_length: Option<usize>
+}
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct TransportSection {
+    kind: Option<String>,
+    endpoint: Option<String>
+}
+
+pub struct Manifest {
+    pub name: String,
+    pub author: Option<String>,
+    pub plot_size: u64,
+    pub minecraft_version: Option<String>,
+    // When set, locations in the sources are relative to this corner of the plot
+    pub origin: Option<PlotOrigin>,
+    pub sources: Vec<PathBuf>,
+    // Functions and processes to place even when nothing calls them directly
+    pub keep: Vec<String>,
+    // Modules whose event handlers run first, in this order, when several modules
+    // handle the same event
+    pub handler_order: Vec<String>,
+    pub transport: Transport,
+    pub endpoint: Option<String>,
+    pub limits: SizeLimits
+}
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Self, ProjectError> {
+        let invalid = |message: String, span: Option<Range<usize>>| ProjectError::Manifest { message, text: String::from(text), span };
+        let file: ManifestFile = toml::from_str(text).map_err(|e| invalid(e.message().to_string(), e.span()))?;
+        let plot_size = match file.plot.size {
+            None => 50,
+            Some(PlotSize::Blocks(n)) => n,
+            Some(PlotSize::Named(n)) => match n.as_str() {
+                "basic" => 50,
+                "large" => 100,
+                "massive" => 300,
+                "mega" => 1000,
+                _ => return Err(invalid(format!("unknown plot size {n}, expected basic, large, massive, mega or a number"), None))
+            }
+        };
+        let transport = match file.transport.kind {
+            None => Transport::Recode,
+            Some(t) => Transport::parse(&t).ok_or_else(|| invalid(format!("unknown transport {t}, expected recode or codeclient"), None))?
+        };
+        if let Some(v) = &file.plot.minecraft_version {
+            if data_version(v).is_none() {
+                return Err(invalid(format!("unsupported minecraft version {v}"), None));
+            }
+        }
+        let l = &file.limits;
+        let limits = [
+            (Metric::Blocks, l.blocks),
+            (Metric::Length, l.length),
+            (Metric::JsonBytes, l.json_bytes),
+            (Metric::CompressedBytes, l.compressed_bytes),
+            (Metric::CodeLength, l.code_length),
+            (Metric::GiveLength, l.give_length),
+            (Metric::MessageLength, l.message_length),
+        ].into_iter().fold(SizeLimits::for_plot(plot_size), |limits, (metric, value)| match value {
+            Some(v) => limits.limit(metric, v),
+            None => limits
+        });
+        Ok(Self {
+            name: file.plot.name,
+            author: file.plot.author,
+            plot_size,
+            minecraft_version: file.plot.minecraft_version,
+            origin: file.plot.origin.map(|[x, y, z]| PlotOrigin::new(x, y, z)),
+            sources: file.build.sources.unwrap_or_else(|| vec![PathBuf::from("src")]),
+            keep: file.build.keep.unwrap_or_default(),
+            handler_order: file.build.handler_order.unwrap_or_default(),
+            transport,
+            endpoint: file.transport.endpoint,
+            limits
+        })
+    }
+}
+
+pub struct Project {
+    pub root: PathBuf,
+    pub manifest: Manifest
+}
+impl Project {
+    // Looks for a manifest in the directory and its parents
+    pub fn find(start: &Path) -> Option<PathBuf> {
+        start.ancestors().map(|d| d.join(MANIFEST_NAME)).find(|p| p.is_file())
+    }
+    pub fn load(manifest_path: &Path) -> Result<Self, ProjectError> {
+        let text = fs::read_to_string(manifest_path)
+            .map_err(|e| ProjectError::Io(format!("could not read {}: {e}", manifest_path.display())))?;
+        Ok(Self {
+            root: manifest_path.parent().map_or_else(PathBuf::new, Path::to_path_buf),
+            manifest: Manifest::parse(&text)?
+        })
+    }
+    // Every template file under the source roots, named by its path relative to
+    // the root with the extension dropped and separators replaced by dots
+    pub fn modules(&self) -> Result<Vec<Module>, ProjectError> {
+        let mut modules = vec![];
+        for source_root in &self.manifest.sources {
+            let dir = self.root.join(source_root);
+            let mut files = vec![];
+            collect_sources(&dir, &mut files)?;
+            files.sort();
+            for file in files {
+                let text = fs::read_to_string(&file)
+                    .map_err(|e| ProjectError::Io(format!("could not read {}: {e}", file.display())))?;
+                let name = file.strip_prefix(&dir).unwrap_or(&file)
+                    .with_extension("")
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .collect::<Vec<String>>()
+                    .join(".");
+                let lines = parse_templates(&text).map_err(|error| ProjectError::Source { path: file.display().to_string(), text: text.clone(), error })?;
+                modules.push(Module { name, lines });
+            }
+        }
+        Ok(modules)
+    }
+    pub fn program(&self) -> Result<(Program, Vec<LinkWarning>), ProjectError> {
+        let order = &self.manifest.handler_order;
+        let linker = order.iter().enumerate().fold(Linker::new(), |l, (i, module)| l.priority(module, i as i32 - order.len() as i32));
+        let linker = self.modules()?.into_iter().fold(linker, Linker::module);
+        let (program, warnings) = linker.link_with_warnings().map_err(ProjectError::Link)?;
+        Ok((self.apply(program), warnings))
+    }
+    // Names, moves and credits a linked program the way the manifest says, whether
+    // it came from the source roots or from files given on the command line
+    pub fn apply(&self, program: Program) -> Program {
+        let mut program = program.with_name(&self.manifest.name);
+        if let Some(origin) = &self.manifest.origin {
+            relocate(&mut program, &PlotOrigin::default(), origin);
+        }
+        if let Some(author) = &self.manifest.author {
+            program = program.with_owner(author);
+        }
+        program
+    }
+}
+
+fn collect_sources(dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), ProjectError> {
+    let entries = fs::read_dir(dir).map_err(|e| ProjectError::Io(format!("could not read {}: {e}", dir.display())))?;
+    for entry in entries {
+        let path = entry.map_err(|e| ProjectError::Io(e.to_string()))?.path();
+        if path.is_dir() {
+            collect_sources(&path, out)?;
+        } else if path.extension().is_some_and(|e| e == "json" || e == "template") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code_line::CodeLine, params::ParamBuilder, serialization::DFSerializable, statements::Statement, values::{Location, Selector, Value}};
+
+    #[test]
+    fn test_manifest() {
+        let m = Manifest::parse(r#"
+            [plot]
+            name = "Parkour"
+            author = "ashli"
+            size = "large"
+            minecraft-version = "1.20.4"
+
+            [build]
+            sources = ["code", "lib"]
+            keep = ["lib.api.*"]
+            handler-order = ["lib.core"]
+
+            [transport]
+            kind = "codeclient"
+        "#).unwrap();
+        assert_eq!(m.author.as_deref(), Some("ashli"));
+        assert_eq!(m.plot_size, 100);
+        assert_eq!(m.sources, vec![PathBuf::from("code"), PathBuf::from("lib")]);
+        assert_eq!(m.keep, vec![String::from("lib.api.*")]);
+        assert_eq!(m.handler_order, vec![String::from("lib.core")]);
+        assert_eq!(m.transport, Transport::CodeClient);
+        assert_eq!(m.limits, SizeLimits::for_plot(100));
+    }
+
+    #[test]
+    fn test_manifest_limits() {
+        let m = Manifest::parse("[plot]\nname = \"x\"\n[limits]\nlength = 40\ncode-length = 5000").unwrap();
+        assert_eq!(m.limits, SizeLimits { limits: vec![(Metric::Length, 40), (Metric::CodeLength, 5000)] });
+    }
+
+    #[test]
+    fn test_manifest_unknown_values() {
+        assert!(Manifest::parse("[plot]\nname = \"x\"\nsize = \"huge\"").is_err());
+        assert!(Manifest::parse("[plot]\nname = \"x\"\nminecraft-version = \"0.1\"").is_err());
+    }
+
+    #[test]
+    fn test_manifest_error_span() {
+        let report = Manifest::parse("[plot]\nname = 5").err().unwrap().to_report();
+        assert_eq!(report.diagnostics[0].code, "E0702");
+        assert_eq!(report.diagnostics[0].labels[0].span.range, 14..15);
+    }
+
+    #[test]
+    fn test_manifest_origin() {
+        assert_eq!(Manifest::parse("[plot]\nname = \"x\"\norigin = [1000, 0, -2000]").unwrap().origin, Some(PlotOrigin::new(1000.0, 0.0, -2000.0)));
+    }
+
+    #[test]
+    fn test_apply_relocates() {
+        // Files given on the command line are placed by the manifest like its own sources
+        let project = Project { root: PathBuf::new(), manifest: Manifest::parse("[plot]\nname = \"x\"\norigin = [1000, 0, -2000]").unwrap() };
+        let p = project.apply(Program::new_from(vec![ CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            Statement::PlayerAction { action: String::from("Teleport"), parameters: ParamBuilder::new().param(Value::Location(Location::block(5, 50, 5))).complete_unchecked(), selector: Selector::Default },
+        ]) ]));
+        assert!(p.lines()[0].serialize().contains(r#""loc":{"x":1005,"y":50,"z":-1995"#));
+    }
+}
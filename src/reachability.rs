@@ -0,0 +1,130 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{code_line::CodeLine, linker::DefinitionKind, program::Program, statements::Statement};
+
+// A kept name is either exact or a prefix ending in `*`, like `lib.api.*`
+pub fn is_kept(name: &str, keep: &[String]) -> bool {
+    keep.iter().any(|k| match k.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => k == name
+    })
+}
+
+fn definition(line: &CodeLine) -> Option<(DefinitionKind, &str)> {
+    match line.body().first() {
+        Some(Statement::Function { name, .. }) => Some((DefinitionKind::Function, name)),
+        Some(Statement::Process { name, .. }) => Some((DefinitionKind::Process, name)),
+        _ => None
+    }
+}
+
+fn callees(line: &CodeLine) -> impl Iterator<Item = (DefinitionKind, &str)> {
+    line.body().iter().filter_map(|s| match s {
+        Statement::CallFunction { name, .. } => Some((DefinitionKind::Function, name.as_str())),
+        Statement::CallProcess { name, .. } => Some((DefinitionKind::Process, name.as_str())),
+        _ => None
+    })
+}
+
+// The functions and processes reachable from an event line or a kept
+// definition. Calls through a text code can't be followed; whatever they might
+// reach has to be kept by name.
+pub fn reachable(program: &Program, keep: &[String]) -> HashSet<(DefinitionKind, String)> {
+    let definitions: HashMap<(DefinitionKind, &str), &CodeLine> = program.lines().iter()
+        .filter_map(|l| definition(l).map(|def| (def, l)))
+        .collect();
+    let mut seen = HashSet::new();
+    let mut stack: Vec<(DefinitionKind, &str)> = vec![];
+    for line in program.lines() {
+        match definition(line) {
+            Some(def) if is_kept(def.1, keep) => stack.push(def),
+            Some(_) => {}
+            None => stack.extend(callees(line))
+        }
+    }
+    while let Some((kind, name)) = stack.pop() {
+        if !seen.insert((kind, String::from(name))) {
+            continue;
+        }
+        if let Some(line) = definitions.get(&(kind, name)) {
+            stack.extend(callees(line));
+        }
+    }
+    seen
+}
+
+// Drops function and process lines nothing can reach, returning their names
+pub fn eliminate_dead_code(program: &mut Program, keep: &[String]) -> Vec<String> {
+    let reachable = reachable(program, keep);
+    let mut removed = vec![];
+    program.lines_mut().retain(|l| match definition(l) {
+        Some((kind, name)) if !reachable.contains(&(kind, String::from(name))) => {
+            removed.push(String::from(name));
+            false
+        }
+        _ => true
+    });
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::ParamBuilder;
+
+    fn call(name: &str) -> Statement {
+        Statement::CallFunction { name: String::from(name), parameters: ParamBuilder::new().complete_unchecked() }
+    }
+    fn func(name: &str, calls: &[&str]) -> CodeLine {
+        let mut body = vec![ Statement::Function { name: String::from(name), parameters: ParamBuilder::new().complete_unchecked() } ];
+        body.extend(calls.iter().map(|c| call(c)));
+        CodeLine::new_from(body)
+    }
+    fn process(name: &str, calls: &[&str]) -> CodeLine {
+        let mut body = vec![ Statement::Process { name: String::from(name), parameters: ParamBuilder::new().complete_unchecked() } ];
+        body.extend(calls.iter().map(|c| call(c)));
+        CodeLine::new_from(body)
+    }
+    fn start(name: &str) -> Statement {
+        Statement::CallProcess { name: String::from(name), parameters: ParamBuilder::new().complete_unchecked() }
+    }
+
+    #[test]
+    fn test_dead_code() {
+        let mut p = Program::new_from(vec![
+            CodeLine::new_from(vec![ Statement::PlayerEvent(String::from("Join")), start("tick") ]),
+            process("tick", &["a"]),
+            func("a", &["b"]),
+            func("b", &["a"]),
+            func("api.hook", &["c"]),
+            func("c", &[]),
+            func("unused", &["c"]),
+            func("unused.too", &["unused"]),
+        ]);
+        let removed = eliminate_dead_code(&mut p, &[String::from("api.*")]);
+        assert_eq!(removed, vec![String::from("unused"), String::from("unused.too")]);
+        assert_eq!(p.lines().len(), 6);
+    }
+
+    #[test]
+    fn test_keep_patterns() {
+        assert!(is_kept("api.hook", &[String::from("api.*")]));
+        assert!(!is_kept("api", &[String::from("api.hook")]));
+    }
+
+    #[test]
+    fn test_function_and_process_share_name() {
+        // A function and a process sharing a name are separate definitions
+        let mut p = Program::new_from(vec![
+            CodeLine::new_from(vec![ Statement::PlayerEvent(String::from("Join")), call("spawn"), start("spawn") ]),
+            func("spawn", &["from_function"]),
+            process("spawn", &["from_process"]),
+            func("from_function", &[]),
+            func("from_process", &[]),
+            process("from_function", &[]),
+        ]);
+        assert_eq!(eliminate_dead_code(&mut p, &[]), vec![String::from("from_function")]);
+        assert_eq!(p.lines().len(), 5);
+        assert!(matches!(p.lines()[4].body().first(), Some(Statement::Function { name, .. }) if name == "from_process"));
+    }
+}
@@ -0,0 +1,278 @@
+use std::{fmt::Display, io::Write};
+
+use flate2::{write::GzEncoder, Compression};
+
+use crate::{code_line::CodeLine, diagnostics::{Diagnostic, Report, SourceMap}, program::Program, serialization::{json_escape, DFSerializable}, statements::Statement, transport::{message, Transport}, validate::LineListing};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Metric {
+    Blocks, Length, JsonBytes, CompressedBytes, CodeLength, GiveLength, MessageLength
+}
+impl Metric {
+    pub const ALL: [Metric; 7] = [Metric::Blocks, Metric::Length, Metric::JsonBytes, Metric::CompressedBytes, Metric::CodeLength, Metric::GiveLength, Metric::MessageLength];
+    pub fn parse(s: &str) -> Option<Self> {
+        Metric::ALL.into_iter().find(|m| m.to_string() == s)
+    }
+}
+impl Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Metric::Blocks => "blocks",
+            Metric::Length => "length",
+            Metric::JsonBytes => "json-bytes",
+            Metric::CompressedBytes => "compressed-bytes",
+            Metric::CodeLength => "code-length",
+            Metric::GiveLength => "give-length",
+            Metric::MessageLength => "message-length",
+        })
+    }
+}
+
+// Code blocks take up their own space and the one after it (for the stone or
+// piston); closing brackets take one
+fn spaces(stmnt: &Statement) -> usize {
+    if matches!(stmnt, Statement::Close | Statement::CloseRepeat) { 1 } else { 2 }
+}
+pub fn physical_length(line: &CodeLine) -> usize {
+    line.body().iter().map(spaces).sum()
+}
+
+fn gzip(json: &str) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(json.as_bytes())?;
+    encoder.finish()
+}
+
+pub fn compress_error(e: std::io::Error) -> Diagnostic {
+    Diagnostic::error("E0503", format!("could not compress a template to measure it: {e}"))
+}
+
+// How an amount of a metric reads, for whole lines and single blocks alike
+fn amount(metric: Metric, n: usize) -> String {
+    match metric {
+        Metric::Blocks if n == 1 => String::from("1 block"),
+        Metric::Blocks => format!("{n} blocks"),
+        Metric::Length => format!("{n} spaces long"),
+        Metric::JsonBytes => format!("{n} bytes of template JSON"),
+        Metric::CompressedBytes => format!("{n} bytes gzipped"),
+        Metric::CodeLength => format!("{n} characters of template code"),
+        Metric::GiveLength => format!("{n} characters as a give command"),
+        Metric::MessageLength => format!("{n} characters as a message to the mod"),
+    }
+}
+
+// How much one block adds to a metric, and how to describe it. Give and message
+// lengths grow with the template code, so blocks are ranked by their code for those.
+fn contribution(stmnt: &Statement, metric: Metric) -> std::io::Result<(usize, String)> {
+    let (metric, n) = match metric {
+        Metric::Blocks => (metric, 1),
+        Metric::Length => (metric, spaces(stmnt)),
+        Metric::JsonBytes => (metric, stmnt.serialize().len()),
+        Metric::CompressedBytes => (metric, gzip(&stmnt.serialize())?.len()),
+        Metric::CodeLength | Metric::GiveLength | Metric::MessageLength =>
+            (Metric::CodeLength, base64::encode(gzip(&stmnt.serialize())?).len()),
+    };
+    Ok((n, amount(metric, n)))
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LineSize {
+    pub name: String,
+    pub blocks: usize,
+    pub length: usize,
+    pub json_bytes: usize,
+    pub compressed_bytes: usize,
+    pub code_length: usize,
+    pub give_length: usize,
+    pub message_length: usize
+}
+impl LineSize {
+    pub fn measure(program: &Program, line: &CodeLine, transport: Transport) -> std::io::Result<Self> {
+        let json = line.serialize();
+        let compressed = gzip(&json)?;
+        Ok(Self {
+            name: line.name(),
+            blocks: line.body().len(),
+            length: physical_length(line),
+            json_bytes: json.len(),
+            compressed_bytes: compressed.len(),
+            code_length: base64::encode(compressed).len(),
+            give_length: program.give_command(line).len(),
+            message_length: message(program, line, transport).len()
+        })
+    }
+    pub fn get(&self, metric: Metric) -> usize {
+        match metric {
+            Metric::Blocks => self.blocks,
+            Metric::Length => self.length,
+            Metric::JsonBytes => self.json_bytes,
+            Metric::CompressedBytes => self.compressed_bytes,
+            Metric::CodeLength => self.code_length,
+            Metric::GiveLength => self.give_length,
+            Metric::MessageLength => self.message_length,
+        }
+    }
+}
+
+pub fn measure(program: &Program, transport: Transport) -> std::io::Result<Vec<LineSize>> {
+    program.lines().iter().map(|l| LineSize::measure(program, l, transport)).collect()
+}
+
+pub fn render_table(sizes: &[LineSize]) -> String {
+    let mut out = format!("{:<40} {:>6} {:>6} {:>8} {:>8} {:>8} {:>8} {:>8}\n", "line", "blocks", "length", "json", "gzip", "code", "give", "message");
+    for s in sizes {
+        out += &format!(
+            "{:<40} {:>6} {:>6} {:>8} {:>8} {:>8} {:>8} {:>8}\n",
+            s.name, s.blocks, s.length, s.json_bytes, s.compressed_bytes, s.code_length, s.give_length, s.message_length
+        );
+    }
+    out
+}
+
+pub fn to_json(sizes: &[LineSize]) -> String {
+    format!("[{}]", sizes.iter().map(|s| format!(
+        r#"{{"name":"{}","blocks":{},"length":{},"json_bytes":{},"compressed_bytes":{},"code_length":{},"give_length":{},"message_length":{}}}"#,
+        json_escape(&s.name), s.blocks, s.length, s.json_bytes, s.compressed_bytes, s.code_length, s.give_length, s.message_length
+    )).collect::<Vec<String>>().join(","))
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SizeLimits {
+    pub limits: Vec<(Metric, usize)>
+}
+impl SizeLimits {
+    // Lines can't be longer than the plot's code space
+    pub fn for_plot(plot_size: u64) -> Self {
+        Self { limits: vec![(Metric::Length, plot_size as usize)] }
+    }
+    pub fn limit(mut self, metric: Metric, value: usize) -> Self {
+        self.limits.retain(|(m, _)| *m != metric);
+        self.limits.push((metric, value));
+        self
+    }
+    pub fn get(&self, metric: Metric) -> Option<usize> {
+        self.limits.iter().find(|(m, _)| *m == metric).map(|(_, v)| *v)
+    }
+}
+
+// One error per line and limit it goes over, pointing at the blocks with the
+// biggest share of that metric
+pub fn check_limits(program: &Program, sizes: &[LineSize], limits: &SizeLimits) -> Report {
+    let mut sources = SourceMap::new();
+    let listings: Vec<LineListing> = program.lines().iter()
+        .enumerate()
+        .map(|(i, l)| LineListing::new(&mut sources, i, l))
+        .collect();
+    let mut report = Report::new(sources);
+    for (i, (line, size)) in program.lines().iter().zip(sizes).enumerate() {
+        for &(metric, limit) in &limits.limits {
+            let value = size.get(metric);
+            if value <= limit {
+                continue;
+            }
+            let contributors: std::io::Result<Vec<(usize, (usize, String))>> = line.body().iter().enumerate()
+                .map(|(si, s)| contribution(s, metric).map(|c| (si, c)))
+                .collect();
+            let mut contributors = match contributors {
+                Ok(c) => c,
+                Err(e) => {
+                    report.push(compress_error(e));
+                    continue;
+                }
+            };
+            contributors.sort_by(|a, b| b.1.0.cmp(&a.1.0).then(a.0.cmp(&b.0)));
+            let verb = if metric == Metric::Blocks { "has" } else { "is" };
+            let mut diagnostic = Diagnostic::error("E0201", format!("{} {verb} {}, over the limit of {limit}", size.name, amount(metric, value)));
+            for (n, (si, (_, label))) in contributors.into_iter().take(3).enumerate() {
+                diagnostic = if n == 0 {
+                    diagnostic.primary(listings[i].span(si), &label)
+                } else {
+                    diagnostic.secondary(listings[i].span(si), &label)
+                };
+            }
+            report.push(diagnostic.note(String::from("split the line into functions, or move large values into variables")));
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile::DFCompile, params::ParamBuilder, values::{Selector, Text, Value}};
+
+    // One line, seven spaces long, with a 200 character message in it
+    fn program() -> Program {
+        let big = Statement::PlayerAction {
+            action: String::from("SendMessage"),
+            parameters: ParamBuilder::new().param(Value::Text(Text("x".repeat(200)))).complete_unchecked(),
+            selector: Selector::Default
+        };
+        Program::new_from(vec![ CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            Statement::IfVariable { action: String::from("="), parameters: ParamBuilder::new().complete_unchecked(), not: false },
+            big,
+            Statement::Close,
+        ]) ])
+    }
+    // The first label of a diagnostic: where it points and what it says
+    fn top(d: &Diagnostic) -> (std::ops::Range<usize>, String) {
+        (d.labels[0].span.range.clone(), d.labels[0].message.clone())
+    }
+
+    #[test]
+    fn test_measure() {
+        let p = program();
+        let sizes = measure(&p, Transport::Recode).unwrap();
+        let size = &sizes[0];
+        assert_eq!((size.blocks, size.length), (4, 7));
+        assert_eq!(size.json_bytes, p.lines()[0].serialize().len());
+        assert_eq!(size.code_length, p.lines()[0].compile().len());
+        assert!(size.compressed_bytes < size.json_bytes);
+        assert_eq!(size.give_length, p.give_command(&p.lines()[0]).len());
+        assert!(size.message_length > size.give_length);
+    }
+
+    #[test]
+    fn test_within_limits() {
+        let p = program();
+        let sizes = measure(&p, Transport::Recode).unwrap();
+        assert!(!check_limits(&p, &sizes, &SizeLimits::for_plot(50)).has_errors());
+    }
+
+    #[test]
+    fn test_over_limits() {
+        let p = program();
+        let sizes = measure(&p, Transport::Recode).unwrap();
+        let report = check_limits(&p, &sizes, &SizeLimits::for_plot(6).limit(Metric::JsonBytes, 100));
+        assert_eq!(report.diagnostics.len(), 2);
+        let rendered = report.render(false);
+        assert!(rendered.contains("error[E0201]: Player Event: Join is 7 spaces long, over the limit of 6"));
+        assert!(rendered.contains(&format!("error[E0201]: Player Event: Join is {} bytes of template JSON, over the limit of 100", sizes[0].json_bytes)));
+        assert!(rendered.contains("Player Action: SendMessage"));
+    }
+
+    #[test]
+    fn test_ranked_by_metric() {
+        // Blocks are ranked by the metric that went over, not always by JSON size
+        let p = program();
+        let sizes = measure(&p, Transport::Recode).unwrap();
+        let report = check_limits(&p, &sizes, &SizeLimits::for_plot(6).limit(Metric::JsonBytes, 100));
+        let join = p.lines()[0].body()[0].name().len();
+        assert_eq!(top(&report.diagnostics[0]), (0..join, String::from("2 spaces long")));
+        assert!(report.diagnostics[0].labels.iter().all(|l| l.message == "2 spaces long"));
+        assert_eq!(top(&report.diagnostics[1]).1, "349 bytes of template JSON");
+    }
+
+    #[test]
+    fn test_limit_wording() {
+        let p = program();
+        let sizes = measure(&p, Transport::Recode).unwrap();
+        let report = check_limits(&p, &sizes, &SizeLimits::default().limit(Metric::Blocks, 3).limit(Metric::CodeLength, 10));
+        let (join, send) = (p.lines()[0].body()[0].name().len(), p.lines()[0].body()[2].name().len());
+        assert_eq!(report.diagnostics[0].message, "Player Event: Join has 4 blocks, over the limit of 3");
+        assert_eq!(top(&report.diagnostics[0]), (0..join, String::from("1 block")));
+        let (range, message) = top(&report.diagnostics[1]);
+        assert!(range.len() == send && message.ends_with("characters of template code"));
+    }
+}
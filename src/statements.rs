@@ -1,4 +1,4 @@
-use crate::{serialization::{DFSerializable, DFSerializableStatementContext}, values::{Selector, ParameterList}};
+use crate::{serialization::DFSerializable, template, values::{Selector, ParameterList}};
 
 pub enum Statement {
     PlayerEvent(PlayerEventType),
@@ -23,95 +23,10 @@ pub enum Statement {
 
 impl DFSerializable for Statement {
     fn serialize(&self) -> String {
-        match self {
-            Statement::PlayerAction { action, parameters, selector } |
-            Statement::EntityAction { action, parameters, selector } => {
-                format!(
-                    r#"{{"id":"block","block":"{}","args":{{"items":[{}]}},"action":"{}","target":"{}"}}"#,
-                    self.technical_name(),
-                    parameters.serialize_params(&self),
-                    action,
-                    selector.serialize()
-                )
-            }
-            Statement::SetVariable { action, parameters } |
-            Statement::GameAction { action, parameters } |
-            Statement::Control { action, parameters } => {
-                format!(
-                    r#"{{"id":"block","block":"{}","args":{{"items":[{}]}},"action":"{}"}}"#,
-                    self.technical_name(),
-                    parameters.serialize_params(&self),
-                    action
-                )
-            }
-            Statement::CallFunction { name, parameters } |
-            Statement::CallProcess { name, parameters } => {
-                format!(
-                    r#"{{"id":"block","block":"{}","args":{{"items":[{}]}},"data":"{}"}}"#,
-                    self.technical_name(),
-                    parameters.serialize_params(&self),
-                    name
-                )
-            }
-            Statement::SelectObject { action, subaction, parameters, not } => {
-                format!(
-                    r#"{{"id":"block","block":"select_obj","args":{{"items":[{}]}},"action":"{}","subAction":"{}","inverted":"{}"}}"#,
-                    parameters.serialize_params(&self),
-                    action,
-                    subaction.as_ref().map_or_else(|| "", |a| &a),
-                    if *not { "NOT" } else { "" }
-                )
-            }
-            Statement::IfVariable { action, parameters, not } |
-            Statement::IfGame { action, parameters, not } => {
-                format!(
-                    r#"{{"id":"block","block":"{}","args":{{"items":[{}]}},"action":"{}","inverted":"{}"}},{{"id":"bracket","direct":"open","type":"norm"}}"#,
-                    self.technical_name(),
-                    parameters.serialize_params(&self),
-                    action,
-                    if *not { "NOT" } else { "" }
-                )
-            }
-            Statement::IfPlayer { action, parameters, selector, not } |
-            Statement::IfEntity { action, parameters, selector, not } => {
-                format!(
-                    r#"{{"id":"block","block":"{}","args":{{"items":[{}]}},"action":"{}","inverted":"{}","target":"{}"}},{{"id":"bracket","direct":"open","type":"norm"}}"#,
-                    self.technical_name(),
-                    parameters.serialize_params(&self),
-                    action,
-                    if *not { "NOT" } else { "" },
-                    selector.serialize()
-                )
-            }
-            Statement::Else => String::from(r#"{"id":"block","block":"else"},{"id":"bracket","direct":"open","type":"norm"}"#),
-            Statement::Close => String::from(r#"{"id":"bracket","direct":"close","type":"norm"}"#),
-            Statement::CloseRepeat => String::from(r#"{"id":"bracket","direct":"close","type":"repeat"}"#),
-            Statement::Repeat { action, subaction, parameters, not } => {
-                format!(
-                    r#"{{"id":"block","block":"repeat","args":{{"items":[{}]}},"action":"{}",subAction:"{}","inverted":"{}"}},{{"id":"bracket","direct":"open","type":"repeat"}}"#,
-                    parameters.serialize_params(&self),
-                    action,
-                    subaction.as_ref().map_or_else(|| "", |a| &a),
-                    if *not { "NOT" } else { "" }
-                )
-            }
-            Statement::PlayerEvent(e) |
-            Statement::EntityEvent(e) => { 
-                format!(
-                    r#"{{"id":"block","block":"{}","args":{{"items":[]}},"action":"{}"}}"#,
-                    self.technical_name(),
-                    e
-                )
-            }
-            Statement::Function {name, parameters } |
-            Statement::Process {name, parameters } => {
-                format!(
-                    r#"{{"id":"block","block":"{}","args":{{"items":[{}]}},"data":"{name}"}}"#,
-                    self.technical_name(),
-                    parameters.serialize_params(&self),
-                )
-            }
-        }
+        let blocks: Vec<String> = template::blocks(self).iter()
+            .map(|b| serde_json::to_string(b).unwrap())
+            .collect();
+        blocks.join(",")
     }
 }
 impl Statement {
@@ -138,6 +53,64 @@ impl Statement {
             Statement::Process { .. } => "process",
         }
     }
+    pub fn action(&self) -> Option<&str> {
+        match self {
+            Statement::PlayerAction { action, .. } |
+            Statement::EntityAction { action, .. } |
+            Statement::SetVariable { action, .. } |
+            Statement::GameAction { action, .. } |
+            Statement::Control { action, .. } |
+            Statement::SelectObject { action, .. } |
+            Statement::IfVariable { action, .. } |
+            Statement::IfPlayer { action, .. } |
+            Statement::IfEntity { action, .. } |
+            Statement::IfGame { action, .. } |
+            Statement::Repeat { action, .. } => Some(action),
+            _ => None
+        }
+    }
+    pub fn parameters(&self) -> Option<&ParameterList> {
+        match self {
+            Statement::Function { parameters, .. } |
+            Statement::Process { parameters, .. } |
+            Statement::PlayerAction { parameters, .. } |
+            Statement::EntityAction { parameters, .. } |
+            Statement::SetVariable { parameters, .. } |
+            Statement::GameAction { parameters, .. } |
+            Statement::Control { parameters, .. } |
+            Statement::SelectObject { parameters, .. } |
+            Statement::IfVariable { parameters, .. } |
+            Statement::IfPlayer { parameters, .. } |
+            Statement::IfEntity { parameters, .. } |
+            Statement::IfGame { parameters, .. } |
+            Statement::Repeat { parameters, .. } |
+            Statement::CallFunction { parameters, .. } |
+            Statement::CallProcess { parameters, .. } => Some(parameters),
+            Statement::PlayerEvent(_) | Statement::EntityEvent(_) |
+            Statement::Else | Statement::Close | Statement::CloseRepeat => None
+        }
+    }
+    pub fn parameters_mut(&mut self) -> Option<&mut ParameterList> {
+        match self {
+            Statement::Function { parameters, .. } |
+            Statement::Process { parameters, .. } |
+            Statement::PlayerAction { parameters, .. } |
+            Statement::EntityAction { parameters, .. } |
+            Statement::SetVariable { parameters, .. } |
+            Statement::GameAction { parameters, .. } |
+            Statement::Control { parameters, .. } |
+            Statement::SelectObject { parameters, .. } |
+            Statement::IfVariable { parameters, .. } |
+            Statement::IfPlayer { parameters, .. } |
+            Statement::IfEntity { parameters, .. } |
+            Statement::IfGame { parameters, .. } |
+            Statement::Repeat { parameters, .. } |
+            Statement::CallFunction { parameters, .. } |
+            Statement::CallProcess { parameters, .. } => Some(parameters),
+            Statement::PlayerEvent(_) | Statement::EntityEvent(_) |
+            Statement::Else | Statement::Close | Statement::CloseRepeat => None
+        }
+    }
     pub fn name(&self) -> String{
         match self {
             Statement::PlayerAction { action,.. } => format!("Player Action: {}", action),
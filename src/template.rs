@@ -0,0 +1,741 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{code_line::CodeLine, decimal::Decimal, functions::FunctionSignature, visit::{walk_variable_mut, VisitorMut}, textcode, decompile::DecompileError, serialization::DFSerializable, statements::Statement, values::{Attribute, AttributeOperation, Component, FunctionParam, GameValue, Item as ItemValue, Location, Number, ParameterList, Particle, Potion, PotionEffect, Selector, Sound, Tag, Text, Value, ValueType, Variable, VariableScope, Vector}};
+
+// The template JSON DiamondFire stores in a code template item
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Template {
+    pub blocks: Vec<Block>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "id")]
+pub enum Block {
+    #[serde(rename = "block")]
+    Code(CodeBlock),
+    #[serde(rename = "bracket")]
+    Bracket(Bracket)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CodeBlock {
+    pub block: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<Args>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    // Function and process names
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(default, rename = "subAction", skip_serializing_if = "Option::is_none")]
+    pub sub_action: Option<String>,
+    // "NOT" or empty
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inverted: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Bracket {
+    pub direct: Direction,
+    #[serde(rename = "type")]
+    pub kind: BracketKind
+}
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Open, Close
+}
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BracketKind {
+    Norm, Repeat
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Args {
+    pub items: Vec<ArgItem>
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ArgItem {
+    pub item: Item,
+    pub slot: u8
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "id", content = "data")]
+pub enum Item {
+    #[serde(rename = "txt")]
+    Text(NameData),
+    // Numbers are stored as text so they can hold %math and other codes
+    #[serde(rename = "num")]
+    Number(NameData),
+    // Styled text in MiniMessage format
+    #[serde(rename = "comp")]
+    Component(NameData),
+    #[serde(rename = "loc")]
+    Location(LocationData),
+    #[serde(rename = "vec")]
+    Vector(VectorData),
+    #[serde(rename = "snd")]
+    Sound(SoundData),
+    #[serde(rename = "part")]
+    Particle(ParticleData),
+    #[serde(rename = "pot")]
+    Potion(PotionData),
+    #[serde(rename = "var")]
+    Variable(VariableData),
+    #[serde(rename = "g_val")]
+    GameValue(GameValueData),
+    #[serde(rename = "bl_tag")]
+    Tag(TagData),
+    #[serde(rename = "pn_el")]
+    Parameter(ParameterData),
+    // A Minecraft item as SNBT
+    #[serde(rename = "item")]
+    Minecraft(ItemData),
+    // Shown by the code editor only, like the function icon in slot 25
+    #[serde(rename = "hint")]
+    Hint(HintData)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NameData {
+    pub name: String
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LocationData {
+    #[serde(rename = "isBlock")]
+    pub is_block: bool,
+    pub loc: Loc
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Loc {
+    #[serde(serialize_with = "number")]
+    pub x: f64,
+    #[serde(serialize_with = "number")]
+    pub y: f64,
+    #[serde(serialize_with = "number")]
+    pub z: f64,
+    #[serde(default, serialize_with = "number")]
+    pub pitch: f64,
+    #[serde(default, serialize_with = "number")]
+    pub yaw: f64
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct VectorData {
+    #[serde(serialize_with = "number")]
+    pub x: f64,
+    #[serde(serialize_with = "number")]
+    pub y: f64,
+    #[serde(serialize_with = "number")]
+    pub z: f64
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SoundData {
+    pub sound: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    #[serde(serialize_with = "number")]
+    pub pitch: f64,
+    #[serde(serialize_with = "number")]
+    pub vol: f64
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ParticleData {
+    pub particle: String,
+    pub cluster: Cluster,
+    pub data: ParticleFields
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Cluster {
+    pub amount: u64,
+    #[serde(serialize_with = "number")]
+    pub horizontal: f64,
+    #[serde(serialize_with = "number")]
+    pub vertical: f64
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ParticleFields {
+    #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "opt_number")]
+    pub x: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "opt_number")]
+    pub y: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "opt_number")]
+    pub z: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "opt_number")]
+    pub motion_variation: Option<f64>,
+    // 0xRRGGBB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rgb: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "opt_number")]
+    pub color_variation: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "opt_number")]
+    pub size: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "opt_number")]
+    pub size_variation: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "opt_number")]
+    pub roll: Option<f64>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PotionData {
+    pub pot: String,
+    pub dur: u64,
+    pub amp: i16
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct VariableData {
+    pub name: String,
+    pub scope: Scope
+}
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Scope {
+    #[serde(rename = "local")]
+    Local,
+    #[serde(rename = "unsaved")]
+    Global,
+    #[serde(rename = "saved")]
+    Saved,
+    #[serde(rename = "line")]
+    Line
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GameValueData {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub target: String
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TagData {
+    pub option: String,
+    pub tag: String,
+    pub action: String,
+    pub block: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variable: Option<Box<Item>>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ParameterData {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub plural: bool,
+    pub optional: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_value: Option<Box<Item>>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ItemData {
+    pub item: String
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HintData {
+    pub id: String
+}
+
+// Whole numbers are written without a fractional part, like DiamondFire does
+fn number<S: Serializer>(n: &f64, s: S) -> Result<S::Ok, S::Error> {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        s.serialize_i64(*n as i64)
+    } else {
+        s.serialize_f64(*n)
+    }
+}
+fn opt_number<S: Serializer>(n: &Option<f64>, s: S) -> Result<S::Ok, S::Error> {
+    match n {
+        Some(n) => number(n, s),
+        None => s.serialize_none()
+    }
+}
+
+fn malformed(message: String) -> DecompileError {
+    DecompileError::Malformed(message)
+}
+
+impl Template {
+    pub fn from_line(line: &CodeLine) -> Self {
+        Self { blocks: line.body().iter().flat_map(blocks).collect() }
+    }
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+    pub fn to_line(&self) -> Result<CodeLine, DecompileError> {
+        let mut body = vec![];
+        for block in &self.blocks {
+            match block {
+                // Opening brackets are implied by the block before them
+                Block::Bracket(Bracket { direct: Direction::Open, .. }) => {}
+                Block::Bracket(Bracket { direct: Direction::Close, kind: BracketKind::Norm }) => body.push(Statement::Close),
+                Block::Bracket(Bracket { direct: Direction::Close, kind: BracketKind::Repeat }) => body.push(Statement::CloseRepeat),
+                Block::Code(b) => body.push(statement(b)?),
+            }
+        }
+        let mut line = CodeLine::new_from(body);
+        // Line variables named after one of the function's parameters refer to it
+        let params: Vec<String> = line.body().first().and_then(FunctionSignature::of)
+            .map(|sig| sig.params.iter().map(|p| p.name.clone()).collect())
+            .unwrap_or_default();
+        if !params.is_empty() {
+            ParameterRefs { params: &params }.visit_line_mut(&mut line);
+        }
+        Ok(line)
+    }
+}
+
+struct ParameterRefs<'a> {
+    params: &'a [String]
+}
+impl VisitorMut for ParameterRefs<'_> {
+    fn visit_variable_mut(&mut self, var: &mut Variable) {
+        if var.scope == VariableScope::Line && self.params.contains(&var.name) {
+            var.scope = VariableScope::Parameter;
+        }
+        walk_variable_mut(self, var);
+    }
+}
+
+pub fn blocks(stmnt: &Statement) -> Vec<Block> {
+    let bracket = |kind| Block::Bracket(Bracket { direct: Direction::Open, kind });
+    let inverted = |not: &bool| Some(String::from(if *not { "NOT" } else { "" }));
+    let mut block = CodeBlock {
+        block: String::from(stmnt.technical_name()),
+        args: stmnt.parameters().map(|p| args(p, stmnt)),
+        action: None,
+        data: None,
+        sub_action: None,
+        inverted: None,
+        target: None
+    };
+    match stmnt {
+        Statement::Close => return vec![Block::Bracket(Bracket { direct: Direction::Close, kind: BracketKind::Norm })],
+        Statement::CloseRepeat => return vec![Block::Bracket(Bracket { direct: Direction::Close, kind: BracketKind::Repeat })],
+        Statement::Else => return vec![Block::Code(block), bracket(BracketKind::Norm)],
+        Statement::PlayerEvent(e) | Statement::EntityEvent(e) => {
+            block.args = Some(Args::default());
+            block.action = Some(e.clone());
+        }
+        Statement::Function { name, .. } | Statement::Process { name, .. } |
+        Statement::CallFunction { name, .. } | Statement::CallProcess { name, .. } => block.data = Some(name.clone()),
+        Statement::PlayerAction { action, selector, .. } | Statement::EntityAction { action, selector, .. } => {
+            block.action = Some(action.clone());
+            block.target = Some(selector.serialize());
+        }
+        Statement::SetVariable { action, .. } | Statement::GameAction { action, .. } | Statement::Control { action, .. } =>
+            block.action = Some(action.clone()),
+        Statement::SelectObject { action, subaction, not, .. } => {
+            block.action = Some(action.clone());
+            block.sub_action = Some(subaction.clone().unwrap_or_default());
+            block.inverted = inverted(not);
+        }
+        Statement::IfVariable { action, not, .. } | Statement::IfGame { action, not, .. } => {
+            block.action = Some(action.clone());
+            block.inverted = inverted(not);
+            return vec![Block::Code(block), bracket(BracketKind::Norm)];
+        }
+        Statement::IfPlayer { action, selector, not, .. } | Statement::IfEntity { action, selector, not, .. } => {
+            block.action = Some(action.clone());
+            block.inverted = inverted(not);
+            block.target = Some(selector.serialize());
+            return vec![Block::Code(block), bracket(BracketKind::Norm)];
+        }
+        Statement::Repeat { action, subaction, not, .. } => {
+            block.action = Some(action.clone());
+            block.sub_action = Some(subaction.clone().unwrap_or_default());
+            block.inverted = inverted(not);
+            return vec![Block::Code(block), bracket(BracketKind::Repeat)];
+        }
+    }
+    vec![Block::Code(block)]
+}
+
+pub fn args(parameters: &ParameterList, stmnt: &Statement) -> Args {
+    Args {
+        items: parameters.iter()
+            .enumerate()
+            .filter_map(|(slot, v)| v.as_ref().map(|v| ArgItem { item: item(v, stmnt), slot: slot as u8 }))
+            .collect()
+    }
+}
+
+fn variable_data(v: &Variable) -> VariableData {
+    VariableData {
+        name: v.name.clone(),
+        scope: match v.scope {
+            VariableScope::Local => Scope::Local,
+            VariableScope::Global => Scope::Global,
+            VariableScope::Saved => Scope::Saved,
+            VariableScope::Line | VariableScope::Parameter => Scope::Line,
+        }
+    }
+}
+
+// Tags name the action they belong to, which for blocks with a sub action is that one
+fn tag_action(stmnt: &Statement) -> &str {
+    match stmnt {
+        Statement::PlayerEvent(n) | Statement::EntityEvent(n) => n,
+        Statement::Function { .. } | Statement::CallFunction { .. } |
+        Statement::CallProcess { .. } | Statement::Process { .. } => "dynamic",
+        Statement::PlayerAction { action, .. } | Statement::EntityAction { action, .. } |
+        Statement::SetVariable { action, .. } | Statement::GameAction { action, .. } |
+        Statement::Control { action, .. } | Statement::IfVariable { action, .. } |
+        Statement::IfPlayer { action, .. } | Statement::IfEntity { action, .. } |
+        Statement::IfGame { action, .. } => action,
+        Statement::Else => "else",
+        Statement::Close => "close",
+        Statement::CloseRepeat => "close_repeat",
+        Statement::Repeat { action, subaction, .. } |
+        Statement::SelectObject { action, subaction, .. } => subaction.as_ref().unwrap_or(action)
+    }
+}
+
+pub fn item(value: &Value, stmnt: &Statement) -> Item {
+    match value {
+        Value::Text(Text(t)) => Item::Text(NameData { name: t.clone() }),
+        Value::Component(c) => Item::Component(NameData { name: String::from(c.as_str()) }),
+        Value::Number(Number::Literal(n)) => Item::Number(NameData { name: n.to_string() }),
+        Value::Number(Number::Math(e)) => Item::Number(NameData { name: e.to_code() }),
+        Value::Location(Location { x, y, z, pitch, yaw, is_block }) => Item::Location(LocationData {
+            is_block: *is_block,
+            loc: Loc { x: *x, y: *y, z: *z, pitch: *pitch, yaw: *yaw }
+        }),
+        Value::Vector(Vector { x, y, z }) => Item::Vector(VectorData { x: *x, y: *y, z: *z }),
+        Value::Sound(Sound { sound, variant, pitch, volume }) => Item::Sound(SoundData { sound: sound.clone(), variant: variant.clone(), pitch: *pitch, vol: *volume }),
+        Value::Particle(p) => Item::Particle(ParticleData {
+            particle: p.particle.clone(),
+            cluster: Cluster { amount: p.amount, horizontal: p.spread.0, vertical: p.spread.1 },
+            data: ParticleFields {
+                x: p.motion.as_ref().map(|m| m.x),
+                y: p.motion.as_ref().map(|m| m.y),
+                z: p.motion.as_ref().map(|m| m.z),
+                motion_variation: p.variation_motion,
+                rgb: p.color.map(|(r, g, b)| (r as u32) << 16 | (g as u32) << 8 | b as u32),
+                color_variation: p.variation_color,
+                material: p.material.clone(),
+                size: p.size,
+                size_variation: p.variation_size,
+                roll: p.roll
+            }
+        }),
+        Value::Potion(Potion { effect, ticks, level }) => Item::Potion(PotionData { pot: effect.serialize(), dur: *ticks, amp: *level }),
+        Value::Variable(v) => Item::Variable(variable_data(v)),
+        Value::GameValue(GameValue { name, selector }) => Item::GameValue(GameValueData {
+            kind: name.clone(),
+            target: selector.unwrap_or_default().serialize()
+        }),
+        Value::Item(i) => Item::Minecraft(ItemData { item: item_snbt(i) }),
+        Value::Tag(Tag { name, option, var }) => Item::Tag(TagData {
+            option: option.clone(),
+            tag: name.clone(),
+            action: String::from(tag_action(stmnt)),
+            block: String::from(stmnt.technical_name()),
+            variable: var.as_ref().map(|v| Box::new(Item::Variable(variable_data(v))))
+        }),
+        Value::Parameter(FunctionParam { name, value_type, plural, optional, default }) => Item::Parameter(ParameterData {
+            name: name.clone(),
+            kind: value_type.serialize(),
+            plural: *plural,
+            optional: *optional,
+            description: None,
+            note: None,
+            default_value: default.as_ref().map(|d| Box::new(item(d, stmnt)))
+        }),
+    }
+}
+
+fn snbt_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn uuid_ints(uuid: &str) -> Option<[i32; 4]> {
+    let n = u128::from_str_radix(&uuid.replace('-', ""), 16).ok()?;
+    Some([(n >> 96) as i32, (n >> 64) as i32, (n >> 32) as i32, n as i32])
+}
+
+// The item as SNBT, in the 1.20 format DiamondFire reads
+pub fn item_snbt(item: &ItemValue) -> String {
+    if let Some(snbt) = &item.snbt {
+        return snbt.clone();
+    }
+    let mut tag = vec![];
+    let mut display = vec![];
+    if let Some(name) = &item.name {
+        display.push(format!("Name:'{}'", format!(r#"{{"text":{}}}"#, serde_json::to_string(name).unwrap()).replace('\'', "\\'")));
+    }
+    if let Some(lore) = &item.lore {
+        let lines: Vec<String> = lore.iter()
+            .map(|l| format!("'{}'", format!(r#"{{"text":{}}}"#, serde_json::to_string(l).unwrap()).replace('\'', "\\'")))
+            .collect();
+        display.push(format!("Lore:[{}]", lines.join(",")));
+    }
+    if !display.is_empty() {
+        tag.push(format!("display:{{{}}}", display.join(",")));
+    }
+    if let Some(data) = item.modeldata {
+        tag.push(format!("CustomModelData:{data}"));
+    }
+    if item.unbreakable {
+        tag.push(String::from("Unbreakable:1b"));
+    }
+    if let Some(flags) = item.flags {
+        tag.push(format!("HideFlags:{flags}"));
+    }
+    if let Some(attributes) = &item.attributes {
+        let modifiers: Vec<String> = attributes.iter().map(|Attribute { uuid, amount, operation, name, slot }| {
+            let [a, b, c, d] = uuid_ints(uuid).unwrap_or_default();
+            let operation = match operation {
+                AttributeOperation::AddModifier => 0,
+                AttributeOperation::MultiplyBase => 1,
+                AttributeOperation::MultiplyModifier => 2,
+            };
+            format!(
+                "{{AttributeName:{},Name:{},Amount:{}d,Operation:{operation},Slot:{},UUID:[I;{a},{b},{c},{d}]}}",
+                snbt_string(name), snbt_string(name), amount, snbt_string(slot)
+            )
+        }).collect();
+        tag.push(format!("AttributeModifiers:[{}]", modifiers.join(",")));
+    }
+    let mut bukkit: Vec<String> = item.string_tags.iter().map(|(k, v)| format!("\"hypercube:{k}\":{}", snbt_string(v))).collect();
+    bukkit.extend(item.num_tags.iter().map(|(k, v)| format!("\"hypercube:{k}\":{v}d")));
+    if !bukkit.is_empty() {
+        bukkit.sort();
+        tag.push(format!("PublicBukkitValues:{{{}}}", bukkit.join(",")));
+    }
+    let id = if item.material.contains(':') { item.material.clone() } else { format!("minecraft:{}", item.material) };
+    let mut out = format!("{{Count:{}b,DF_NBT:3465,id:{}", item.count, snbt_string(&id));
+    if !tag.is_empty() {
+        out += &format!(",tag:{{{}}}", tag.join(","));
+    }
+    out + "}"
+}
+
+// A value in the outermost compound of some SNBT, skipping nested compounds,
+// lists and quoted strings
+fn snbt_field<'a>(snbt: &'a str, key: &str) -> Option<&'a str> {
+    let body = snbt.trim().strip_prefix('{')?.strip_suffix('}')?;
+    let (mut depth, mut quote, mut escaped, mut start) = (0, None, false, 0);
+    let mut fields = vec![];
+    for (i, c) in body.char_indices() {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '{' | '[') => depth += 1,
+            (None, '}' | ']') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                fields.push(&body[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(&body[start..]);
+    fields.into_iter().find_map(|f| {
+        let (k, v) = f.split_once(':')?;
+        (k.trim().trim_matches('"') == key).then_some(v.trim())
+    })
+}
+
+// Only the material and count are read out; the SNBT itself is kept so the item
+// compiles back unchanged
+fn item_value(snbt: &str) -> ItemValue {
+    let material = snbt_field(snbt, "id").map_or("air", |id| id.trim_matches(['"', '\''])).trim_start_matches("minecraft:");
+    let count = snbt_field(snbt, "Count").and_then(|c| c.trim_end_matches(['b', 'B']).parse().ok()).unwrap_or(1);
+    ItemValue {
+        material: String::from(material),
+        count,
+        attributes: None,
+        flags: None,
+        lore: None,
+        modeldata: None,
+        name: None,
+        unbreakable: false,
+        string_tags: HashMap::new(),
+        num_tags: HashMap::new(),
+        snbt: Some(String::from(snbt))
+    }
+}
+
+pub fn statement(block: &CodeBlock) -> Result<Statement, DecompileError> {
+    let kind = block.block.as_str();
+    let field = |value: &Option<String>, name: &str| value.clone().ok_or_else(|| malformed(format!("{kind} block is missing \"{name}\"")));
+    let action = || field(&block.action, "action");
+    let data = || field(&block.data, "data");
+    let subaction = || block.sub_action.clone().filter(|s| !s.is_empty());
+    let not = block.inverted.as_deref() == Some("NOT");
+    let selector = || match block.target.as_deref() {
+        None | Some("") => Ok(Selector::Default),
+        Some(t) => Selector::parse(t).ok_or_else(|| malformed(format!("unknown target {t}")))
+    };
+    let parameters = || parameter_list(block.args.as_ref());
+    Ok(match kind {
+        "event" => Statement::PlayerEvent(action()?),
+        "entity_event" => Statement::EntityEvent(action()?),
+        "func" => Statement::Function { name: data()?, parameters: parameters()? },
+        "process" => Statement::Process { name: data()?, parameters: parameters()? },
+        "call_func" => Statement::CallFunction { name: data()?, parameters: parameters()? },
+        "start_process" => Statement::CallProcess { name: data()?, parameters: parameters()? },
+        "player_action" => Statement::PlayerAction { action: action()?, parameters: parameters()?, selector: selector()? },
+        "entity_action" => Statement::EntityAction { action: action()?, parameters: parameters()?, selector: selector()? },
+        "set_var" => Statement::SetVariable { action: action()?, parameters: parameters()? },
+        "game_action" => Statement::GameAction { action: action()?, parameters: parameters()? },
+        "control" => Statement::Control { action: action()?, parameters: parameters()? },
+        "select_obj" => Statement::SelectObject { action: action()?, subaction: subaction(), parameters: parameters()?, not },
+        "if_var" => Statement::IfVariable { action: action()?, parameters: parameters()?, not },
+        "if_player" => Statement::IfPlayer { action: action()?, parameters: parameters()?, selector: selector()?, not },
+        "if_entity" => Statement::IfEntity { action: action()?, parameters: parameters()?, selector: selector()?, not },
+        "if_game" => Statement::IfGame { action: action()?, parameters: parameters()?, not },
+        "repeat" => Statement::Repeat { action: action()?, subaction: subaction(), parameters: parameters()?, not },
+        "else" => Statement::Else,
+        other => return Err(malformed(format!("unknown block {other}")))
+    })
+}
+
+pub fn parameter_list(args: Option<&Args>) -> Result<ParameterList, DecompileError> {
+    let mut out: ParameterList = Default::default();
+    for arg in args.map_or(&[][..], |a| &a.items[..]) {
+        let slot = arg.slot as usize;
+        if slot >= out.len() {
+            return Err(malformed(format!("slot {slot} is out of range")));
+        }
+        out[slot] = value(&arg.item)?;
+    }
+    Ok(out)
+}
+
+fn variable(data: &VariableData) -> Variable {
+    Variable {
+        name: data.name.clone(),
+        scope: match data.scope {
+            Scope::Local => VariableScope::Local,
+            Scope::Global => VariableScope::Global,
+            Scope::Saved => VariableScope::Saved,
+            Scope::Line => VariableScope::Line,
+        }
+    }
+}
+
+// None for editor hints, which don't hold a value
+pub fn value(item: &Item) -> Result<Option<Value>, DecompileError> {
+    Ok(Some(match item {
+        Item::Text(NameData { name }) => Value::Text(Text(name.clone())),
+        Item::Component(NameData { name }) => Value::Component(Component::parse(name).map_err(|e| malformed(format!("styled text {name}: {e}")))?),
+        Item::Number(NameData { name }) => Value::Number(match Decimal::parse(name) {
+            Some(n) => Number::Literal(n),
+            None => Number::Math(textcode::parse_expr(name).map_err(|e| malformed(format!("number {name}: {e}")))?)
+        }),
+        Item::Location(LocationData { loc, is_block }) => Value::Location(Location {
+            x: loc.x, y: loc.y, z: loc.z, pitch: loc.pitch, yaw: loc.yaw, is_block: *is_block
+        }),
+        Item::Vector(VectorData { x, y, z }) => Value::Vector(Vector { x: *x, y: *y, z: *z }),
+        Item::Sound(SoundData { sound, variant, pitch, vol }) => Value::Sound(Sound { sound: sound.clone(), variant: variant.clone(), pitch: *pitch, volume: *vol }),
+        Item::Particle(ParticleData { particle, cluster, data }) => Value::Particle(Particle {
+            particle: particle.clone(),
+            amount: cluster.amount,
+            color: data.rgb.map(|c| ((c >> 16) as u8, (c >> 8) as u8, c as u8)),
+            variation_color: data.color_variation,
+            material: data.material.clone(),
+            motion: match (data.x, data.y, data.z) {
+                (Some(x), Some(y), Some(z)) => Some(Vector { x, y, z }),
+                _ => None
+            },
+            variation_motion: data.motion_variation,
+            roll: data.roll,
+            size: data.size,
+            variation_size: data.size_variation,
+            spread: (cluster.horizontal, cluster.vertical)
+        }),
+        Item::Variable(v) => Value::Variable(variable(v)),
+        Item::GameValue(GameValueData { kind, target }) => Value::GameValue(GameValue {
+            name: kind.clone(),
+            selector: Selector::parse(target)
+        }),
+        Item::Tag(TagData { option, tag, variable: var, .. }) => Value::Tag(Tag {
+            name: tag.clone(),
+            option: option.clone(),
+            var: match var.as_deref() {
+                None => None,
+                Some(Item::Variable(v)) => Some(variable(v)),
+                Some(_) => return Err(malformed(format!("tag {tag} is bound to something other than a variable")))
+            }
+        }),
+        Item::Parameter(p) => Value::Parameter(FunctionParam {
+            name: p.name.clone(),
+            value_type: ValueType::parse(&p.kind).ok_or_else(|| DecompileError::Unsupported(format!("parameter type {}", p.kind)))?,
+            plural: p.plural,
+            optional: p.optional,
+            default: p.default_value.as_deref().map(value).transpose()?.flatten().map(Box::new)
+        }),
+        Item::Hint(_) => return Ok(None),
+        Item::Potion(PotionData { pot, dur, amp }) => Value::Potion(Potion { effect: PotionEffect::from_name(pot), ticks: *dur, level: *amp }),
+        Item::Minecraft(ItemData { item }) => Value::Item(item_value(item)),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compile::DFCompile, decompile::parse_templates};
+
+    const BURST: &str = r#"{"blocks":[{"id":"block","block":"func","args":{"items":[{"item":{"id":"pn_el","data":{"name":"at","type":"loc","plural":false,"optional":true,"default_value":{"id":"loc","data":{"isBlock":false,"loc":{"x":1.5,"y":64,"z":-2,"pitch":0,"yaw":90}}}}},"slot":0},{"item":{"id":"hint","data":{"id":"function"}},"slot":25}]},"data":"burst"},{"id":"block","block":"player_action","args":{"items":[{"item":{"id":"snd","data":{"sound":"Pling","pitch":2,"vol":0.5}},"slot":0},{"item":{"id":"g_val","data":{"type":"Location","target":"Default"}},"slot":1},{"item":{"id":"part","data":{"particle":"Dust","cluster":{"amount":4,"horizontal":0.5,"vertical":0},"data":{"rgb":16711680,"size":1.5}}},"slot":2},{"item":{"id":"vec","data":{"x":0,"y":1,"z":0}},"slot":3},{"item":{"id":"txt","data":{"name":"say \"hi\""}},"slot":4}]},"action":"PlaySound","target":"Selection"}]}"#;
+
+    #[test]
+    fn test_template_model() {
+        let template: Template = serde_json::from_str(BURST).unwrap();
+        assert_eq!(template.to_json(), BURST);
+        assert!(matches!(&template.blocks[1], Block::Code(b) if b.target.as_deref() == Some("Selection")));
+    }
+
+    #[test]
+    fn test_to_line() {
+        // Hints are dropped, everything else survives the trip through Statement/Value
+        let line = serde_json::from_str::<Template>(BURST).unwrap().to_line().unwrap();
+        assert_eq!(line.serialize(), BURST.replace(r#",{"item":{"id":"hint","data":{"id":"function"}},"slot":25}"#, ""));
+    }
+
+    #[test]
+    fn test_math_number() {
+        assert!(matches!(serde_json::from_str::<Item>(r#"{"id":"num","data":{"name":"%math(1+1)"}}"#), Ok(Item::Number(_))));
+    }
+
+    #[test]
+    fn test_item_snbt() {
+        // Items come back with their SNBT untouched
+        let item = r#"{"id":"item","data":{"item":"{Count:3b,DF_NBT:3465,id:\"minecraft:diamond_sword\",tag:{display:{Name:'{\"text\":\"Sword, of {it}\"}'},Unbreakable:1b}}"}}"#;
+        let json = format!(r#"{{"blocks":[{{"id":"block","block":"player_action","args":{{"items":[{{"item":{},"slot":0}}]}},"action":"GiveItems","target":"Selection"}}]}}"#, item);
+        let line = serde_json::from_str::<Template>(&json).unwrap().to_line().unwrap();
+        assert_eq!(line.serialize(), json);
+        assert_eq!(parse_templates(&line.compile()).unwrap()[0].serialize(), json);
+        let Some(Statement::PlayerAction { parameters, .. }) = line.body().first() else { panic!() };
+        let Some(Value::Item(i)) = &parameters[0] else { panic!() };
+        assert_eq!((i.material.as_str(), i.count), ("diamond_sword", 3));
+    }
+
+    #[test]
+    fn test_incomplete_block() {
+        assert!(serde_json::from_str::<Template>(r#"{"blocks":[{"id":"block"}]}"#).is_err());
+    }
+}
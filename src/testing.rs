@@ -0,0 +1,19 @@
+use crate::{params::ParamBuilder, statements::Statement, values::{Value, Variable, VariableScope}};
+
+// Builders for the variables and set variable blocks most tests need
+pub fn variable(name: &str, scope: VariableScope) -> Variable {
+    Variable { name: String::from(name), scope }
+}
+pub fn local(name: &str) -> Variable {
+    variable(name, VariableScope::Local)
+}
+pub fn var(name: &str) -> Value {
+    Value::Variable(local(name))
+}
+pub fn set_var(action: &str, dest: Variable, values: Vec<Value>) -> Statement {
+    let builder = values.into_iter().fold(ParamBuilder::new().param(Value::Variable(dest)), |b, v| b.param(v));
+    Statement::SetVariable { action: String::from(action), parameters: builder.complete_unchecked() }
+}
+pub fn set(dest: Variable, value: Value) -> Statement {
+    set_var("=", dest, vec![value])
+}
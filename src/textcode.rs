@@ -0,0 +1,620 @@
+use std::{fmt::Display, ops};
+
+use crate::values::{Number, Text, Value, Variable};
+
+// DiamondFire text codes like %default, %var(name) and %math(1+%var(x)).
+// Codes only carry a name, so %var finds the variable through its local, game then saved scope.
+
+#[derive(Debug, PartialEq)]
+pub enum TextCodeError {
+    // A code's ( that is never closed
+    Unbalanced { at: usize },
+    UnknownCode { code: String, at: usize },
+    // Missing parentheses or the wrong number of arguments
+    Arguments { code: String, at: usize },
+    Expression { at: usize }
+}
+impl Display for TextCodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextCodeError::Unbalanced { at } => write!(f, "parenthesis at {at} is never closed"),
+            TextCodeError::UnknownCode { code, at } => write!(f, "unknown text code %{code} at {at}"),
+            TextCodeError::Arguments { code, at } => write!(f, "wrong arguments for %{code} at {at}"),
+            TextCodeError::Expression { at } => write!(f, "invalid math expression at {at}"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Placeholder {
+    Default, Selected, Killer, Damager, Victim, Shooter, Projectile, Uuid
+}
+impl Placeholder {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "default" => Some(Placeholder::Default),
+            "selected" => Some(Placeholder::Selected),
+            "killer" => Some(Placeholder::Killer),
+            "damager" => Some(Placeholder::Damager),
+            "victim" => Some(Placeholder::Victim),
+            "shooter" => Some(Placeholder::Shooter),
+            "projectile" => Some(Placeholder::Projectile),
+            "uuid" => Some(Placeholder::Uuid),
+            _ => None
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Placeholder::Default => "default",
+            Placeholder::Selected => "selected",
+            Placeholder::Killer => "killer",
+            Placeholder::Damager => "damager",
+            Placeholder::Victim => "victim",
+            Placeholder::Shooter => "shooter",
+            Placeholder::Projectile => "projectile",
+            Placeholder::Uuid => "uuid",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Segment {
+    Text(String),
+    Placeholder(Placeholder),
+    Var(TextCode),
+    Math(Expr),
+    // List name and index
+    Index(TextCode, TextCode),
+    // Dictionary name and key
+    Entry(TextCode, TextCode),
+    // One of the options, picked at random
+    Random(Vec<TextCode>)
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct TextCode(pub Vec<Segment>);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Op {
+    Add, Sub, Mul, Div, Rem
+}
+impl Op {
+    fn symbol(&self) -> char {
+        match self {
+            Op::Add => '+',
+            Op::Sub => '-',
+            Op::Mul => '*',
+            Op::Div => '/',
+            Op::Rem => '%',
+        }
+    }
+    fn precedence(&self) -> u8 {
+        match self {
+            Op::Add | Op::Sub => 1,
+            Op::Mul | Op::Div | Op::Rem => 2,
+        }
+    }
+}
+
+// The inside of %math
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    // Kept as written
+    Literal(String),
+    // %var, %index and %entry
+    Code(Box<Segment>),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, Op, Box<Expr>)
+}
+
+impl Expr {
+    pub fn literal(n: f64) -> Self {
+        Expr::Literal(n.to_string())
+    }
+    pub fn var(var: &Variable) -> Self {
+        Expr::Code(Box::new(Segment::Var(name_code(&var.name))))
+    }
+    fn binary(self, op: Op, other: Expr) -> Self {
+        Expr::Binary(Box::new(self), op, Box::new(other))
+    }
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Binary(_, op, _) => op.precedence(),
+            _ => 3
+        }
+    }
+    // How the expression is written in a number item: codes on their own, anything else in %math
+    pub fn to_code(&self) -> String {
+        match self {
+            Expr::Code(c) => TextCode(vec![(**c).clone()]).to_string(),
+            e => format!("%math({e})")
+        }
+    }
+    pub fn into_number(self) -> Number {
+        Number::Math(self)
+    }
+    // Same as TextCode::variables
+    pub fn variables(&self) -> Vec<&str> {
+        let mut out = vec![];
+        self.collect_variables(&mut out);
+        out
+    }
+    fn collect_variables<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            Expr::Literal(_) => {}
+            Expr::Code(c) => c.variables(out),
+            Expr::Neg(e) => e.collect_variables(out),
+            Expr::Binary(a, _, b) => {
+                a.collect_variables(out);
+                b.collect_variables(out);
+            }
+        }
+    }
+    pub fn rename_variables(&mut self, f: &mut dyn FnMut(&str) -> Option<String>) {
+        match self {
+            Expr::Literal(_) => {}
+            Expr::Code(c) => c.rename_variables(f),
+            Expr::Neg(e) => e.rename_variables(f),
+            Expr::Binary(a, _, b) => {
+                a.rename_variables(f);
+                b.rename_variables(f);
+            }
+        }
+    }
+}
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Literal(n) => f.write_str(n),
+            Expr::Code(c) => write!(f, "{}", TextCode(vec![(**c).clone()])),
+            Expr::Neg(e) if e.precedence() < 3 => write!(f, "-({e})"),
+            Expr::Neg(e) => write!(f, "-{e}"),
+            Expr::Binary(a, op, b) => {
+                // Left-associative, so the right side needs parentheses at equal precedence too
+                let left = if a.precedence() < op.precedence() { format!("({a})") } else { a.to_string() };
+                let right = if b.precedence() <= op.precedence() { format!("({b})") } else { b.to_string() };
+                write!(f, "{left}{}{right}", op.symbol())
+            }
+        }
+    }
+}
+impl ops::Add for Expr {
+    type Output = Expr;
+    fn add(self, other: Expr) -> Expr {
+        self.binary(Op::Add, other)
+    }
+}
+impl ops::Sub for Expr {
+    type Output = Expr;
+    fn sub(self, other: Expr) -> Expr {
+        self.binary(Op::Sub, other)
+    }
+}
+impl ops::Mul for Expr {
+    type Output = Expr;
+    fn mul(self, other: Expr) -> Expr {
+        self.binary(Op::Mul, other)
+    }
+}
+impl ops::Div for Expr {
+    type Output = Expr;
+    fn div(self, other: Expr) -> Expr {
+        self.binary(Op::Div, other)
+    }
+}
+impl ops::Rem for Expr {
+    type Output = Expr;
+    fn rem(self, other: Expr) -> Expr {
+        self.binary(Op::Rem, other)
+    }
+}
+impl ops::Neg for Expr {
+    type Output = Expr;
+    fn neg(self) -> Expr {
+        Expr::Neg(Box::new(self))
+    }
+}
+
+// Variable names can be built from codes themselves
+fn name_code(name: &str) -> TextCode {
+    parse(name).unwrap_or_else(|_| TextCode(vec![Segment::Text(String::from(name))]))
+}
+
+impl TextCode {
+    pub fn new() -> Self {
+        Self(vec![])
+    }
+    pub fn text(mut self, text: &str) -> Self {
+        match self.0.last_mut() {
+            Some(Segment::Text(t)) => *t += text,
+            _ => self.0.push(Segment::Text(String::from(text)))
+        }
+        self
+    }
+    pub fn placeholder(mut self, p: Placeholder) -> Self {
+        self.0.push(Segment::Placeholder(p));
+        self
+    }
+    pub fn var(mut self, var: &Variable) -> Self {
+        self.0.push(Segment::Var(name_code(&var.name)));
+        self
+    }
+    pub fn math(mut self, expr: Expr) -> Self {
+        self.0.push(Segment::Math(expr));
+        self
+    }
+    pub fn index(mut self, list: &Variable, index: TextCode) -> Self {
+        self.0.push(Segment::Index(name_code(&list.name), index));
+        self
+    }
+    pub fn entry(mut self, dict: &Variable, key: TextCode) -> Self {
+        self.0.push(Segment::Entry(name_code(&dict.name), key));
+        self
+    }
+    pub fn random(mut self, options: Vec<TextCode>) -> Self {
+        self.0.push(Segment::Random(options));
+        self
+    }
+    pub fn into_text(self) -> Text {
+        Text(self.to_string())
+    }
+    // Names read through %var, %index and %entry. Names that are built from codes
+    // are skipped, but the names inside those codes are not.
+    pub fn variables(&self) -> Vec<&str> {
+        let mut out = vec![];
+        for s in &self.0 {
+            s.variables(&mut out);
+        }
+        out
+    }
+    // Replaces each name `variables` would return with what `f` gives back, if anything
+    pub fn rename_variables(&mut self, f: &mut dyn FnMut(&str) -> Option<String>) {
+        for s in &mut self.0 {
+            s.rename_variables(f);
+        }
+    }
+}
+impl Display for TextCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for s in &self.0 {
+            match s {
+                Segment::Text(t) => f.write_str(t)?,
+                Segment::Placeholder(p) => write!(f, "%{}", p.name())?,
+                Segment::Var(name) => write!(f, "%var({name})")?,
+                Segment::Math(e) => write!(f, "%math({e})")?,
+                Segment::Index(list, i) => write!(f, "%index({list},{i})")?,
+                Segment::Entry(dict, key) => write!(f, "%entry({dict},{key})")?,
+                Segment::Random(options) => {
+                    let options: Vec<String> = options.iter().map(|o| o.to_string()).collect();
+                    write!(f, "%random({})", options.join(","))?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+impl Segment {
+    fn variables<'a>(&'a self, out: &mut Vec<&'a str>) {
+        let literal = |name: &'a TextCode| match &name.0[..] {
+            [Segment::Text(t)] => Some(t.as_str()),
+            _ => None
+        };
+        let name = |name: &'a TextCode, out: &mut Vec<&'a str>| match literal(name) {
+            Some(n) => out.push(n),
+            None => for s in &name.0 {
+                s.variables(out);
+            }
+        };
+        match self {
+            Segment::Text(_) | Segment::Placeholder(_) => {}
+            Segment::Var(n) => name(n, out),
+            Segment::Math(e) => e.collect_variables(out),
+            Segment::Index(n, other) | Segment::Entry(n, other) => {
+                name(n, out);
+                for s in &other.0 {
+                    s.variables(out);
+                }
+            }
+            Segment::Random(options) => for s in options.iter().flat_map(|o| &o.0) {
+                s.variables(out);
+            },
+        }
+    }
+    fn rename_variables(&mut self, f: &mut dyn FnMut(&str) -> Option<String>) {
+        let name = |name: &mut TextCode, f: &mut dyn FnMut(&str) -> Option<String>| match &mut name.0[..] {
+            [Segment::Text(t)] => if let Some(renamed) = f(t) {
+                *t = renamed;
+            },
+            segments => for s in segments {
+                s.rename_variables(f);
+            }
+        };
+        match self {
+            Segment::Text(_) | Segment::Placeholder(_) => {}
+            Segment::Var(n) => name(n, f),
+            Segment::Math(e) => e.rename_variables(f),
+            Segment::Index(n, other) | Segment::Entry(n, other) => {
+                name(n, f);
+                other.rename_variables(f);
+            }
+            Segment::Random(options) => for o in options {
+                o.rename_variables(f);
+            },
+        }
+    }
+}
+
+// Renames the variables read through codes in `text`, leaving it as written when
+// nothing changes or it isn't valid
+pub fn rename_in(text: &str, f: &mut dyn FnMut(&str) -> Option<String>) -> Option<String> {
+    let mut code = parse(text).ok()?;
+    let mut changed = false;
+    code.rename_variables(&mut |n| {
+        let renamed = f(n);
+        changed |= renamed.is_some();
+        renamed
+    });
+    changed.then(|| code.to_string())
+}
+
+pub fn parse(text: &str) -> Result<TextCode, TextCodeError> {
+    parse_at(text, 0)
+}
+
+// The byte offset of the ) that closes the ( at `open`
+fn closing(text: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text[open..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// Splits on commas outside of parentheses, with each part's offset
+fn split_args(text: &str) -> Vec<(usize, &str)> {
+    let mut out = vec![];
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                out.push((start, &text[start..i]));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    out.push((start, &text[start..]));
+    out
+}
+
+fn parse_at(text: &str, base: usize) -> Result<TextCode, TextCodeError> {
+    let mut out = TextCode::new();
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        // Only sliced past the first byte once it's known to be a one byte %
+        let name_len = match rest.strip_prefix('%') {
+            Some(after) => after.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(after.len()),
+            None => 0
+        };
+        if name_len == 0 {
+            let c = rest.chars().next().unwrap();
+            out = out.text(&c.to_string());
+            i += c.len_utf8();
+            continue;
+        }
+        let at = base + i;
+        let code = &rest[1..1 + name_len];
+        if let Some(p) = Placeholder::parse(code) {
+            out = out.placeholder(p);
+            i += 1 + name_len;
+            continue;
+        }
+        if !matches!(code, "var" | "math" | "index" | "entry" | "random") {
+            return Err(TextCodeError::UnknownCode { code: String::from(code), at });
+        }
+        let arguments = || TextCodeError::Arguments { code: String::from(code), at };
+        let open = 1 + name_len;
+        if !rest[open..].starts_with('(') {
+            return Err(arguments());
+        }
+        let close = closing(rest, open).ok_or(TextCodeError::Unbalanced { at: at + open })?;
+        let inner_at = at + open + 1;
+        let args = split_args(&rest[open + 1..close]);
+        let text_arg = |(offset, arg): (usize, &str)| parse_at(arg, inner_at + offset);
+        let segment = match (code, &args[..]) {
+            ("var", [name]) if !name.1.is_empty() => Segment::Var(text_arg(*name)?),
+            ("math", [expr]) => Segment::Math(parse_expr_at(expr.1, inner_at + expr.0)?),
+            ("index", [list, index]) => Segment::Index(text_arg(*list)?, text_arg(*index)?),
+            ("entry", [dict, key]) => Segment::Entry(text_arg(*dict)?, text_arg(*key)?),
+            ("random", options) => Segment::Random(options.iter().map(|o| text_arg(*o)).collect::<Result<_, _>>()?),
+            _ => return Err(arguments())
+        };
+        out.0.push(segment);
+        i += close + 1;
+    }
+    Ok(out)
+}
+
+pub fn parse_expr(text: &str) -> Result<Expr, TextCodeError> {
+    parse_expr_at(text, 0)
+}
+
+fn parse_expr_at(text: &str, base: usize) -> Result<Expr, TextCodeError> {
+    let mut parser = ExprParser { text, base, pos: 0 };
+    let expr = parser.sum()?;
+    parser.skip_space();
+    if parser.pos < text.len() {
+        return Err(TextCodeError::Expression { at: base + parser.pos });
+    }
+    Ok(expr)
+}
+
+struct ExprParser<'a> {
+    text: &'a str,
+    base: usize,
+    pos: usize
+}
+impl ExprParser<'_> {
+    fn skip_space(&mut self) {
+        while self.text[self.pos..].starts_with(' ') {
+            self.pos += 1;
+        }
+    }
+    fn error(&self) -> TextCodeError {
+        TextCodeError::Expression { at: self.base + self.pos }
+    }
+    fn op(&mut self, ops: &[Op]) -> Option<Op> {
+        self.skip_space();
+        let rest = &self.text[self.pos..];
+        // A % followed by a letter starts a code, not a remainder
+        let op = ops.iter().find(|o| rest.starts_with(o.symbol()) && !(o.symbol() == '%' && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic())))?;
+        self.pos += 1;
+        Some(*op)
+    }
+    fn sum(&mut self) -> Result<Expr, TextCodeError> {
+        let mut left = self.product()?;
+        while let Some(op) = self.op(&[Op::Add, Op::Sub]) {
+            left = left.binary(op, self.product()?);
+        }
+        Ok(left)
+    }
+    fn product(&mut self) -> Result<Expr, TextCodeError> {
+        let mut left = self.unary()?;
+        while let Some(op) = self.op(&[Op::Mul, Op::Div, Op::Rem]) {
+            left = left.binary(op, self.unary()?);
+        }
+        Ok(left)
+    }
+    fn unary(&mut self) -> Result<Expr, TextCodeError> {
+        self.skip_space();
+        let rest = &self.text[self.pos..];
+        if rest.starts_with('-') {
+            self.pos += 1;
+            return Ok(-self.unary()?);
+        }
+        if rest.starts_with('(') {
+            let close = closing(self.text, self.pos).ok_or(TextCodeError::Unbalanced { at: self.base + self.pos })?;
+            let inner = parse_expr_at(&self.text[self.pos + 1..close], self.base + self.pos + 1)?;
+            self.pos = close + 1;
+            return Ok(inner);
+        }
+        if let Some(name) = rest.strip_prefix('%') {
+            let end = name.find(|c: char| !c.is_ascii_alphabetic()).map_or(rest.len(), |n| n + 1);
+            let end = if rest[end..].starts_with('(') {
+                closing(rest, end).ok_or(TextCodeError::Unbalanced { at: self.base + self.pos + end })? + 1
+            } else {
+                end
+            };
+            let code = parse_at(&rest[..end], self.base + self.pos)?;
+            self.pos += end;
+            return match code.0.into_iter().next() {
+                Some(s @ (Segment::Var(_) | Segment::Index(..) | Segment::Entry(..))) => Ok(Expr::Code(Box::new(s))),
+                Some(Segment::Math(e)) => Ok(e),
+                _ => Err(self.error())
+            };
+        }
+        let len = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(rest.len());
+        let literal = &rest[..len];
+        if literal.is_empty() || literal.parse::<f64>().is_err() {
+            return Err(self.error());
+        }
+        self.pos += len;
+        Ok(Expr::Literal(String::from(literal)))
+    }
+}
+
+// Checks the text codes in a value; anything without text passes
+pub fn check_value(value: &Value) -> Result<(), TextCodeError> {
+    match value {
+        Value::Text(Text(t)) => parse(t).map(|_| ()),
+        Value::Component(c) => parse(c.as_str()).map(|_| ()),
+        Value::Variable(v) => parse(&v.name).map(|_| ()),
+        _ => Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code_line::CodeLine, decompile::parse_templates, params::ParamBuilder, program::Program, serialization::DFSerializable, statements::Statement, testing::{local, set, variable}, validate::validate, values::{Selector, VariableScope}};
+
+    #[test]
+    fn test_text_codes() {
+        let coins = variable("coins", VariableScope::Saved);
+        let text = TextCode::new()
+            .text("Hi ").placeholder(Placeholder::Default)
+            .text(", you have ").var(&coins)
+            .text(" coins (").math((Expr::var(&coins) + Expr::literal(1.0)) * Expr::literal(2.5)).text(") and ")
+            .index(&local("items"), TextCode::new().text("1"));
+        let written = text.to_string();
+        assert_eq!(written, "Hi %default, you have %var(coins) coins (%math((%var(coins)+1)*2.5)) and %index(items,1)");
+        assert_eq!(parse(&written), Ok(text));
+    }
+
+    #[test]
+    fn test_variables() {
+        assert_eq!(parse("%var(score_%uuid) is 50% done").unwrap().variables(), Vec::<&str>::new());
+        assert_eq!(parse("%entry(stats,%var(key))").unwrap().variables(), vec!["stats", "key"]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse("%math(1+(2*3)"), Err(TextCodeError::Unbalanced { at: 5 }));
+        assert_eq!(parse("hello %nmae"), Err(TextCodeError::UnknownCode { code: String::from("nmae"), at: 6 }));
+        assert_eq!(parse("%index(list)"), Err(TextCodeError::Arguments { code: String::from("index"), at: 0 }));
+        assert_eq!(parse("%var"), Err(TextCodeError::Arguments { code: String::from("var"), at: 0 }));
+        assert_eq!(parse("%math(1+*2)"), Err(TextCodeError::Expression { at: 8 }));
+    }
+
+    #[test]
+    fn test_multibyte() {
+        // Multi-byte characters right before or after a code
+        let welcome = parse("§aWelcome %default!§r » %var(é)").unwrap();
+        assert_eq!(welcome.to_string(), "§aWelcome %default!§r » %var(é)");
+        assert_eq!(welcome.variables(), vec!["é"]);
+        assert_eq!(parse("§%nope"), Err(TextCodeError::UnknownCode { code: String::from("nope"), at: 2 }));
+        assert!(check_value(&Value::Text(Text(String::from("§aWelcome %default!")))).is_ok());
+        assert_eq!(parse("%math(1+é)"), Err(TextCodeError::Expression { at: 8 }));
+    }
+
+    #[test]
+    fn test_expressions() {
+        // Equal precedence on the right keeps its parentheses, a % before a letter is a code
+        assert_eq!(parse_expr("10-(4-%var(x)) % 3").unwrap().to_string(), "10-(4-%var(x))%3");
+        assert_eq!(Expr::var(&variable("coins", VariableScope::Saved)).to_code(), "%var(coins)");
+    }
+
+    #[test]
+    fn test_math_number() {
+        // A %math number survives templates
+        let items = local("items");
+        let line = CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            set(items.clone(), Value::Number(Number::from(2))),
+            Statement::Control { action: String::from("Wait"), parameters: ParamBuilder::new().param(Value::Number((Expr::var(&items) * Expr::literal(20.0)).into_number())).complete_unchecked() },
+        ]);
+        assert!(line.serialize().contains(r#"{"id":"num","data":{"name":"%math(%var(items)*20)"}}"#));
+        assert_eq!(parse_templates(&line.serialize()).unwrap()[0].serialize(), line.serialize());
+    }
+
+    #[test]
+    fn test_unknown_code_warning() {
+        let report = validate(&Program::new_from(vec![ CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            Statement::PlayerAction { action: String::from("SendMessage"), parameters: ParamBuilder::new().param(Value::Text(Text(String::from("%bogus")))).complete_unchecked(), selector: Selector::Default },
+        ]) ]));
+        assert_eq!(report.diagnostics.iter().map(|d| d.code).collect::<Vec<_>>(), vec!["W0401"]);
+    }
+}
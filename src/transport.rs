@@ -0,0 +1,94 @@
+use std::{fmt::Display, thread, time::Duration};
+
+use websocket::{ClientBuilder, OwnedMessage, sync::Client, stream::sync::NetworkStream};
+
+use crate::{code_line::CodeLine, diagnostics::Diagnostic, program::Program, serialization::json_escape};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Transport {
+    Recode, CodeClient
+}
+impl Transport {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "recode" => Some(Transport::Recode),
+            "codeclient" => Some(Transport::CodeClient),
+            _ => None
+        }
+    }
+    pub fn default_endpoint(&self) -> &'static str {
+        match self {
+            Transport::Recode => "ws://localhost:31371/codeutilities/item",
+            Transport::CodeClient => "ws://localhost:31375",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TransportError(pub String);
+impl TransportError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error("E0501", format!("could not send templates: {self}"))
+    }
+}
+impl Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Gives every line of the program to the player as a template item.
+// Returns how many templates were sent.
+pub fn send(program: &Program, transport: Transport, endpoint: Option<&str>) -> Result<usize, TransportError> {
+    let endpoint = endpoint.unwrap_or_else(|| transport.default_endpoint());
+    let mut client = ClientBuilder::new(endpoint)
+        .map_err(|e| TransportError(format!("invalid endpoint {endpoint}: {e}")))?
+        .connect(None)
+        .map_err(|e| TransportError(format!("could not connect to {endpoint}: {e}")))?;
+    if transport == Transport::CodeClient {
+        // CodeClient only hands out items once the player has run /auth in game
+        send_text(&mut client, String::from("scopes inventory"))?;
+        match client.recv_message() {
+            Ok(OwnedMessage::Text(t)) if t.trim() == "auth" => {}
+            Ok(m) => return Err(TransportError(format!("CodeClient refused the inventory scope: {m:?}"))),
+            Err(e) => return Err(TransportError(format!("lost connection to CodeClient: {e}"))),
+        }
+    }
+    for line in program.lines() {
+        send_text(&mut client, message(program, line, transport))?;
+        if transport == Transport::Recode {
+            client.recv_message().map_err(|e| TransportError(format!("lost connection to recode: {e}")))?;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    Ok(program.lines().len())
+}
+
+// What gets sent over the websocket for one line
+pub fn message(program: &Program, line: &CodeLine, transport: Transport) -> String {
+    let tag = program.template_tag(line);
+    match transport {
+        Transport::Recode => {
+            let item = format!(r#"{{"id":"minecraft:ender_chest","Count":1,"tag":{tag}}}"#);
+            format!(r#"{{"source":"Kindling","type":"nbt","data":"{}"}}"#, json_escape(&item))
+        }
+        Transport::CodeClient => format!(r#"give {{id:"minecraft:ender_chest",Count:1b,tag:{tag}}}"#),
+    }
+}
+
+fn send_text(client: &mut Client<Box<dyn NetworkStream + Send>>, message: String) -> Result<(), TransportError> {
+    client.send_message(&OwnedMessage::Text(message)).map_err(|e| TransportError(format!("failed to send template: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::Report;
+
+    #[test]
+    fn test_send_error() {
+        // Send failures come back as reports instead of panics
+        let report = Report::from(TransportError(String::from("connection refused")).to_diagnostic());
+        assert!(report.has_errors() && report.render(false).contains("error[E0501]: could not send templates: connection refused"));
+    }
+}
@@ -0,0 +1,290 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{assets::AssetError, catalog::{action_spec, ArgSpec, GameValueError, Target}, code_line::CodeLine, functions::FunctionSignature, program::Program, statements::Statement, values::{FunctionParam, Value, ValueType, Variable, VariableScope}};
+
+pub trait ParamSpec {
+    fn name(&self) -> &str;
+    fn value_type(&self) -> ValueType;
+    fn plural(&self) -> bool;
+    fn optional(&self) -> bool;
+}
+impl ParamSpec for ArgSpec {
+    fn name(&self) -> &str { self.name }
+    fn value_type(&self) -> ValueType { self.value_type }
+    fn plural(&self) -> bool { self.plural }
+    fn optional(&self) -> bool { self.optional }
+}
+impl ParamSpec for FunctionParam {
+    fn name(&self) -> &str { &self.name }
+    fn value_type(&self) -> ValueType { self.value_type }
+    fn plural(&self) -> bool { self.plural }
+    fn optional(&self) -> bool { self.optional }
+}
+impl<T: ParamSpec> ParamSpec for &T {
+    fn name(&self) -> &str { (*self).name() }
+    fn value_type(&self) -> ValueType { (*self).value_type() }
+    fn plural(&self) -> bool { (*self).plural() }
+    fn optional(&self) -> bool { (*self).optional() }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ArgMismatch {
+    TooFew { expected: usize, found: usize },
+    TooMany { expected: usize, found: usize },
+    Type { parameter: String, expected: ValueType, found: ValueType }
+}
+
+// Matches arguments left to right; plural parameters take as much as they can
+// while leaving one argument for every required parameter after them.
+pub fn match_args<P: ParamSpec>(params: &[P], args: &[ValueType]) -> Result<(), ArgMismatch> {
+    let required = params.iter().filter(|p| !p.optional()).count();
+    let mut index = 0;
+    for (pi, param) in params.iter().enumerate() {
+        let required_after = params[pi+1..].iter().filter(|p| !p.optional()).count();
+        let available = (args.len() - index).saturating_sub(required_after);
+        let take = if param.plural() { available } else { available.min(1) };
+        if take == 0 && !param.optional() {
+            return Err(ArgMismatch::TooFew { expected: required, found: args.len() });
+        }
+        for arg in &args[index..index+take] {
+            if !param.value_type().accepts(*arg) {
+                return Err(ArgMismatch::Type { parameter: String::from(param.name()), expected: param.value_type(), found: *arg });
+            }
+        }
+        index += take;
+    }
+    if index < args.len() {
+        return Err(ArgMismatch::TooMany { expected: index, found: args.len() });
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TypeErrorKind {
+    Arguments { action: String, mismatch: ArgMismatch },
+    MissingTarget { action: String },
+    Assignment { variable: String, declared: ValueType, found: ValueType },
+    GameValue(GameValueError),
+    Asset(AssetError)
+}
+#[derive(Debug, PartialEq)]
+pub struct TypeError {
+    pub line: String,
+    pub statement: usize,
+    pub kind: TypeErrorKind
+}
+impl Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (block {}): ", self.line, self.statement + 1)?;
+        match &self.kind {
+            TypeErrorKind::Arguments { action, mismatch: ArgMismatch::TooFew { expected, found } } =>
+                write!(f, "{action} needs at least {expected} argument(s), found {found}"),
+            TypeErrorKind::Arguments { action, mismatch: ArgMismatch::TooMany { expected, found } } =>
+                write!(f, "{action} takes at most {expected} argument(s) here, found {found}"),
+            TypeErrorKind::Arguments { action, mismatch: ArgMismatch::Type { parameter, expected, found } } =>
+                write!(f, "{action} expects {expected} for {parameter}, found {found}"),
+            TypeErrorKind::MissingTarget { action } =>
+                write!(f, "{action} needs a variable to store its result in"),
+            TypeErrorKind::Assignment { variable, declared, found } =>
+                write!(f, "variable {variable} is declared as {declared} but is assigned {found}"),
+            TypeErrorKind::GameValue(e) => write!(f, "{e}"),
+            TypeErrorKind::Asset(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+pub struct LineTypes {
+    pub types: HashMap<Variable, ValueType>,
+    pub errors: Vec<TypeError>
+}
+
+pub struct TypeChecker<'a> {
+    program: &'a Program,
+    functions: HashMap<&'a str, FunctionSignature<'a>>,
+    declared: HashMap<Variable, ValueType>
+}
+impl<'a> TypeChecker<'a> {
+    pub fn new(program: &'a Program) -> Self {
+        Self {
+            program,
+            functions: program.lines().iter()
+                .filter_map(|l| l.body().first())
+                .filter_map(FunctionSignature::of)
+                .map(|s| (s.name, s))
+                .collect(),
+            declared: HashMap::new()
+        }
+    }
+    pub fn program(&self) -> &'a Program {
+        self.program
+    }
+    pub fn declare(&mut self, var: Variable, value_type: ValueType) {
+        self.declared.insert(var, value_type);
+    }
+    pub fn check(&self) -> Vec<TypeError> {
+        self.program.lines().iter().flat_map(|l| self.check_line(l).errors).collect()
+    }
+    // Types are inferred in block order. A variable that is assigned two different
+    // types within a line becomes Any rather than guessing which one is live.
+    pub fn check_line(&self, line: &CodeLine) -> LineTypes {
+        let mut out = LineTypes { types: HashMap::new(), errors: vec![] };
+        if let Some(sig) = line.body().first().and_then(FunctionSignature::of) {
+            for p in sig.params {
+                let ty = if p.plural { ValueType::List } else { p.value_type };
+                // Referenced as parameters, or as locals in code written before that scope existed
+                out.types.insert(Variable { name: p.name.clone(), scope: VariableScope::Parameter }, ty);
+                out.types.insert(Variable { name: p.name.clone(), scope: VariableScope::Local }, ty);
+            }
+        }
+        for (index, stmnt) in line.body().iter().enumerate() {
+            for value in stmnt.parameters().into_iter().flatten().flatten() {
+                let kind = match value {
+                    Value::GameValue(g) => g.check().err().map(TypeErrorKind::GameValue),
+                    Value::Sound(s) => s.check().err().map(TypeErrorKind::Asset),
+                    Value::Particle(p) => p.check().err().map(TypeErrorKind::Asset),
+                    Value::Potion(p) => p.check().err().map(TypeErrorKind::Asset),
+                    _ => None
+                };
+                if let Some(kind) = kind {
+                    out.errors.push(TypeError { line: line.name(), statement: index, kind });
+                }
+            }
+            let kind = match stmnt {
+                Statement::CallFunction { name, parameters } => {
+                    let Some(sig) = self.functions.get(name.as_str()) else { continue };
+                    let args: Vec<ValueType> = parameters.iter()
+                        .flatten()
+                        .filter(|a| !matches!(a, Value::Tag(_)))
+                        .map(|a| self.type_of(&out.types, a))
+                        .collect();
+                    match_args(&sig.params, &args).err().map(|mismatch| TypeErrorKind::Arguments { action: stmnt.name(), mismatch })
+                }
+                _ => self.check_action(stmnt, &mut out.types)
+            };
+            if let Some(kind) = kind {
+                out.errors.push(TypeError { line: line.name(), statement: index, kind });
+            }
+        }
+        out
+    }
+    fn check_action(&self, stmnt: &Statement, types: &mut HashMap<Variable, ValueType>) -> Option<TypeErrorKind> {
+        let spec = action_spec(stmnt.technical_name(), stmnt.action()?)?;
+        let mut args: Vec<&Value> = stmnt.parameters()?.iter()
+            .flatten()
+            .filter(|a| !matches!(a, Value::Tag(_)))
+            .collect();
+        let takes_target = spec.takes_target(args.len());
+        let target = match args.first() {
+            Some(Value::Variable(v)) if takes_target => {
+                args.remove(0);
+                Some(v)
+            }
+            _ if spec.target == Target::Required => return Some(TypeErrorKind::MissingTarget { action: stmnt.name() }),
+            _ => None
+        };
+        let arg_types: Vec<ValueType> = args.iter().map(|a| self.type_of(types, a)).collect();
+        if let Err(mismatch) = match_args(spec.args, &arg_types) {
+            return Some(TypeErrorKind::Arguments { action: stmnt.name(), mismatch });
+        }
+        let target = target?;
+        let assigned = spec.result.unwrap_or_else(|| arg_types.first().copied().unwrap_or(ValueType::Any));
+        if let Some(declared) = self.declared.get(target) {
+            if !declared.accepts(assigned) {
+                return Some(TypeErrorKind::Assignment { variable: target.name.clone(), declared: *declared, found: assigned });
+            }
+            return None;
+        }
+        types.entry(target.clone())
+            .and_modify(|t| if *t != assigned { *t = ValueType::Any })
+            .or_insert(assigned);
+        None
+    }
+    pub fn type_of(&self, types: &HashMap<Variable, ValueType>, value: &Value) -> ValueType {
+        match value {
+            Value::Variable(v) => self.declared.get(v).or_else(|| types.get(v)).copied().unwrap_or(ValueType::Any),
+            _ => value.value_type()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{catalog::Subject, params::ParamBuilder, testing::{local, set, set_var, var, variable}, values::{GameValue, Location, Selector, Text}};
+
+    #[test]
+    fn test_type_inference() {
+        let p = Program::new_from(vec![
+            CodeLine::new_from(vec![
+                Statement::PlayerEvent(String::from("Join")),
+                set(local("spawn"), Value::Location(Location::new(0.0, 64.0, 0.0))),
+                set_var("Distance", local("d"), vec![var("spawn"), var("spawn")]),
+                Statement::PlayerAction { action: String::from("SetHealth"), parameters: ParamBuilder::new().param(var("d")).complete_unchecked(), selector: Selector::Default },
+                Statement::PlayerAction { action: String::from("SetHealth"), parameters: ParamBuilder::new().param(var("spawn")).complete_unchecked(), selector: Selector::Default },
+                set_var("+", local("total"), vec![Value::Text(Text(String::from("1")))]),
+            ])
+        ]);
+        let checker = TypeChecker::new(&p);
+        let line = checker.check_line(&p.lines()[0]);
+        assert_eq!(line.types.get(&local("spawn")), Some(&ValueType::Location));
+        assert_eq!(line.types.get(&local("d")), Some(&ValueType::Number));
+        assert_eq!(line.errors, vec![
+            TypeError { line: String::from("Player Event: Join"), statement: 4, kind: TypeErrorKind::Arguments {
+                action: String::from("Player Action: SetHealth"),
+                mismatch: ArgMismatch::Type { parameter: String::from("health"), expected: ValueType::Number, found: ValueType::Location }
+            } },
+            TypeError { line: String::from("Player Event: Join"), statement: 5, kind: TypeErrorKind::Arguments {
+                action: String::from("Set Variable Action: +"),
+                mismatch: ArgMismatch::Type { parameter: String::from("numbers"), expected: ValueType::Number, found: ValueType::Text }
+            } },
+        ]);
+    }
+
+    #[test]
+    fn test_declared_types() {
+        let score = variable("score", VariableScope::Saved);
+        let p = Program::new_from(vec![
+            CodeLine::new_from(vec![
+                Statement::PlayerEvent(String::from("Join")),
+                set(score.clone(), Value::Text(Text(String::from("none")))),
+                Statement::PlayerAction { action: String::from("SetHealth"), parameters: ParamBuilder::new().param(Value::Variable(score.clone())).complete_unchecked(), selector: Selector::Default },
+            ])
+        ]);
+        let mut checker = TypeChecker::new(&p);
+        checker.declare(score, ValueType::Number);
+        assert_eq!(checker.check(), vec![
+            TypeError { line: String::from("Player Event: Join"), statement: 1, kind: TypeErrorKind::Assignment {
+                variable: String::from("score"), declared: ValueType::Number, found: ValueType::Text
+            } },
+        ]);
+    }
+
+    #[test]
+    fn test_game_value_types() {
+        let gval = |name: &str, selector| Value::GameValue(GameValue { name: String::from(name), selector });
+        let p = Program::new_from(vec![ CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            set(local("here"), gval("Location", None)),
+            Statement::PlayerAction { action: String::from("Teleport"), parameters: ParamBuilder::new().param(var("here")).complete_unchecked(), selector: Selector::Default },
+            Statement::PlayerAction { action: String::from("SetHealth"), parameters: ParamBuilder::new().param(gval("Name", Some(Selector::Killer))).complete_unchecked(), selector: Selector::Default },
+            Statement::PlayerAction { action: String::from("SetHealth"), parameters: ParamBuilder::new().param(gval("Event Damage", Some(Selector::Killer))).complete_unchecked(), selector: Selector::Default },
+        ]) ]);
+        let checker = TypeChecker::new(&p);
+        let line = checker.check_line(&p.lines()[0]);
+        assert_eq!(line.types.get(&local("here")), Some(&ValueType::Location));
+        assert!(matches!(&line.errors[..], [
+            TypeError { statement: 3, kind: TypeErrorKind::Arguments { mismatch: ArgMismatch::Type { expected: ValueType::Number, found: ValueType::Text, .. }, .. }, .. },
+            TypeError { statement: 4, kind: TypeErrorKind::GameValue(GameValueError::Target { subject: Subject::Event, .. }), .. },
+        ]));
+    }
+
+    #[test]
+    fn test_line_variable_types() {
+        let p = Program::new_from(vec![ CodeLine::new_from(vec![
+            Statement::Function { name: String::from("heal"), parameters: ParamBuilder::new().param(Value::Parameter(FunctionParam::new("amount", ValueType::Number))).complete_unchecked() },
+            set(variable("scratch", VariableScope::Line), Value::Variable(variable("amount", VariableScope::Parameter))),
+        ]) ]);
+        let types = TypeChecker::new(&p).check_line(&p.lines()[0]);
+        assert_eq!(types.types.get(&variable("scratch", VariableScope::Line)), Some(&ValueType::Number));
+    }
+}
@@ -0,0 +1,268 @@
+use std::ops::Range;
+
+use crate::{assets::AssetError, component::ComponentError, catalog::{GameValueError, Subject}, serialization::DFSerializable, callgraph::CallGraph, cfg::Cfg, code_line::CodeLine, dataflow::{dead_stores, uses_before_assignment, FlowWarning, Liveness}, diagnostics::{Diagnostic, Report, SourceId, SourceMap, Span}, program::Program, statements::Statement, textcode::{check_value, TextCodeError}, typeck::{ArgMismatch, TypeChecker, TypeError, TypeErrorKind}, values::Value};
+
+// There's no source text behind a Program, so diagnostics point into a listing
+// of each line with one block per row.
+pub struct LineListing {
+    pub source: SourceId,
+    pub blocks: Vec<Range<usize>>
+}
+impl LineListing {
+    pub fn new(sources: &mut SourceMap, index: usize, line: &CodeLine) -> Self {
+        let mut text = String::new();
+        let mut blocks = vec![];
+        let mut depth: usize = 0;
+        for stmnt in line.body() {
+            if matches!(stmnt, Statement::Close | Statement::CloseRepeat) {
+                depth = depth.saturating_sub(1);
+            }
+            text += &"  ".repeat(depth);
+            let start = text.len();
+            text += &match stmnt {
+                Statement::CloseRepeat => String::from("Close Repeat Bracket"),
+                s => s.name()
+            };
+            blocks.push(start..text.len());
+            text += "\n";
+            if opens_bracket(stmnt) {
+                depth += 1;
+            }
+        }
+        Self { source: sources.add(&format!("line {} ({})", index + 1, line.name()), text), blocks }
+    }
+    pub fn span(&self, statement: usize) -> Span {
+        Span { source: self.source, range: self.blocks.get(statement).cloned().unwrap_or(0..0) }
+    }
+}
+
+pub fn opens_bracket(stmnt: &Statement) -> bool {
+    matches!(stmnt,
+        Statement::IfVariable { .. } | Statement::IfPlayer { .. } | Statement::IfEntity { .. } | Statement::IfGame { .. } |
+        Statement::Else | Statement::Repeat { .. })
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BracketError {
+    Unclosed { open: usize },
+    UnexpectedClose { close: usize },
+    Mismatched { open: usize, close: usize, repeat: bool },
+    ElseWithoutIf { at: usize }
+}
+
+pub fn check_brackets(line: &CodeLine) -> Vec<BracketError> {
+    let mut errors = vec![];
+    let mut stack: Vec<usize> = vec![];
+    let mut after_if = false;
+    for (index, stmnt) in line.body().iter().enumerate() {
+        let closed_if = match stmnt {
+            Statement::Close | Statement::CloseRepeat => match stack.pop() {
+                None => {
+                    errors.push(BracketError::UnexpectedClose { close: index });
+                    false
+                }
+                Some(open) => {
+                    let opener = &line.body()[open];
+                    let repeat = matches!(opener, Statement::Repeat { .. });
+                    if repeat != matches!(stmnt, Statement::CloseRepeat) {
+                        errors.push(BracketError::Mismatched { open, close: index, repeat });
+                    }
+                    !repeat && !matches!(opener, Statement::Else)
+                }
+            },
+            Statement::Else if !after_if => {
+                errors.push(BracketError::ElseWithoutIf { at: index });
+                false
+            }
+            _ => false
+        };
+        if opens_bracket(stmnt) {
+            stack.push(index);
+        }
+        after_if = closed_if;
+    }
+    errors.extend(stack.into_iter().map(|open| BracketError::Unclosed { open }));
+    errors
+}
+
+impl BracketError {
+    pub fn to_diagnostic(&self, listing: &LineListing) -> Diagnostic {
+        match *self {
+            BracketError::Unclosed { open } => Diagnostic::error("E0001", String::from("unclosed bracket"))
+                .primary(listing.span(open), "this block is never closed"),
+            BracketError::UnexpectedClose { close } => Diagnostic::error("E0002", String::from("unexpected closing bracket"))
+                .primary(listing.span(close), "there is no open bracket to close"),
+            BracketError::Mismatched { open, close, repeat } => {
+                let expected = if repeat { "Close Repeat Bracket" } else { "Close Bracket" };
+                Diagnostic::error("E0003", String::from("mismatched closing bracket"))
+                    .primary(listing.span(close), "closes the wrong kind of bracket")
+                    .secondary(listing.span(open), "opened here")
+                    .suggest(listing.span(close), "use the matching bracket", expected)
+            }
+            BracketError::ElseWithoutIf { at } => Diagnostic::error("E0004", String::from("else without a preceding if"))
+                .primary(listing.span(at), "must come directly after the closing bracket of an if")
+        }
+    }
+}
+
+impl TypeError {
+    pub fn to_diagnostic(&self, listing: &LineListing) -> Diagnostic {
+        let span = listing.span(self.statement);
+        match &self.kind {
+            TypeErrorKind::Arguments { action, mismatch: ArgMismatch::TooFew { expected, found } } =>
+                Diagnostic::error("E0101", format!("too few arguments for {action}"))
+                    .primary(span, &format!("expected at least {expected}, found {found}")),
+            TypeErrorKind::Arguments { action, mismatch: ArgMismatch::TooMany { expected, found } } =>
+                Diagnostic::error("E0102", format!("too many arguments for {action}"))
+                    .primary(span, &format!("expected at most {expected}, found {found}")),
+            TypeErrorKind::Arguments { action, mismatch: ArgMismatch::Type { parameter, expected, found } } =>
+                Diagnostic::error("E0103", format!("mismatched types in {action}"))
+                    .primary(span, &format!("expected {expected}, found {found}"))
+                    .note(format!("argument `{parameter}` takes a {expected}")),
+            TypeErrorKind::MissingTarget { action } =>
+                Diagnostic::error("E0104", format!("{action} has no variable to store its result in"))
+                    .primary(span, "missing target variable")
+                    .note(String::from("the first argument of this block must be a variable")),
+            TypeErrorKind::Assignment { variable, declared, found } =>
+                Diagnostic::error("E0105", format!("cannot assign {found} to `{variable}`"))
+                    .primary(span, &format!("assigns a {found}"))
+                    .note(format!("`{variable}` is declared as {declared}")),
+            TypeErrorKind::GameValue(GameValueError::Unknown { name }) =>
+                Diagnostic::error("E0106", format!("unknown game value `{name}`"))
+                    .primary(span, "not a DiamondFire game value"),
+            TypeErrorKind::GameValue(GameValueError::Target { name, subject, target }) =>
+                Diagnostic::error("E0107", format!("`{name}` can't be read from {}", target.serialize()))
+                    .primary(span, "invalid target for this game value")
+                    .note(match subject {
+                        Subject::Player => String::from("only players have this value"),
+                        Subject::Entity => String::from("this value needs an entity target"),
+                        s => format!("{s} values always use the Default target")
+                    }),
+            TypeErrorKind::Asset(e @ AssetError::Range { .. }) =>
+                Diagnostic::error("E0109", format!("{e}"))
+                    .primary(span, "value out of range"),
+            TypeErrorKind::Asset(AssetError::Field { particle, field }) =>
+                Diagnostic::error("E0111", format!("{particle} doesn't take a {field}"))
+                    .primary(span, "unsupported particle data"),
+            TypeErrorKind::Asset(e) => {
+                let d = Diagnostic::error("E0108", match e {
+                    AssetError::UnknownSound { name, .. } => format!("unknown sound `{name}`"),
+                    AssetError::UnknownVariant { sound, variant, .. } => format!("`{sound}` has no variant `{variant}`"),
+                    AssetError::UnknownParticle { name, .. } => format!("unknown particle `{name}`"),
+                    AssetError::UnknownEffect { name, .. } => format!("unknown potion effect `{name}`"),
+                    _ => e.to_string()
+                }).primary(span, "not in DiamondFire's catalog");
+                match e.suggestion() {
+                    Some(s) => d.note(format!("did you mean `{s}`?")),
+                    None => d
+                }
+            }
+        }
+    }
+}
+
+impl ComponentError {
+    pub fn to_diagnostic(&self, span: Span) -> Diagnostic {
+        Diagnostic::error("E0110", format!("invalid styled text: {self}"))
+            .primary(span, "not valid MiniMessage")
+    }
+}
+
+impl TextCodeError {
+    pub fn to_diagnostic(&self, span: Span) -> Diagnostic {
+        // DiamondFire leaves broken codes in the text as they are
+        Diagnostic::warning("W0401", format!("invalid text code: {self}"))
+            .primary(span, "this block's text would show the code literally")
+    }
+}
+
+impl FlowWarning {
+    pub fn to_diagnostic(&self, listing: &LineListing) -> Diagnostic {
+        match self {
+            FlowWarning::UseBeforeAssignment { statement, variable } =>
+                Diagnostic::warning("W0301", format!("`{variable}` may be read before it is assigned"))
+                    .primary(listing.span(*statement), "read here")
+                    .note(String::from("an event's locals and a line's own line variables start out unset, so this reads 0 or an empty value")),
+            FlowWarning::DeadStore { statement, variable } =>
+                Diagnostic::warning("W0302", format!("value assigned to `{variable}` is never read"))
+                    .primary(listing.span(*statement), "overwritten or dropped before any read"),
+        }
+    }
+}
+
+pub fn validate(program: &Program) -> Report {
+    validate_with(&TypeChecker::new(program))
+}
+
+pub fn validate_with(checker: &TypeChecker) -> Report {
+    let program = checker.program();
+    let mut sources = SourceMap::new();
+    let listings: Vec<LineListing> = program.lines().iter()
+        .enumerate()
+        .map(|(i, l)| LineListing::new(&mut sources, i, l))
+        .collect();
+    let mut report = Report::new(sources);
+    for (line, listing) in program.lines().iter().zip(&listings) {
+        for e in check_brackets(line) {
+            report.push(e.to_diagnostic(listing));
+        }
+        for e in checker.check_line(line).errors {
+            report.push(e.to_diagnostic(listing));
+        }
+        for (index, stmnt) in line.body().iter().enumerate() {
+            for value in stmnt.parameters().into_iter().flatten().flatten() {
+                if let Value::Component(c) = value {
+                    if let Err(e) = c.check() {
+                        report.push(e.to_diagnostic(listing.span(index)));
+                    }
+                }
+                if let Err(e) = check_value(value) {
+                    report.push(e.to_diagnostic(listing.span(index)));
+                }
+            }
+        }
+        if check_brackets(line).is_empty() {
+            let cfg = Cfg::build(line);
+            let liveness = Liveness::compute(line, &cfg);
+            for w in uses_before_assignment(line, &cfg).into_iter().chain(dead_stores(line, &liveness)) {
+                report.push(w.to_diagnostic(listing));
+            }
+        }
+    }
+    let graph = CallGraph::build(program);
+    for cycle in graph.cycles() {
+        let names: Vec<&str> = cycle.iter().map(|n| graph.nodes[*n].name.as_str()).collect();
+        let mut calls = graph.edges.iter().filter(|e| !e.process && cycle.contains(&e.from) && cycle.contains(&e.to));
+        let first = calls.next().unwrap();
+        let mut diagnostic = Diagnostic::warning("W0201", format!("recursion through {}", names.join(", ")))
+            .primary(listings[first.line].span(first.statement), "this call can come back around to itself");
+        for e in calls {
+            diagnostic = diagnostic.secondary(listings[e.line].span(e.statement), "part of the cycle");
+        }
+        report.push(diagnostic.note(String::from("DiamondFire limits how deeply functions can call each other")));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::ParamBuilder;
+
+    #[test]
+    fn test_brackets() {
+        let line = CodeLine::new_from(vec![
+            Statement::PlayerEvent(String::from("Join")),
+            Statement::Else,
+            Statement::Close,
+            Statement::Repeat { action: String::from("Forever"), subaction: None, parameters: ParamBuilder::new().complete_unchecked(), not: false },
+            Statement::Close,
+            Statement::IfGame { action: String::from("EventCancelled"), parameters: ParamBuilder::new().complete_unchecked(), not: false },
+        ]);
+        assert_eq!(check_brackets(&line), vec![
+            BracketError::ElseWithoutIf { at: 1 },
+            BracketError::Mismatched { open: 3, close: 4, repeat: true },
+            BracketError::Unclosed { open: 5 },
+        ]);
+    }
+}
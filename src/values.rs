@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 
-use crate::serialization::DFSerializable;
+use crate::{decimal::Decimal, serialization::DFSerializable, textcode::Expr};
 
 pub enum Value {
     Text(Text),
+    Component(Component),
     Number(Number),
     Location(Location),
     Vector(Vector),
@@ -13,23 +14,121 @@ pub enum Value {
     Variable(Variable),
     GameValue(GameValue),
     Item(Item),
-    Tag(Tag)
+    Tag(Tag),
+    Parameter(FunctionParam)
+}
+impl Value {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Value::Text(_) => ValueType::Text,
+            Value::Component(_) => ValueType::Component,
+            Value::Number(_) => ValueType::Number,
+            Value::Location(_) => ValueType::Location,
+            Value::Vector(_) => ValueType::Vector,
+            Value::Sound(_) => ValueType::Sound,
+            Value::Particle(_) => ValueType::Particle,
+            Value::Potion(_) => ValueType::Potion,
+            Value::Item(_) => ValueType::Item,
+            Value::GameValue(g) => g.spec().map_or(ValueType::Any, |s| s.result),
+            Value::Variable(_) | Value::Tag(_) | Value::Parameter(_) => ValueType::Any,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ValueType {
+    Text, Component, Number, Location, Vector, Sound, Particle, Potion, Item, List, Dict, Any
+}
+impl ValueType {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "txt" => Some(ValueType::Text),
+            "comp" => Some(ValueType::Component),
+            "num" => Some(ValueType::Number),
+            "loc" => Some(ValueType::Location),
+            "vec" => Some(ValueType::Vector),
+            "snd" => Some(ValueType::Sound),
+            "part" => Some(ValueType::Particle),
+            "pot" => Some(ValueType::Potion),
+            "item" => Some(ValueType::Item),
+            "list" => Some(ValueType::List),
+            "dict" => Some(ValueType::Dict),
+            "any" => Some(ValueType::Any),
+            _ => None
+        }
+    }
+    pub fn accepts(&self, other: ValueType) -> bool {
+        // Plain text is turned into styled text where that's expected
+        *self == ValueType::Any || other == ValueType::Any || *self == other ||
+            (*self == ValueType::Component && other == ValueType::Text)
+    }
+}
+impl DFSerializable for ValueType {
+    fn serialize(&self) -> String {
+        String::from(match self {
+            ValueType::Text => "txt",
+            ValueType::Component => "comp",
+            ValueType::Number => "num",
+            ValueType::Location => "loc",
+            ValueType::Vector => "vec",
+            ValueType::Sound => "snd",
+            ValueType::Particle => "part",
+            ValueType::Potion => "pot",
+            ValueType::Item => "item",
+            ValueType::List => "list",
+            ValueType::Dict => "dict",
+            ValueType::Any => "any",
+        })
+    }
+}
+impl std::fmt::Display for ValueType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ValueType::Text => "Text",
+            ValueType::Component => "Styled Text",
+            ValueType::Number => "Number",
+            ValueType::Location => "Location",
+            ValueType::Vector => "Vector",
+            ValueType::Sound => "Sound",
+            ValueType::Particle => "Particle",
+            ValueType::Potion => "Potion",
+            ValueType::Item => "Item",
+            ValueType::List => "List",
+            ValueType::Dict => "Dictionary",
+            ValueType::Any => "Any",
+        })
+    }
 }
 
-pub type DFNum = f32;
+pub type DFNum = f64;
 pub type DFNumPrecise = f64;
 pub type ParameterList = [Option<Value>; 27];
 
 
 pub struct Text(pub String);
-pub struct Number(pub DFNum);
+// MiniMessage, checked when it's made; see component.rs
+pub use crate::component::Component;
+pub enum Number {
+    Literal(Decimal),
+    // %math or another text code, see textcode.rs
+    Math(Expr)
+}
+impl From<i64> for Number {
+    fn from(n: i64) -> Self {
+        Number::Literal(Decimal::from(n))
+    }
+}
+// Constructors and helpers are in location.rs
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Location {
-    pub x: DFNum,
-    pub y: DFNum,
-    pub z: DFNum,
-    pub pitch: DFNum,
-    pub yaw: DFNum
+    pub x: DFNumPrecise,
+    pub y: DFNumPrecise,
+    pub z: DFNumPrecise,
+    pub pitch: DFNumPrecise,
+    pub yaw: DFNumPrecise,
+    pub is_block: bool
 }
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Vector {
     pub x: DFNumPrecise,
     pub y: DFNumPrecise,
@@ -50,63 +149,40 @@ pub struct Particle {
 
 }
 
+// Effects, durations and amplifiers are checked against the catalog in assets.rs
+pub use crate::assets::PotionEffect;
 pub struct Potion {
     pub effect: PotionEffect,
     pub ticks: u64,
     pub level: i16
 }
-pub enum PotionEffect {
-    Absorption, ConduitPower, DolphinGrace, FireResistance, Haste, HealthBoost, HeroOfTheVillage,
-    InstantHealth, Invisibility, JumpBoost, Luck, NightVision, Regeneration, Resistance, Saturation,
-    SlowFalling, Speed, Strength, WaterBreathing, BadLuck, BadOmen, Blindness, Darkness, Glowing,
-    Hunger, InstantDamage, Levitation, MiningFatigue, Nausea, Poison, Slowness, Weakness, Wither
-}
-impl DFSerializable for PotionEffect {
-    fn serialize(&self) -> String {
-        String::from(match self {
-            PotionEffect::Absorption => "Absorption",
-            PotionEffect::ConduitPower => "Conduit Power",
-            PotionEffect::DolphinGrace => "Dolphin's Grace",
-            PotionEffect::FireResistance => "Fire Resistance",
-            PotionEffect::Haste => "Haste",
-            PotionEffect::HealthBoost => "Health Boost",
-            PotionEffect::HeroOfTheVillage => "Hero of the Village",
-            PotionEffect::InstantHealth => "Instant Health",
-            PotionEffect::Invisibility => "Invisibility",
-            PotionEffect::JumpBoost => "Jump Boost",
-            PotionEffect::Luck => "Luck",
-            PotionEffect::NightVision => "Night Vision",
-            PotionEffect::Regeneration => "Regeneration",
-            PotionEffect::Resistance => "Resistance",
-            PotionEffect::Saturation => "Saturation",
-            PotionEffect::SlowFalling => "Slow Falling",
-            PotionEffect::Speed => "Speed",
-            PotionEffect::Strength => "Strength",
-            PotionEffect::WaterBreathing => "Water Breathing",
-            PotionEffect::BadLuck => "Bad Luck",
-            PotionEffect::BadOmen => "Bad Omen",
-            PotionEffect::Blindness => "Blindness",
-            PotionEffect::Darkness => "Darkness",
-            PotionEffect::Glowing => "Glowing",
-            PotionEffect::Hunger => "Hunger",
-            PotionEffect::InstantDamage => "Instant Damage",
-            PotionEffect::Levitation => "Levitation",
-            PotionEffect::MiningFatigue => "Mining Fatigue",
-            PotionEffect::Nausea => "Nausea",
-            PotionEffect::Poison => "Poison",
-            PotionEffect::Slowness => "Slowness",
-            PotionEffect::Weakness => "Weakness",
-            PotionEffect::Wither => "Wither",
-        })
-    }
-}
-
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Variable {
     pub name: String,
     pub scope: VariableScope
 }
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum VariableScope {
-    Local, Global, Saved
+    Local, Global, Saved,
+    // Only visible in its own line, not in functions the line calls
+    Line,
+    // A reference to one of the function's parameters, which are line variables in game
+    Parameter
+}
+impl VariableScope {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "local" => Some(VariableScope::Local),
+            "unsaved" => Some(VariableScope::Global),
+            "saved" => Some(VariableScope::Saved),
+            "line" => Some(VariableScope::Line),
+            _ => None
+        }
+    }
+    // Line and parameter variables can't be seen from any other line
+    pub fn is_line(&self) -> bool {
+        matches!(self, VariableScope::Line | VariableScope::Parameter)
+    }
 }
 impl DFSerializable for VariableScope {
     fn serialize(&self) -> String {
@@ -114,6 +190,7 @@ impl DFSerializable for VariableScope {
             VariableScope::Local => "local",
             VariableScope::Global => "unsaved",
             VariableScope::Saved => "saved",
+            VariableScope::Line | VariableScope::Parameter => "line",
         })
     }
 }
@@ -122,13 +199,26 @@ pub struct GameValue {
     pub name: String,
     pub selector: Option<Selector>
 }
-#[derive(Copy,Clone)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
 pub enum Selector {
-    Selection, Default, Killer, Damager, Victim, Shooter, Projectile, LastEntity, AllPlayers, AllEntities, AllMobs
+    Selection, #[default] Default, Killer, Damager, Victim, Shooter, Projectile, LastEntity, AllPlayers, AllEntities, AllMobs
 }
-impl Default for Selector {
-    fn default() -> Self {
-        Self::Default
+impl Selector {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "Selection" => Some(Selector::Selection),
+            "Default" => Some(Selector::Default),
+            "Killer" => Some(Selector::Killer),
+            "Damager" => Some(Selector::Damager),
+            "Victim" => Some(Selector::Victim),
+            "Shooter" => Some(Selector::Shooter),
+            "Projectile" => Some(Selector::Projectile),
+            "LastEntity" => Some(Selector::LastEntity),
+            "AllPlayers" => Some(Selector::AllPlayers),
+            "AllEntities" => Some(Selector::AllEntities),
+            "AllMobs" => Some(Selector::AllMobs),
+            _ => None
+        }
     }
 }
 impl DFSerializable for Selector {
@@ -149,8 +239,10 @@ impl DFSerializable for Selector {
     }
 }
 
+// Names, variants and ranges are checked against the catalog in assets.rs
 pub struct Sound {
-    pub sound: String, // Not adding an enum for every single sound
+    pub sound: String,
+    pub variant: Option<String>,
     pub pitch: DFNum,
     pub volume: DFNum
 }
@@ -167,6 +259,8 @@ pub struct Item {
     pub unbreakable: bool,
     pub string_tags: HashMap<String, String>,
     pub num_tags: HashMap<String, DFNum>,
+    // SNBT read from a template, written back exactly as it was
+    pub snbt: Option<String>
 }
 pub struct Attribute {
     pub uuid: String,
@@ -183,4 +277,46 @@ pub struct Tag {
     pub name: String,
     pub option: String,
     pub var: Option<Variable>
-}
\ No newline at end of file
+}
+
+// A parameter declaration on a Function block (the "pn_el" item)
+pub struct FunctionParam {
+    pub name: String,
+    pub value_type: ValueType,
+    pub plural: bool,
+    pub optional: bool,
+    pub default: Option<Box<Value>>
+}
+impl FunctionParam {
+    pub fn new(name: &str, value_type: ValueType) -> Self {
+        Self { name: String::from(name), value_type, plural: false, optional: false, default: None }
+    }
+    pub fn plural(mut self) -> Self {
+        self.plural = true;
+        self
+    }
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+    // DiamondFire only allows defaults on optional, non-plural parameters
+    pub fn with_default(mut self, v: Value) -> Option<Self> {
+        if self.plural || !self.value_type.accepts(v.value_type()) {
+            return None;
+        }
+        self.optional = true;
+        self.default = Some(Box::new(v));
+        Some(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_scope() {
+        assert_eq!(VariableScope::parse("line"), Some(VariableScope::Line));
+        assert_eq!(VariableScope::Parameter.serialize(), "line");
+    }
+}
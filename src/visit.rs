@@ -0,0 +1,343 @@
+use std::mem;
+
+use crate::{code_line::CodeLine, program::Program, statements::Statement, textcode, values::{Number, Text, Value, Variable}};
+
+// Read-only traversal. Override the methods for the nodes you care about and
+// call the matching walk_ function to keep going into their children.
+pub trait Visitor {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program);
+    }
+    fn visit_line(&mut self, line: &CodeLine) {
+        walk_line(self, line);
+    }
+    fn visit_statement(&mut self, stmnt: &Statement) {
+        walk_statement(self, stmnt);
+    }
+    fn visit_value(&mut self, value: &Value) {
+        walk_value(self, value);
+    }
+    // Variable values and the variables tags are bound to
+    fn visit_variable(&mut self, var: &Variable) {
+        walk_variable(self, var);
+    }
+    // Names read through %var, %index and %entry codes, whose scope isn't known
+    fn visit_code_name(&mut self, _name: &str) {}
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for line in program.lines() {
+        visitor.visit_line(line);
+    }
+}
+pub fn walk_line<V: Visitor + ?Sized>(visitor: &mut V, line: &CodeLine) {
+    for stmnt in line.body() {
+        visitor.visit_statement(stmnt);
+    }
+}
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, stmnt: &Statement) {
+    for value in stmnt.parameters().into_iter().flatten().flatten() {
+        visitor.visit_value(value);
+    }
+}
+pub fn walk_value<V: Visitor + ?Sized>(visitor: &mut V, value: &Value) {
+    match value {
+        Value::Variable(v) => visitor.visit_variable(v),
+        Value::Tag(t) => if let Some(v) = &t.var {
+            visitor.visit_variable(v);
+        },
+        Value::Text(Text(t)) => walk_codes(visitor, t),
+        Value::Component(c) => walk_codes(visitor, c.as_str()),
+        Value::Number(Number::Math(e)) => for name in e.variables() {
+            visitor.visit_code_name(name);
+        },
+        Value::Parameter(p) => if let Some(default) = &p.default {
+            visitor.visit_value(default);
+        },
+        _ => {}
+    }
+}
+// Codes inside the name, as in slot_%var(i)
+pub fn walk_variable<V: Visitor + ?Sized>(visitor: &mut V, var: &Variable) {
+    walk_codes(visitor, &var.name);
+}
+fn walk_codes<V: Visitor + ?Sized>(visitor: &mut V, text: &str) {
+    if let Ok(code) = textcode::parse(text) {
+        for name in code.variables() {
+            visitor.visit_code_name(name);
+        }
+    }
+}
+
+// In-place rewriting, same shape as Visitor
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program);
+    }
+    fn visit_line_mut(&mut self, line: &mut CodeLine) {
+        walk_line_mut(self, line);
+    }
+    fn visit_statement_mut(&mut self, stmnt: &mut Statement) {
+        walk_statement_mut(self, stmnt);
+    }
+    fn visit_value_mut(&mut self, value: &mut Value) {
+        walk_value_mut(self, value);
+    }
+    fn visit_variable_mut(&mut self, var: &mut Variable) {
+        walk_variable_mut(self, var);
+    }
+    // Change the name to rename what the code reads
+    fn visit_code_name_mut(&mut self, _name: &mut String) {}
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut Program) {
+    for line in program.lines_mut() {
+        visitor.visit_line_mut(line);
+    }
+}
+pub fn walk_line_mut<V: VisitorMut + ?Sized>(visitor: &mut V, line: &mut CodeLine) {
+    for stmnt in line.body_mut() {
+        visitor.visit_statement_mut(stmnt);
+    }
+}
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmnt: &mut Statement) {
+    for value in stmnt.parameters_mut().into_iter().flatten().flatten() {
+        visitor.visit_value_mut(value);
+    }
+}
+pub fn walk_value_mut<V: VisitorMut + ?Sized>(visitor: &mut V, value: &mut Value) {
+    match value {
+        Value::Variable(v) => visitor.visit_variable_mut(v),
+        Value::Tag(t) => if let Some(v) = &mut t.var {
+            visitor.visit_variable_mut(v);
+        },
+        Value::Text(Text(t)) => walk_codes_mut(visitor, t),
+        Value::Component(c) => {
+            let mut source = String::from(c.as_str());
+            walk_codes_mut(visitor, &mut source);
+            // A new name that would break the markup leaves the text as it was
+            let _ = c.set_source(source);
+        }
+        Value::Number(Number::Math(e)) => e.rename_variables(&mut |name| code_name_mut(visitor, name)),
+        Value::Parameter(p) => if let Some(default) = &mut p.default {
+            visitor.visit_value_mut(default);
+        },
+        _ => {}
+    }
+}
+pub fn walk_variable_mut<V: VisitorMut + ?Sized>(visitor: &mut V, var: &mut Variable) {
+    walk_codes_mut(visitor, &mut var.name);
+}
+fn code_name_mut<V: VisitorMut + ?Sized>(visitor: &mut V, name: &str) -> Option<String> {
+    let mut renamed = String::from(name);
+    visitor.visit_code_name_mut(&mut renamed);
+    (renamed != name).then_some(renamed)
+}
+fn walk_codes_mut<V: VisitorMut + ?Sized>(visitor: &mut V, text: &mut String) {
+    if let Some(renamed) = textcode::rename_in(text, &mut |name| code_name_mut(visitor, name)) {
+        *text = renamed;
+    }
+}
+
+// Rebuilding by value. A statement folds into any number of statements, so
+// folds can drop blocks or expand one into several.
+pub trait Fold {
+    fn fold_program(&mut self, program: Program) -> Program {
+        fold_program(self, program)
+    }
+    fn fold_line(&mut self, line: CodeLine) -> CodeLine {
+        fold_line(self, line)
+    }
+    fn fold_statement(&mut self, stmnt: Statement) -> Vec<Statement> {
+        vec![fold_statement(self, stmnt)]
+    }
+    fn fold_value(&mut self, value: Value) -> Value {
+        fold_value(self, value)
+    }
+    fn fold_variable(&mut self, var: Variable) -> Variable {
+        fold_variable(self, var)
+    }
+    fn fold_code_name(&mut self, name: String) -> String {
+        name
+    }
+}
+
+pub fn fold_program<F: Fold + ?Sized>(folder: &mut F, mut program: Program) -> Program {
+    let lines = mem::take(program.lines_mut());
+    *program.lines_mut() = lines.into_iter().map(|l| folder.fold_line(l)).collect();
+    program
+}
+pub fn fold_line<F: Fold + ?Sized>(folder: &mut F, mut line: CodeLine) -> CodeLine {
+    let body = mem::take(line.body_mut());
+    *line.body_mut() = body.into_iter().flat_map(|s| folder.fold_statement(s)).collect();
+    line
+}
+pub fn fold_statement<F: Fold + ?Sized>(folder: &mut F, mut stmnt: Statement) -> Statement {
+    for slot in stmnt.parameters_mut().into_iter().flatten() {
+        *slot = slot.take().map(|v| folder.fold_value(v));
+    }
+    stmnt
+}
+pub fn fold_value<F: Fold + ?Sized>(folder: &mut F, value: Value) -> Value {
+    match value {
+        Value::Variable(v) => Value::Variable(folder.fold_variable(v)),
+        Value::Tag(mut t) => {
+            t.var = t.var.map(|v| folder.fold_variable(v));
+            Value::Tag(t)
+        }
+        Value::Text(Text(t)) => Value::Text(Text(fold_codes(folder, t))),
+        Value::Component(mut c) => {
+            let source = fold_codes(folder, String::from(c.as_str()));
+            // A new name that would break the markup leaves the text as it was
+            let _ = c.set_source(source);
+            Value::Component(c)
+        }
+        Value::Number(Number::Math(mut e)) => {
+            e.rename_variables(&mut |name| fold_code_name(folder, name));
+            Value::Number(Number::Math(e))
+        }
+        Value::Parameter(mut p) => {
+            p.default = p.default.map(|d| Box::new(folder.fold_value(*d)));
+            Value::Parameter(p)
+        }
+        v => v
+    }
+}
+pub fn fold_variable<F: Fold + ?Sized>(folder: &mut F, mut var: Variable) -> Variable {
+    var.name = fold_codes(folder, var.name);
+    var
+}
+fn fold_code_name<F: Fold + ?Sized>(folder: &mut F, name: &str) -> Option<String> {
+    let renamed = folder.fold_code_name(String::from(name));
+    (renamed != name).then_some(renamed)
+}
+fn fold_codes<F: Fold + ?Sized>(folder: &mut F, text: String) -> String {
+    textcode::rename_in(&text, &mut |name| fold_code_name(folder, name)).unwrap_or(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{params::ParamBuilder, serialization::DFSerializable, testing::{local, set, var, variable}, textcode::Expr, values::{FunctionParam, Selector, Tag, ValueType, VariableScope}};
+
+    // A function whose parameter defaults to a variable, followed by an assignment and a Return
+    fn function() -> Program {
+        Program::new_from(vec![ CodeLine::new_from(vec![
+            Statement::Function { name: String::from("f"), parameters: ParamBuilder::new()
+                .param(Value::Parameter(FunctionParam::new("p", ValueType::Any).with_default(var("fallback")).unwrap()))
+                .complete_unchecked() },
+            set(local("a"), var("b")),
+            Statement::Control { action: String::from("Return"), parameters: ParamBuilder::new().complete_unchecked() },
+        ]) ])
+    }
+
+    // Names in text codes, a %math number, a code inside a variable name and a tag variable
+    fn codes() -> CodeLine {
+        CodeLine::new_from(vec![
+            Statement::PlayerAction { action: String::from("SendMessage"), parameters: ParamBuilder::new()
+                .param(Value::Text(Text(String::from("§a%var(coins) » %index(list,%var(i))"))))
+                .param(Value::Number((Expr::var(&local("n")) + Expr::literal(1.0)).into_number()))
+                .param(var("slot_%var(i)"))
+                .tag(Tag { name: String::from("Alignment Mode"), option: String::from("Regular"), var: Some(variable("mode", VariableScope::Line)) })
+                .complete_unchecked(), selector: Selector::Default },
+        ])
+    }
+
+    #[test]
+    fn test_visitor() {
+        struct CountVariables(usize);
+        impl Visitor for CountVariables {
+            fn visit_value(&mut self, value: &Value) {
+                if let Value::Variable(_) = value {
+                    self.0 += 1;
+                }
+                walk_value(self, value);
+            }
+        }
+        let mut count = CountVariables(0);
+        count.visit_program(&function());
+        assert_eq!(count.0, 3);
+    }
+
+    #[test]
+    fn test_fold() {
+        struct DropControls;
+        impl Fold for DropControls {
+            fn fold_statement(&mut self, stmnt: Statement) -> Vec<Statement> {
+                match stmnt {
+                    Statement::Control { .. } => vec![],
+                    s => vec![s]
+                }
+            }
+        }
+        let p = DropControls.fold_program(function());
+        assert_eq!(p.lines()[0].body().len(), 2);
+    }
+
+    #[test]
+    fn test_visitor_mut() {
+        struct Rename;
+        impl VisitorMut for Rename {
+            fn visit_value_mut(&mut self, value: &mut Value) {
+                if let Value::Variable(v) = value {
+                    v.name = format!("renamed.{}", v.name);
+                }
+                walk_value_mut(self, value);
+            }
+        }
+        let mut p = function();
+        Rename.visit_program_mut(&mut p);
+        assert!(p.lines()[0].serialize().contains(r#""name":"renamed.fallback""#));
+        assert!(p.lines()[0].serialize().contains(r#""name":"renamed.b""#));
+    }
+
+    #[test]
+    fn test_code_names() {
+        // Tag variables, %math numbers, text codes and codes inside names are walked too
+        #[derive(Default)]
+        struct Names(Vec<String>);
+        impl Visitor for Names {
+            fn visit_variable(&mut self, var: &Variable) {
+                self.0.push(var.name.clone());
+                walk_variable(self, var);
+            }
+            fn visit_code_name(&mut self, name: &str) {
+                self.0.push(format!("%{name}"));
+            }
+        }
+        let mut names = Names::default();
+        names.visit_line(&codes());
+        assert_eq!(names.0, ["%coins", "%list", "%i", "%n", "slot_%var(i)", "%i", "mode"]);
+    }
+
+    #[test]
+    fn test_code_names_mut() {
+        struct Upper;
+        impl VisitorMut for Upper {
+            fn visit_variable_mut(&mut self, var: &mut Variable) {
+                var.name = var.name.replace("slot", "SLOT");
+                walk_variable_mut(self, var);
+            }
+            fn visit_code_name_mut(&mut self, name: &mut String) {
+                *name = name.to_uppercase();
+            }
+        }
+        let mut upper = codes();
+        Upper.visit_line_mut(&mut upper);
+        let json = upper.serialize();
+        assert!(json.contains("§a%var(COINS) » %index(LIST,%var(I))") && json.contains("%math(%var(N)+1)") && json.contains(r#""name":"SLOT_%var(I)""#));
+    }
+
+    #[test]
+    fn test_fold_code_names() {
+        struct FoldNames;
+        impl Fold for FoldNames {
+            fn fold_code_name(&mut self, name: String) -> String {
+                format!("{name}2")
+            }
+        }
+        let folded = FoldNames.fold_line(codes()).serialize();
+        assert!(folded.contains("%var(coins2)") && folded.contains(r#""name":"slot_%var(i2)""#));
+        assert!(folded.contains(r#""name":"mode","scope":"line""#));
+    }
+}